@@ -0,0 +1,218 @@
+pub mod module {
+
+use crate::types::module::*;
+use crate::vec3::module::*;
+use crate::camera::module::*;
+use crate::rand::module::*;
+
+use std::f32::consts;
+use std::io::{Error, ErrorKind};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    CatmullRom // smooth curve through every key
+}
+
+// camera view at a point in time, measured in frames
+#[derive(Copy, Clone, Debug)]
+pub struct Keyframe {
+    pub time: f32,
+    pub view: View
+}
+
+pub struct CameraPath {
+    keys: Vec<Keyframe>,
+    interpolation: Interpolation
+}
+
+impl CameraPath {
+    // keys: at least one, in any order
+    pub fn new(keys: Vec<Keyframe>, interpolation: Interpolation) -> CameraPath {
+        assert!(!keys.is_empty(), "a camera path needs at least one keyframe");
+        let mut keys = keys;
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        CameraPath { keys, interpolation }
+    }
+
+    // Orbits view.lookfrom around view.lookat about the up axis, one
+    // full turn over frames frames, keeping height and distance.
+    pub fn turntable(view: &View, frames: u32) -> CameraPath {
+        // enough keys that catmull-rom stays close to the circle
+        let steps = 32;
+        let up = Vec3::unit_vector(view.vup);
+        let offset = view.lookfrom - view.lookat;
+        let height = Vec3::dot(&offset, &up) * up;
+        let radial = offset - height;
+        let side = Vec3::cross(&up, &radial);
+        let keys = (0..=steps).map(|k| {
+            let angle = 2. * consts::PI * k as f32 / steps as f32;
+            let mut v = *view;
+            v.lookfrom = view.lookat + height + angle.cos() * radial + angle.sin() * side;
+            Keyframe { time: frames as f32 * k as f32 / steps as f32, view: v }
+        }).collect();
+        CameraPath::new(keys, Interpolation::CatmullRom)
+    }
+
+    pub fn end_time(&self) -> f32 {
+        self.keys[self.keys.len() - 1].time
+    }
+
+    // view at time, holding the first and last keys outside their range
+    pub fn view_at(&self, time: f32) -> View {
+        let keys = &self.keys;
+        let last = keys.len() - 1;
+        if time <= keys[0].time {
+            return keys[0].view;
+        }
+        if time >= keys[last].time {
+            return keys[last].view;
+        }
+        // keys[i].time <= time < keys[i + 1].time
+        let i = keys.iter().rposition(|k| k.time <= time).unwrap();
+        let (k1, k2) = (&keys[i], &keys[i + 1]);
+        let t = (time - k1.time) / (k2.time - k1.time);
+
+        match self.interpolation {
+            Interpolation::Linear => lerp_view(&k1.view, &k2.view, t),
+            Interpolation::CatmullRom => {
+                // repeat the end keys as the outer control points
+                let k0 = &keys[i.saturating_sub(1)];
+                let k3 = &keys[(i + 2).min(last)];
+                catmull_rom_view(&k0.view, &k1.view, &k2.view, &k3.view, t)
+            }
+        }
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn lerp_view(a: &View, b: &View, t: f32) -> View {
+    View {
+        lookfrom: a.lookfrom + t * (b.lookfrom - a.lookfrom),
+        lookat: a.lookat + t * (b.lookat - a.lookat),
+        vup: a.vup + t * (b.vup - a.vup),
+        vfov: lerp(a.vfov, b.vfov, t),
+        aperature: lerp(a.aperature, b.aperature, t),
+        focus_dist: lerp(a.focus_dist, b.focus_dist, t)
+    }
+}
+
+// uniform catmull-rom segment between p1 and p2
+fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * ((2. * p1) +
+           (-p0 + p2) * t +
+           (2. * p0 - 5. * p1 + 4. * p2 - p3) * t2 +
+           (-p0 + 3. * p1 - 3. * p2 + p3) * t3)
+}
+
+fn catmull_rom_vec(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    Vec3::new(catmull_rom(p0.x(), p1.x(), p2.x(), p3.x(), t),
+              catmull_rom(p0.y(), p1.y(), p2.y(), p3.y(), t),
+              catmull_rom(p0.z(), p1.z(), p2.z(), p3.z(), t))
+}
+
+fn catmull_rom_view(a: &View, b: &View, c: &View, d: &View, t: f32) -> View {
+    // overshoot must not make these negative
+    let positive = |f: &dyn Fn(&View) -> f32| {
+        catmull_rom(f(a), f(b), f(c), f(d), t).max(0.)
+    };
+    View {
+        lookfrom: catmull_rom_vec(a.lookfrom, b.lookfrom, c.lookfrom, d.lookfrom, t),
+        lookat: catmull_rom_vec(a.lookat, b.lookat, c.lookat, d.lookat, t),
+        vup: catmull_rom_vec(a.vup, b.vup, c.vup, d.vup, t),
+        vfov: positive(&|v| v.vfov),
+        aperature: positive(&|v| v.aperature),
+        focus_dist: positive(&|v| v.focus_dist)
+    }
+}
+
+// Parses keyframes, one per line:
+//   time  from_x from_y from_z  at_x at_y at_z  up_x up_y up_z  vfov focus_dist aperature
+// Blank lines and lines starting with # are skipped.
+pub fn parse_keyframes(text: &str) -> std::io::Result<Vec<Keyframe>> {
+    let mut keys = Vec::new();
+    for (line_num, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let bad = || Error::new(ErrorKind::InvalidData,
+                                format!("keyframe line {}: expected 13 numbers", line_num + 1));
+        let values: Vec<f32> = line.split_whitespace()
+            .map(|v| v.parse::<f32>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| bad())?;
+        if values.len() != 13 {
+            return Err(bad());
+        }
+        if values.iter().any(|v| !v.is_finite()) {
+            return Err(Error::new(ErrorKind::InvalidData,
+                                  format!("keyframe line {}: numbers must be finite", line_num + 1)));
+        }
+        keys.push(Keyframe {
+            time: values[0],
+            view: View {
+                lookfrom: Point::new(values[1], values[2], values[3]),
+                lookat: Point::new(values[4], values[5], values[6]),
+                vup: Vec3::new(values[7], values[8], values[9]),
+                vfov: values[10],
+                focus_dist: values[11],
+                aperature: values[12]
+            }
+        });
+    }
+    if keys.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidData, "no keyframes found"));
+    }
+    Ok(keys)
+}
+
+pub fn load_keyframes(path: &str) -> std::io::Result<Vec<Keyframe>> {
+    parse_keyframes(&std::fs::read_to_string(path)?)
+}
+
+// seed for one frame so any frame can be rerendered on its own
+pub fn frame_seed(seed: u64, frame: u32) -> u64 {
+    hash_values(&[seed, frame as u64])
+}
+
+// Fills in the frame number: a run of #s in pattern becomes the
+// zero padded number, e.g., "out_####.ppm" -> "out_0012.ppm".  Without
+// #s the number goes before the extension.
+pub fn frame_path(pattern: &str, frame: u32) -> String {
+    if let Some(start) = pattern.find('#') {
+        let width = pattern[start..].chars().take_while(|&c| c == '#').count();
+        return format!("{}{:0width$}{}", &pattern[..start], frame,
+                       &pattern[start + width..], width = width);
+    }
+    // the extension's dot is in the file name, after its first character
+    let name = pattern.rfind('/').map_or(0, |slash| slash + 1);
+    match pattern[name..].rfind('.').filter(|&dot| dot > 0).map(|dot| name + dot) {
+        Some(dot) => format!("{}_{:04}{}", &pattern[..dot], frame, &pattern[dot..]),
+        None => format!("{}_{:04}", pattern, frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_numbers_go_into_the_file_name() {
+        assert_eq!(frame_path("out_####.ppm", 12), "out_0012.ppm");
+        assert_eq!(frame_path("out_##.ppm", 123), "out_123.ppm");
+        assert_eq!(frame_path("out.ppm", 7), "out_0007.ppm");
+        assert_eq!(frame_path("out", 7), "out_0007");
+        // dots in directories and leading dots aren't extensions
+        assert_eq!(frame_path("renders.v2/out", 7), "renders.v2/out_0007");
+        assert_eq!(frame_path("./out.ppm", 7), "./out_0007.ppm");
+        assert_eq!(frame_path("renders/.out", 7), "renders/.out_0007");
+    }
+}
+
+}
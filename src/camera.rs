@@ -4,25 +4,189 @@ use crate::vec3::module::*;
 use crate::types::module::*;
 use crate::ray::module::*;
 use crate::rand::module::*;
+use crate::distribution::module::*;
+use crate::ppm::module::*;
 
 use std::f32::consts;
+use std::io::{Error, ErrorKind};
 
-pub struct Camera {
+pub trait Camera {
+    // s, t: position on the image in [0, 1], (0, 0) at the bottom left
+    // lens: sample in [0,1)^2 for cameras with a lens
+    // Returns None where the image isn't covered, e.g., outside a
+    // fisheye's circle.
+    fn get_ray(&self, s: f32, t: f32, lens: (f32, f32)) -> Option<Ray>;
+
+    // scale from scene radiance to image values
+    fn exposure(&self) -> f32 {
+        1.
+    }
+}
+
+// where a camera is and what it looks at, independent of the projection
+#[derive(Copy, Clone, Debug)]
+pub struct View {
+    pub lookfrom: Point,
+    pub lookat: Point,
+    pub vup: Vec3,
+    pub vfov: f32,       // field of view in degrees
+    pub aperature: f32,  // lens diameter
+    pub focus_dist: f32
+}
+
+impl View {
+    // camera frame: w points back from lookat, u right, v up
+    pub fn basis(&self) -> (Vec3, Vec3, Vec3) {
+        let w = Vec3::unit_vector(self.lookfrom - self.lookat);
+        let u = Vec3::unit_vector(Vec3::cross(&self.vup, &w));
+        let v = Vec3::cross(&w, &u);
+        (u, v, w)
+    }
+}
+
+pub const CAMERA_NAMES: [&str; 4] = ["perspective", "orthographic", "fisheye", "equirectangular"];
+
+// builds any of the projections for the same view
+// aspect: width / height
+pub fn camera_by_name(name: &str, view: &View, aspect: f32) -> Option<Box<dyn Camera>> {
+    let camera: Box<dyn Camera> = match name {
+        "perspective"     => Box::new(ThinLensCamera::from_view(view, aspect)),
+        "orthographic"    => Box::new(OrthographicCamera::from_view(view, aspect)),
+        "fisheye"         => Box::new(FisheyeCamera::from_view(view, aspect)),
+        "equirectangular" => Box::new(EquirectangularCamera::from_view(view)),
+        _ => return None
+    };
+    Some(camera)
+}
+
+// Real lens and exposure settings.  Lengths are in millimeters on the
+// camera side; the scene is assumed to be in meters and its radiance
+// in cd/m^2 (nits).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PhysicalLens {
+    pub focal_length: f32,
+    pub sensor_width: f32,
+    pub sensor_height: f32,
+    pub f_stop: f32,
+    pub shutter: f32, // seconds
+    pub iso: f32
+}
+
+impl PhysicalLens {
+    // full frame sensor
+    pub fn new(focal_length: f32, f_stop: f32, shutter: f32, iso: f32) -> PhysicalLens {
+        PhysicalLens {
+            focal_length, sensor_width: 36., sensor_height: 24., f_stop, shutter, iso
+        }
+    }
+
+    // Vertical field of view in degrees.  The sensor is fit inside the
+    // image: its width fills a wider image, its height a taller one.
+    pub fn vfov(&self, aspect: f32) -> f32 {
+        let height = if aspect >= self.sensor_width / self.sensor_height {
+            self.sensor_width / aspect
+        }
+        else {
+            self.sensor_height
+        };
+        2. * (height / (2. * self.focal_length)).atan().to_degrees()
+    }
+
+    // entrance pupil diameter in scene units (meters)
+    pub fn aperture_diameter(&self) -> f32 {
+        self.focal_length / self.f_stop / 1000.
+    }
+
+    // Exposure from the saturation based sensitivity model (as in
+    // Lagarde and de Rousiers, "Moving Frostbite to PBR"): the
+    // luminance that just saturates the sensor maps to 1.
+    pub fn exposure(&self) -> f32 {
+        let ev100 = (self.f_stop * self.f_stop / self.shutter * 100. / self.iso).log2();
+        let max_luminance = 1.2 * 2f32.powf(ev100);
+        1. / max_luminance
+    }
+}
+
+// Shape of the lens opening, which out of focus highlights (bokeh)
+// take on.  Lens samples are mapped into the shape inside [-1, 1]^2.
+#[derive(Clone)]
+pub enum Aperture {
+    Circle,
+    // regular polygon of diaphragm blades, rotation in degrees
+    Polygon { blades: u32, rotation: f32 },
+    Mask(ApertureMask)
+}
+
+impl Aperture {
+    pub fn sample(&self, u1: f32, u2: f32) -> Point {
+        match self {
+            Aperture::Circle => sample_unit_disk(u1, u2),
+            Aperture::Polygon { blades, rotation } => {
+                let n = (*blades).max(3);
+                // pick one of the n triangles fanning out from the center
+                let scaled = u1 * n as f32;
+                let k = (scaled as u32).min(n - 1);
+                let u1 = scaled - k as f32;
+                let angle = |i: u32| rotation.to_radians() + 2. * consts::PI * i as f32 / n as f32;
+                let a = Point::new(angle(k).cos(), angle(k).sin(), 0.);
+                let b = Point::new(angle(k + 1).cos(), angle(k + 1).sin(), 0.);
+                // uniform in the triangle (center, a, b)
+                let su = u1.sqrt();
+                su * (1. - u2) * a + su * u2 * b
+            }
+            Aperture::Mask(mask) => mask.sample(u1, u2)
+        }
+    }
+}
+
+// Aperture from an image, brighter texels letting more light through.
+// Lens positions are drawn in proportion to the mask's luminance.
+#[derive(Clone)]
+pub struct ApertureMask {
+    dist: Distribution2D
+}
+
+impl ApertureMask {
+    // img[row][col], row 0 at the top
+    // None for an image without pixels or with ones that aren't finite
+    pub fn new(img: &FloatImage) -> Option<ApertureMask> {
+        let weights: Vec<Vec<f32>> = img.iter()
+            .map(|row| row.iter().map(|c| (c.r() + c.g() + c.b()) / 3.).collect())
+            .collect();
+        Some(ApertureMask { dist: Distribution2D::new(&weights)? })
+    }
+
+    pub fn load(path: &str) -> std::io::Result<ApertureMask> {
+        ApertureMask::new(&read_ppm(path)?).ok_or_else(|| Error::new(ErrorKind::InvalidData,
+            format!("{}: aperture mask has no pixels or ones that aren't finite", path)))
+    }
+
+    fn sample(&self, u1: f32, u2: f32) -> Point {
+        let ((x, y), _) = self.dist.sample(u1, u2);
+        Point::new(2. * x - 1., 1. - 2. * y, 0.)
+    }
+}
+
+// perspective projection through a thin lens, giving depth of field
+pub struct ThinLensCamera {
     origin: Point,
     lower_left_corner: Point,
     horizontal: Vec3,
     vertical: Vec3,
     u: Vec3,
     v: Vec3,
-    lens_radius: f32
+    lens_radius: f32,
+    aperture: Aperture,
+    exposure: f32,
+    eye_offset: Vec3 // moves the eye but not the image window (stereo)
 }
 
-impl Camera {
+impl ThinLensCamera {
     // vfov: vertical field of view in degrees
     // aspect: width / height
     pub fn new(lookfrom: Point, lookat: Point, vup: Vec3,
                vfov: f32, aspect: f32,
-               aperature: f32, focus_dist: f32) -> Camera {
+               aperature: f32, focus_dist: f32) -> ThinLensCamera {
         let theta = vfov * consts::PI / 180.;
         let half_height = (theta / 2.).tan();
         let half_width  = aspect * half_height;
@@ -32,24 +196,373 @@ impl Camera {
         let w = Vec3::unit_vector(lookfrom - lookat);
         let u = Vec3::unit_vector(Vec3::cross(&vup, &w));
         let v = Vec3::cross(&w, &u);
-        Camera {
+        ThinLensCamera {
             origin: lookfrom,
             lower_left_corner: lookfrom - focus_dist * (half_width*u + half_height*v + w),
             horizontal: 2.*half_width*focus_dist*u,
             vertical: 2.*half_height*focus_dist*v,
             u, v,
-            lens_radius
+            lens_radius,
+            aperture: Aperture::Circle,
+            exposure: 1.,
+            eye_offset: Vec3::init()
         }
     }
 
-    pub fn get_ray(&self, s: f32, t: f32) -> Ray {
-        let rd = self.lens_radius * random_in_unit_disk();
-        let offset = self.u * rd.x() + self.v * rd.y();
-        Ray::new(
+    pub fn from_view(view: &View, aspect: f32) -> ThinLensCamera {
+        ThinLensCamera::new(view.lookfrom, view.lookat, view.vup,
+                            view.vfov, aspect, view.aperature, view.focus_dist)
+    }
+
+    // takes the position and focus distance from view, but the field of
+    // view, aperture and exposure from the lens settings
+    pub fn physical(view: &View, aspect: f32, lens: &PhysicalLens) -> ThinLensCamera {
+        let mut camera = ThinLensCamera::new(
+            view.lookfrom, view.lookat, view.vup,
+            lens.vfov(aspect), aspect, lens.aperture_diameter(), view.focus_dist);
+        camera.exposure = lens.exposure();
+        camera
+    }
+
+    pub fn with_aperture(mut self, aperture: Aperture) -> ThinLensCamera {
+        self.aperture = aperture;
+        self
+    }
+
+    // Moves the eye sideways by offset (along the camera's right axis)
+    // while rays still pass through the same image window, so objects
+    // at the focus distance line up between eyes (off-axis stereo).
+    pub fn with_eye_offset(mut self, offset: f32) -> ThinLensCamera {
+        self.eye_offset = offset * self.u;
+        self
+    }
+}
+
+impl Camera for ThinLensCamera {
+    fn get_ray(&self, s: f32, t: f32, lens: (f32, f32)) -> Option<Ray> {
+        let rd = self.lens_radius * self.aperture.sample(lens.0, lens.1);
+        let offset = self.u * rd.x() + self.v * rd.y() + self.eye_offset;
+        Some(Ray::new(
             self.origin + offset,
             self.lower_left_corner +
-                s*self.horizontal + t*self.vertical - self.origin - offset)
+                s*self.horizontal + t*self.vertical - self.origin - offset))
+    }
+
+    fn exposure(&self) -> f32 {
+        self.exposure
+    }
+}
+
+// parallel rays, so sizes don't change with distance (elevations, plans)
+pub struct OrthographicCamera {
+    lower_left_corner: Point,
+    horizontal: Vec3,
+    vertical: Vec3,
+    dir: Vec3
+}
+
+impl OrthographicCamera {
+    // height: world space height of the view, aspect: width / height
+    pub fn new(lookfrom: Point, lookat: Point, vup: Vec3,
+               height: f32, aspect: f32) -> OrthographicCamera {
+        let w = Vec3::unit_vector(lookfrom - lookat);
+        let u = Vec3::unit_vector(Vec3::cross(&vup, &w));
+        let v = Vec3::cross(&w, &u);
+        let horizontal = aspect * height * u;
+        let vertical = height * v;
+        OrthographicCamera {
+            lower_left_corner: lookfrom - 0.5 * horizontal - 0.5 * vertical,
+            horizontal,
+            vertical,
+            dir: -w
+        }
+    }
+
+    // frames what the perspective camera sees at the focus distance
+    pub fn from_view(view: &View, aspect: f32) -> OrthographicCamera {
+        let height = 2. * view.focus_dist * (view.vfov.to_radians() / 2.).tan();
+        OrthographicCamera::new(view.lookfrom, view.lookat, view.vup, height, aspect)
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn get_ray(&self, s: f32, t: f32, _: (f32, f32)) -> Option<Ray> {
+        Some(Ray::new(self.lower_left_corner + s*self.horizontal + t*self.vertical, self.dir))
+    }
+}
+
+// Equidistant fisheye: the angle away from the view direction grows
+// linearly with the distance from the image center.  The image circle
+// fills the height of the frame and covers fov degrees (up to 360).
+pub struct FisheyeCamera {
+    origin: Point,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    half_fov: f32, // radians
+    aspect: f32
+}
+
+impl FisheyeCamera {
+    pub fn new(lookfrom: Point, lookat: Point, vup: Vec3, fov: f32, aspect: f32) -> FisheyeCamera {
+        let w = Vec3::unit_vector(lookfrom - lookat);
+        let u = Vec3::unit_vector(Vec3::cross(&vup, &w));
+        let v = Vec3::cross(&w, &u);
+        FisheyeCamera {
+            origin: lookfrom, u, v, w,
+            half_fov: fov.clamp(0., 360.).to_radians() / 2.,
+            aspect
+        }
+    }
+
+    pub fn from_view(view: &View, aspect: f32) -> FisheyeCamera {
+        FisheyeCamera::new(view.lookfrom, view.lookat, view.vup, view.vfov, aspect)
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn get_ray(&self, s: f32, t: f32, _: (f32, f32)) -> Option<Ray> {
+        let x = (2. * s - 1.) * self.aspect;
+        let y = 2. * t - 1.;
+        let r = (x * x + y * y).sqrt();
+        if r > 1. {
+            return None;
+        }
+        let theta = r * self.half_fov;
+        let phi = y.atan2(x);
+        let dir = theta.sin() * (phi.cos() * self.u + phi.sin() * self.v) - theta.cos() * self.w;
+        Some(Ray::new(self.origin, dir))
     }
 }
-    
-}
\ No newline at end of file
+
+// Full 360 x 180 degree panorama in latitude/longitude layout, with
+// the view direction in the center of the image.
+pub struct EquirectangularCamera {
+    origin: Point,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3
+}
+
+impl EquirectangularCamera {
+    pub fn new(lookfrom: Point, lookat: Point, vup: Vec3) -> EquirectangularCamera {
+        let w = Vec3::unit_vector(lookfrom - lookat);
+        let u = Vec3::unit_vector(Vec3::cross(&vup, &w));
+        let v = Vec3::cross(&w, &u);
+        EquirectangularCamera { origin: lookfrom, u, v, w }
+    }
+
+    pub fn from_view(view: &View) -> EquirectangularCamera {
+        EquirectangularCamera::new(view.lookfrom, view.lookat, view.vup)
+    }
+}
+
+impl Camera for EquirectangularCamera {
+    fn get_ray(&self, s: f32, t: f32, _: (f32, f32)) -> Option<Ray> {
+        let longitude = (s - 0.5) * 2. * consts::PI;
+        let latitude = (t - 0.5) * consts::PI;
+        let dir = latitude.cos() * (longitude.sin() * self.u - longitude.cos() * self.w) +
+                  latitude.sin() * self.v;
+        Some(Ray::new(self.origin, dir))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StereoLayout {
+    SideBySide, // left eye in the left half
+    TopBottom   // left eye in the top half
+}
+
+// Renders a left and a right eye into one image.
+pub struct StereoCamera {
+    left: Box<dyn Camera>,
+    right: Box<dyn Camera>,
+    layout: StereoLayout
+}
+
+impl StereoCamera {
+    // Two thin lens eyes interocular apart (scene units) centered on
+    // view.lookfrom.  Things at the convergence distance have zero
+    // parallax, nearer ones pop out of the screen.  Each eye is also
+    // focused at the convergence distance, through the physical lens
+    // and aperture shape when given (see ThinLensCamera::physical).
+    // aspect: width / height of the whole image
+    pub fn perspective(view: &View, aspect: f32, lens: Option<&PhysicalLens>, aperture: Option<&Aperture>,
+                       interocular: f32, convergence: f32, layout: StereoLayout) -> StereoCamera {
+        let eye_aspect = match layout {
+            StereoLayout::SideBySide => aspect / 2.,
+            StereoLayout::TopBottom => aspect * 2.
+        };
+        let view = View { focus_dist: convergence, ..*view };
+        let eye = |offset: f32| -> Box<dyn Camera> {
+            let mut camera = match lens {
+                Some(lens) => ThinLensCamera::physical(&view, eye_aspect, lens),
+                None => ThinLensCamera::from_view(&view, eye_aspect)
+            };
+            if let Some(aperture) = aperture {
+                camera = camera.with_aperture(aperture.clone());
+            }
+            Box::new(camera.with_eye_offset(offset))
+        };
+        StereoCamera {
+            left: eye(-0.5 * interocular),
+            right: eye(0.5 * interocular),
+            layout
+        }
+    }
+
+    // omni-directional stereo panoramas, see OdsCamera
+    pub fn omnidirectional(view: &View, interocular: f32, layout: StereoLayout) -> StereoCamera {
+        StereoCamera {
+            left: Box::new(OdsCamera::new(view, -0.5 * interocular)),
+            right: Box::new(OdsCamera::new(view, 0.5 * interocular)),
+            layout
+        }
+    }
+}
+
+impl Camera for StereoCamera {
+    fn get_ray(&self, s: f32, t: f32, lens: (f32, f32)) -> Option<Ray> {
+        match self.layout {
+            StereoLayout::SideBySide if s < 0.5 => self.left.get_ray(2. * s, t, lens),
+            StereoLayout::SideBySide => self.right.get_ray(2. * s - 1., t, lens),
+            StereoLayout::TopBottom if t >= 0.5 => self.left.get_ray(s, 2. * t - 1., lens),
+            StereoLayout::TopBottom => self.right.get_ray(s, 2. * t, lens)
+        }
+    }
+
+    fn exposure(&self) -> f32 {
+        self.left.exposure()
+    }
+}
+
+// One eye of an omni-directional stereo (ODS) panorama: an
+// equirectangular image where every column is seen from an eye
+// circling the viewer's head, offset sideways from the viewing
+// direction by eye_offset (negative for the left eye).  This keeps
+// the stereo correct whichever way the viewer turns.
+pub struct OdsCamera {
+    origin: Point,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    eye_offset: f32
+}
+
+impl OdsCamera {
+    pub fn new(view: &View, eye_offset: f32) -> OdsCamera {
+        let (u, v, w) = view.basis();
+        OdsCamera { origin: view.lookfrom, u, v, w, eye_offset }
+    }
+}
+
+impl Camera for OdsCamera {
+    fn get_ray(&self, s: f32, t: f32, _: (f32, f32)) -> Option<Ray> {
+        let longitude = (s - 0.5) * 2. * consts::PI;
+        let latitude = (t - 0.5) * consts::PI;
+        let forward = longitude.sin() * self.u - longitude.cos() * self.w;
+        let right = longitude.cos() * self.u + longitude.sin() * self.w;
+        let dir = latitude.cos() * forward + latitude.sin() * self.v;
+        Some(Ray::new(self.origin + self.eye_offset * right, dir))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stereo_eyes_are_offset_and_converge() {
+        let view = View {
+            lookfrom: Point::new(0., 1., 5.),
+            lookat: Point::new(0., 1., 0.),
+            vup: Vec3::new(0., 1., 0.),
+            vfov: 40.,
+            aperature: 0.1,
+            focus_dist: 5.
+        };
+        let (interocular, convergence) = (0.064, 3.);
+        let camera = StereoCamera::perspective(&view, 2., None, None, interocular, convergence,
+                                               StereoLayout::SideBySide);
+        // the middle of each eye's half, through the middle of the lens
+        let left = camera.get_ray(0.25, 0.5, (0.5, 0.5)).unwrap();
+        let right = camera.get_ray(0.75, 0.5, (0.5, 0.5)).unwrap();
+        let apart = right.origin() - left.origin();
+        assert!((apart.x() - interocular).abs() < 1e-5);
+        assert!(apart.y().abs() < 1e-5 && apart.z().abs() < 1e-5);
+
+        // any lens position of either eye lands on the same point at the
+        // convergence distance
+        let focus = Point::new(0., 1., 5. - convergence);
+        for lens in [(0.5, 0.5), (0.1, 0.8), (0.9, 0.3)] {
+            for s in [0.25, 0.75] {
+                let r = camera.get_ray(s, 0.5, lens).unwrap();
+                let t = (focus.z() - r.origin().z()) / r.dir().z();
+                assert!((r.point_at_parameter(t) - focus).length() < 1e-4);
+            }
+        }
+    }
+
+    fn front_view(vfov: f32) -> View {
+        View {
+            lookfrom: Point::new(0., 0., 5.),
+            lookat: Point::new(0., 0., 0.),
+            vup: Vec3::new(0., 1., 0.),
+            vfov,
+            aperature: 0.,
+            focus_dist: 5.
+        }
+    }
+
+    fn assert_heads(r: &Ray, dir: Vec3) {
+        let d = Vec3::unit_vector(r.dir());
+        assert!((d - dir).length() < 1e-5, "{:?} is not {:?}", d, dir);
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel_across_the_focus_plane() {
+        // 10 units high at the focus distance, 20 wide
+        let camera = OrthographicCamera::from_view(&front_view(90.), 2.);
+        for (s, t, x, y) in [(0., 0., -10., -5.), (1., 1., 10., 5.), (0.5, 0.5, 0., 0.)] {
+            let r = camera.get_ray(s, t, (0.5, 0.5)).unwrap();
+            assert!((r.origin() - Point::new(x, y, 5.)).length() < 1e-4);
+            assert_heads(&r, Vec3::new(0., 0., -1.));
+        }
+    }
+
+    #[test]
+    fn fisheye_angle_grows_with_the_distance_from_the_center() {
+        let camera = FisheyeCamera::from_view(&front_view(180.), 1.);
+        assert_heads(&camera.get_ray(0.5, 0.5, (0.5, 0.5)).unwrap(), Vec3::new(0., 0., -1.));
+        // the edge of the image circle is 90 degrees off the view direction
+        assert_heads(&camera.get_ray(1., 0.5, (0.5, 0.5)).unwrap(), Vec3::new(1., 0., 0.));
+        assert_heads(&camera.get_ray(0.5, 1., (0.5, 0.5)).unwrap(), Vec3::new(0., 1., 0.));
+        // halfway out is halfway round
+        let h = consts::FRAC_1_SQRT_2;
+        assert_heads(&camera.get_ray(0.25, 0.5, (0.5, 0.5)).unwrap(), Vec3::new(-h, 0., -h));
+        assert!(camera.get_ray(1., 1., (0.5, 0.5)).is_none());
+    }
+
+    #[test]
+    fn equirectangular_covers_the_whole_sphere() {
+        let camera = EquirectangularCamera::from_view(&front_view(90.));
+        assert_heads(&camera.get_ray(0.5, 0.5, (0.5, 0.5)).unwrap(), Vec3::new(0., 0., -1.));
+        assert_heads(&camera.get_ray(0.75, 0.5, (0.5, 0.5)).unwrap(), Vec3::new(1., 0., 0.));
+        assert_heads(&camera.get_ray(0.25, 0.5, (0.5, 0.5)).unwrap(), Vec3::new(-1., 0., 0.));
+        assert_heads(&camera.get_ray(0., 0.5, (0.5, 0.5)).unwrap(), Vec3::new(0., 0., 1.));
+        assert_heads(&camera.get_ray(0.5, 1., (0.5, 0.5)).unwrap(), Vec3::new(0., 1., 0.));
+        assert_heads(&camera.get_ray(0.3, 0., (0.5, 0.5)).unwrap(), Vec3::new(0., -1., 0.));
+    }
+
+    #[test]
+    fn aperture_masks_need_pixels() {
+        assert!(ApertureMask::new(&vec![]).is_none());
+        assert!(ApertureMask::new(&vec![vec![]]).is_none());
+        // a black mask still gives lens positions inside the square
+        let mask = ApertureMask::new(&vec![vec![Color::init(); 2]; 2]).unwrap();
+        let p = Aperture::Mask(mask).sample(0.9, 0.1);
+        assert!(p.x().abs() <= 1. && p.y().abs() <= 1.);
+    }
+}
+
+}
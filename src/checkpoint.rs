@@ -0,0 +1,338 @@
+pub mod module {
+
+use crate::types::module::*;
+use crate::film::module::*;
+use crate::camera::module::*;
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Seek, Write};
+
+const MAGIC: &[u8; 4] = b"RTCK";
+const VERSION: u32 = 5;
+
+// bytes per pixel: the count, the weight, the weighted sums, and the
+// film's own count and plain sums
+const PIXEL_BYTES: u64 = 9 * 4;
+
+// Every other setting that changes the image, so a resume with
+// different ones can be refused
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImageOptions {
+    pub spectral: bool,
+    pub camera: String,
+    pub fov: Option<f32>,
+    pub lens: Option<PhysicalLens>,
+    pub blades: Option<u32>,
+    pub blade_rotation: f32,
+    pub aperture_mask: Option<String>,
+    pub stereo: Option<StereoLayout>,
+    pub interocular: f32,
+    pub convergence: Option<f32>,
+    pub environment: Option<String>,
+    pub env_rotation: f32,
+    pub env_intensity: f32,
+    pub sun: Option<(f32, f32)>,
+    pub turbidity: f32,
+    pub sky_intensity: Option<f32>
+}
+
+impl ImageOptions {
+    fn write(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        write_u32(w, self.spectral as u32)?;
+        write_str(w, &self.camera)?;
+        write_option(w, &self.fov, |w, v| write_f32(w, *v))?;
+        write_option(w, &self.lens, |w, lens| {
+            for v in [lens.focal_length, lens.sensor_width, lens.sensor_height,
+                      lens.f_stop, lens.shutter, lens.iso] {
+                write_f32(w, v)?;
+            }
+            Ok(())
+        })?;
+        write_option(w, &self.blades, |w, v| write_u32(w, *v))?;
+        write_f32(w, self.blade_rotation)?;
+        write_option(w, &self.aperture_mask, |w, v| write_str(w, v))?;
+        write_option(w, &self.stereo, |w, layout| write_u32(w, match layout {
+            StereoLayout::SideBySide => 0,
+            StereoLayout::TopBottom => 1
+        }))?;
+        write_f32(w, self.interocular)?;
+        write_option(w, &self.convergence, |w, v| write_f32(w, *v))?;
+        write_option(w, &self.environment, |w, v| write_str(w, v))?;
+        write_f32(w, self.env_rotation)?;
+        write_f32(w, self.env_intensity)?;
+        write_option(w, &self.sun, |w, (elevation, azimuth)| {
+            write_f32(w, *elevation)?;
+            write_f32(w, *azimuth)
+        })?;
+        write_f32(w, self.turbidity)?;
+        write_option(w, &self.sky_intensity, |w, v| write_f32(w, *v))
+    }
+
+    fn read(r: &mut dyn Read) -> std::io::Result<ImageOptions> {
+        Ok(ImageOptions {
+            spectral: read_u32(r)? != 0,
+            camera: read_str(r)?,
+            fov: read_option(r, read_f32)?,
+            lens: read_option(r, |r| Ok(PhysicalLens {
+                focal_length: read_f32(r)?,
+                sensor_width: read_f32(r)?,
+                sensor_height: read_f32(r)?,
+                f_stop: read_f32(r)?,
+                shutter: read_f32(r)?,
+                iso: read_f32(r)?
+            }))?,
+            blades: read_option(r, read_u32)?,
+            blade_rotation: read_f32(r)?,
+            aperture_mask: read_option(r, read_str)?,
+            stereo: read_option(r, |r| match read_u32(r)? {
+                0 => Ok(StereoLayout::SideBySide),
+                1 => Ok(StereoLayout::TopBottom),
+                _ => Err(bad_data("bad stereo layout in checkpoint".to_string()))
+            })?,
+            interocular: read_f32(r)?,
+            convergence: read_option(r, read_f32)?,
+            environment: read_option(r, read_str)?,
+            env_rotation: read_f32(r)?,
+            env_intensity: read_f32(r)?,
+            sun: read_option(r, |r| Ok((read_f32(r)?, read_f32(r)?)))?,
+            turbidity: read_f32(r)?,
+            sky_intensity: read_option(r, read_f32)?
+        })
+    }
+}
+
+// The film of a progressive render plus everything needed to carry on
+// later.  Every pixel sample reseeds the random number generator from
+// (seed, pixel, sample index), so the seed together with each pixel's
+// sample count is the complete RNG state.
+pub struct Accumulation {
+    pub scene: String,
+    pub sampler: String,
+    pub seed: u64,
+    pub filter: String,
+    pub pass_size: u32,
+    pub options: ImageOptions,
+    pub film: Film,
+    pub counts: Vec<u32> // samples taken so far, per pixel
+}
+
+impl Accumulation {
+    // filter: name of the film's filter, so it can be rebuilt on load
+    pub fn new(scene: &str, sampler: &str, seed: u64, filter: &str,
+               pass_size: u32, options: ImageOptions, film: Film) -> Accumulation {
+        let n = (film.nx * film.ny) as usize;
+        Accumulation {
+            scene: scene.to_string(),
+            sampler: sampler.to_string(),
+            seed,
+            filter: filter.to_string(),
+            pass_size,
+            options,
+            film,
+            counts: vec![0; n]
+        }
+    }
+
+    // reconstructed radiance so far
+    pub fn image(&self) -> FloatImage {
+        self.film.image()
+    }
+
+    // fewest samples any pixel has
+    pub fn min_count(&self) -> u32 {
+        self.counts.iter().cloned().min().unwrap_or(0)
+    }
+
+    // Writes to a temporary file first and renames it into place so a
+    // render killed mid-write still leaves the previous checkpoint.
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let tmp_path = format!("{}.tmp", path);
+        {
+            let mut file = BufWriter::new(File::create(&tmp_path)?);
+            file.write_all(MAGIC)?;
+            write_u32(&mut file, VERSION)?;
+            write_str(&mut file, &self.scene)?;
+            write_str(&mut file, &self.sampler)?;
+            file.write_all(&self.seed.to_le_bytes())?;
+            write_str(&mut file, &self.filter)?;
+            write_f32(&mut file, self.film.filter.radius())?;
+            write_u32(&mut file, self.pass_size)?;
+            self.options.write(&mut file)?;
+            write_u32(&mut file, self.film.nx)?;
+            write_u32(&mut file, self.film.ny)?;
+            for idx in 0..self.counts.len() {
+                write_u32(&mut file, self.counts[idx])?;
+                write_f32(&mut file, self.film.weights[idx])?;
+                for k in 0..3 {
+                    write_f32(&mut file, self.film.sums[idx][k])?;
+                }
+                write_u32(&mut file, self.film.box_counts[idx])?;
+                for k in 0..3 {
+                    write_f32(&mut file, self.film.box_sums[idx][k])?;
+                }
+            }
+            file.flush()?;
+        }
+        std::fs::rename(&tmp_path, path)
+    }
+
+    pub fn load(path: &str) -> std::io::Result<Accumulation> {
+        let mut file = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 4];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(bad_data(format!("{} is not a checkpoint file", path)));
+        }
+        let version = read_u32(&mut file)?;
+        if version != VERSION {
+            return Err(bad_data(format!("unsupported checkpoint version {}", version)));
+        }
+        let scene = read_str(&mut file)?;
+        let sampler = read_str(&mut file)?;
+        let mut seed = [0u8; 8];
+        file.read_exact(&mut seed)?;
+        let filter = read_str(&mut file)?;
+        let filter_radius = read_f32(&mut file)?;
+        let pass_size = read_u32(&mut file)?;
+        let options = ImageOptions::read(&mut file)?;
+        let nx = read_u32(&mut file)?;
+        let ny = read_u32(&mut file)?;
+        // the pixels have to be all that's left, so a corrupt size can't
+        // ask for a huge film
+        let pixels = nx.checked_mul(ny).filter(|&n| n > 0)
+            .ok_or_else(|| bad_data(format!("bad image size {} x {} in checkpoint", nx, ny)))?;
+        let remaining = file.get_ref().metadata()?.len().saturating_sub(file.stream_position()?);
+        if remaining != pixels as u64 * PIXEL_BYTES {
+            return Err(bad_data(format!("{} doesn't hold the {} x {} pixels it says", path, nx, ny)));
+        }
+
+        let film = Film::new(nx, ny, filter_by_name(&filter, filter_radius)
+            .ok_or_else(|| bad_data(format!("unknown filter '{}' in checkpoint", filter)))?);
+        let mut acc = Accumulation::new(&scene, &sampler, u64::from_le_bytes(seed), &filter,
+                                        pass_size, options, film);
+        for idx in 0..pixels as usize {
+            acc.counts[idx] = read_u32(&mut file)?;
+            acc.film.weights[idx] = read_f32(&mut file)?;
+            acc.film.sums[idx] = Color::new(read_f32(&mut file)?,
+                                            read_f32(&mut file)?,
+                                            read_f32(&mut file)?);
+            acc.film.box_counts[idx] = read_u32(&mut file)?;
+            acc.film.box_sums[idx] = Color::new(read_f32(&mut file)?,
+                                                read_f32(&mut file)?,
+                                                read_f32(&mut file)?);
+        }
+        Ok(acc)
+    }
+}
+
+fn bad_data(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn write_u32(w: &mut dyn Write, v: u32) -> std::io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+fn write_str(w: &mut dyn Write, s: &str) -> std::io::Result<()> {
+    write_u32(w, s.len() as u32)?;
+    w.write_all(s.as_bytes())
+}
+
+fn write_f32(w: &mut dyn Write, v: f32) -> std::io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+fn read_u32(r: &mut dyn Read) -> std::io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f32(r: &mut dyn Read) -> std::io::Result<f32> {
+    Ok(f32::from_bits(read_u32(r)?))
+}
+
+// reads at most what's in the file, whatever length it claims
+fn read_str(r: &mut dyn Read) -> std::io::Result<String> {
+    let len = read_u32(r)? as u64;
+    let mut bytes = Vec::new();
+    r.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "checkpoint string is cut short"));
+    }
+    String::from_utf8(bytes).map_err(|_| bad_data("checkpoint string is not utf-8".to_string()))
+}
+
+// a flag, then the value if there is one
+fn write_option<T>(w: &mut dyn Write, v: &Option<T>,
+                   write: impl Fn(&mut dyn Write, &T) -> std::io::Result<()>) -> std::io::Result<()> {
+    match v {
+        Some(v) => {
+            write_u32(w, 1)?;
+            write(w, v)
+        }
+        None => write_u32(w, 0)
+    }
+}
+
+fn read_option<T>(r: &mut dyn Read,
+                  read: impl Fn(&mut dyn Read) -> std::io::Result<T>) -> std::io::Result<Option<T>> {
+    match read_u32(r)? {
+        0 => Ok(None),
+        1 => Ok(Some(read(r)?)),
+        _ => Err(bad_data("bad optional setting in checkpoint".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("{}-{}.ckpt", name, std::process::id()));
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn options_survive_a_save_and_load() {
+        let options = ImageOptions {
+            spectral: true,
+            camera: "perspective".to_string(),
+            lens: Some(PhysicalLens::new(35., 2.8, 1. / 60., 400.)),
+            stereo: Some(StereoLayout::TopBottom),
+            sun: Some((30., 120.)),
+            environment: Some("sky.hdr".to_string()),
+            ..ImageOptions::default()
+        };
+        let film = Film::new(3, 2, filter_by_name("box", 0.5).unwrap());
+        let acc = Accumulation::new("diffuse", "sobol", 7, "box", 4, options.clone(), film);
+        let path = temp_path("options-test");
+        acc.save(&path).unwrap();
+        let loaded = Accumulation::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.options, options);
+        assert_eq!(loaded.pass_size, 4);
+        assert_eq!((loaded.film.nx, loaded.film.ny), (3, 2));
+    }
+
+    // a header claiming far more pixels than the file holds
+    #[test]
+    fn load_rejects_sizes_the_file_does_not_hold() {
+        let film = Film::new(3, 2, filter_by_name("box", 0.5).unwrap());
+        let acc = Accumulation::new("diffuse", "sobol", 7, "box", 4, ImageOptions::default(), film);
+        let path = temp_path("size-test");
+        acc.save(&path).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        let size_at = bytes.len() - 6 * PIXEL_BYTES as usize - 8;
+        for (k, dim) in [0x10000u32, 0x10000].iter().enumerate() {
+            bytes[size_at + 4 * k..size_at + 4 * k + 4].copy_from_slice(&dim.to_le_bytes());
+        }
+        std::fs::write(&path, &bytes).unwrap();
+        let result = Accumulation::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(result.err().map(|e| e.kind()), Some(ErrorKind::InvalidData));
+    }
+}
+
+}
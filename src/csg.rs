@@ -0,0 +1,296 @@
+pub mod module {
+
+use crate::vec3::module::*;
+use crate::ray::module::*;
+use crate::hittable::module::*;
+
+use std::f32;
+
+// most surfaces Closed looks for along a ray
+const MAX_CROSSINGS: usize = 64;
+
+// A stretch of a ray inside a solid, between where it enters and where
+// it leaves.  Records have t at -infinity or infinity for rays that
+// are inside from, or until, the end.
+#[derive(Copy, Clone)]
+pub struct Span {
+    pub enter: HitRecord,
+    pub exit: HitRecord
+}
+
+// Closed objects that can report every part of a ray's line inside
+// them, which is what constructive solid geometry combines
+pub trait Solid: Hittable {
+    // Spans along the whole line of r (any t), in increasing t and not
+    // overlapping.  Normals point out of the solid.
+    fn spans(&self, r: &Ray) -> Vec<Span>;
+}
+
+// the first boundary of spans within (t_min, t_max)
+fn first_hit(spans: &[Span], t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+    for span in spans {
+        for boundary in [&span.enter, &span.exit] {
+            if boundary.t > t_min && boundary.t < t_max {
+                *record = *boundary;
+                return true;
+            }
+        }
+    }
+    false
+}
+
+// record for a span that never ends (or never starts) along the ray
+fn unbounded(t: f32) -> HitRecord {
+    HitRecord { t, ..HitRecord::default() }
+}
+
+// Makes a solid of any closed Hittable with outward normals, like a
+// Sphere or a Cuboid, by following its hits along the ray and telling
+// entries from exits by the normal
+pub struct Closed {
+    obj: Box<dyn Hittable>
+}
+
+impl Closed {
+    pub fn new<H: Hittable + 'static>(obj: H) -> Closed {
+        Closed { obj: Box::new(obj) }
+    }
+}
+
+impl Hittable for Closed {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        self.obj.hit(r, t_min, t_max, record)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.obj.bounding_box()
+    }
+}
+
+impl Solid for Closed {
+    fn spans(&self, r: &Ray) -> Vec<Span> {
+        let mut spans = Vec::new();
+        let mut enter: Option<HitRecord> = None;
+        let mut t = f32::NEG_INFINITY;
+        for _ in 0..MAX_CROSSINGS {
+            let mut record = HitRecord::default();
+            if !self.obj.hit(r, t, f32::INFINITY, &mut record) || record.t <= t {
+                break;
+            }
+            t = record.t;
+            let entering = Vec3::dot(&r.dir(), &record.normal) < 0.;
+            match (entering, enter) {
+                (true, None) => enter = Some(record),
+                (false, Some(start)) => {
+                    spans.push(Span { enter: start, exit: record });
+                    enter = None;
+                }
+                // leaving without having entered or entering twice happen
+                // when grazing the surface, the line starts outside so
+                // keep to the spans seen so far
+                _ => ()
+            }
+        }
+        if let Some(start) = enter {
+            spans.push(Span { enter: start, exit: unbounded(f32::INFINITY) });
+        }
+        spans
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    Difference // the first solid without the second
+}
+
+impl CsgOp {
+    fn inside(&self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOp::Union => in_a || in_b,
+            CsgOp::Intersection => in_a && in_b,
+            CsgOp::Difference => in_a && !in_b
+        }
+    }
+}
+
+// Two solids combined into one.  Each surface keeps the material of
+// the solid it came from, so cuts show the carving solid's material.
+pub struct Csg {
+    op: CsgOp,
+    a: Box<dyn Solid>,
+    b: Box<dyn Solid>,
+    bounds: Option<Aabb>
+}
+
+impl Csg {
+    pub fn new<A: Solid + 'static, B: Solid + 'static>(op: CsgOp, a: A, b: B) -> Csg {
+        let bounds = match op {
+            CsgOp::Union => a.bounding_box().zip(b.bounding_box()).map(|(a, b)| a.union(&b)),
+            CsgOp::Intersection => match (a.bounding_box(), b.bounding_box()) {
+                (Some(a), Some(b)) => Some(a.intersection(&b)),
+                (a, b) => a.or(b)
+            },
+            CsgOp::Difference => a.bounding_box()
+        };
+        Csg { op, a: Box::new(a), b: Box::new(b), bounds }
+    }
+
+    // false when r's line between t_min and t_max misses the bounds, so
+    // there's no need to work out the spans
+    fn may_hit(&self, r: &Ray, t_min: f32, t_max: f32) -> bool {
+        self.bounds.is_none_or(|bounds| bounds.hit(r, t_min, t_max).is_some())
+    }
+
+    pub fn union<A: Solid + 'static, B: Solid + 'static>(a: A, b: B) -> Csg {
+        Csg::new(CsgOp::Union, a, b)
+    }
+
+    pub fn intersection<A: Solid + 'static, B: Solid + 'static>(a: A, b: B) -> Csg {
+        Csg::new(CsgOp::Intersection, a, b)
+    }
+
+    pub fn difference<A: Solid + 'static, B: Solid + 'static>(a: A, b: B) -> Csg {
+        Csg::new(CsgOp::Difference, a, b)
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        self.may_hit(r, t_min, t_max) && first_hit(&self.spans(r), t_min, t_max, record)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounds
+    }
+}
+
+impl Solid for Csg {
+    // Walks the boundaries of both solids' spans in order, keeping
+    // track of being inside each, and records where being inside the
+    // combination changes
+    fn spans(&self, r: &Ray) -> Vec<Span> {
+        if !self.may_hit(r, f32::NEG_INFINITY, f32::INFINITY) {
+            return Vec::new();
+        }
+        let spans_a = self.a.spans(r);
+        // nothing of the first solid leaves nothing to keep or carve
+        if spans_a.is_empty() && self.op != CsgOp::Union {
+            return Vec::new();
+        }
+        let spans_b = self.b.spans(r);
+        if spans_a.is_empty() && spans_b.is_empty() {
+            return Vec::new();
+        }
+
+        // (record, from a, entering)
+        let mut boundaries: Vec<(HitRecord, bool, bool)> = Vec::new();
+        for (spans, from_a) in [(&spans_a, true), (&spans_b, false)] {
+            for span in spans {
+                boundaries.push((span.enter, from_a, true));
+                boundaries.push((span.exit, from_a, false));
+            }
+        }
+        boundaries.sort_by(|x, y| x.0.t.total_cmp(&y.0.t));
+
+        let mut spans = Vec::new();
+        let (mut in_a, mut in_b) = (false, false);
+        let mut enter: Option<HitRecord> = None;
+        for (mut record, from_a, entering) in boundaries {
+            if from_a { in_a = entering; } else { in_b = entering; }
+            // the carved out solid's surface faces the other way
+            if self.op == CsgOp::Difference && !from_a {
+                record.normal = -record.normal;
+            }
+            let inside = self.op.inside(in_a, in_b);
+            match (inside, enter) {
+                (true, None) => enter = Some(record),
+                (false, Some(start)) => {
+                    spans.push(Span { enter: start, exit: record });
+                    enter = None;
+                }
+                _ => ()
+            }
+        }
+        spans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::module::*;
+    use crate::transform::module::*;
+    use crate::rect::module::*;
+    use std::f32::consts;
+
+    fn ball(x: f32, material: u32) -> Closed {
+        Closed::new(Sphere::new(Point::new(x, 0., 0.), 1., material))
+    }
+
+    // along the x axis from x = -5
+    fn along_x() -> Ray {
+        Ray::new(Point::new(-5., 0., 0.), Vec3::new(1., 0., 0.))
+    }
+
+    // (enter, exit) t of each span
+    fn extents(solid: &dyn Solid) -> Vec<(f32, f32)> {
+        solid.spans(&along_x()).iter().map(|s| (s.enter.t, s.exit.t)).collect()
+    }
+
+    fn assert_extents(solid: &dyn Solid, expected: &[(f32, f32)]) {
+        let got = extents(solid);
+        assert_eq!(got.len(), expected.len(), "{:?}", got);
+        for ((a, b), (x, y)) in got.iter().zip(expected) {
+            assert!((a - x).abs() < 1e-4 && (b - y).abs() < 1e-4, "{:?}", got);
+        }
+    }
+
+    // balls over x in [-1, 1] and [0, 2]
+    #[test]
+    fn operations_combine_spans() {
+        assert_extents(&Csg::union(ball(0., 0), ball(1., 1)), &[(4., 7.)]);
+        assert_extents(&Csg::intersection(ball(0., 0), ball(1., 1)), &[(5., 6.)]);
+        assert_extents(&Csg::difference(ball(0., 0), ball(1., 1)), &[(4., 5.)]);
+        assert_extents(&Csg::difference(ball(1., 1), ball(0., 0)), &[(6., 7.)]);
+        // apart, a union keeps both and an intersection nothing
+        assert_extents(&Csg::union(ball(0., 0), ball(3., 1)), &[(4., 6.), (7., 9.)]);
+        assert_extents(&Csg::intersection(ball(0., 0), ball(3., 1)), &[]);
+    }
+
+    #[test]
+    fn cut_surfaces_face_out_of_the_difference() {
+        let solid = Csg::difference(ball(0., 0), ball(1., 1));
+        let mut record = HitRecord::default();
+        // from inside the carved out ball, onto the cut
+        let r = Ray::new(Point::new(1., 0., 0.), Vec3::new(-1., 0., 0.));
+        assert!(solid.hit(&r, 0.001, f32::MAX, &mut record));
+        assert!((record.t - 1.).abs() < 1e-4);
+        assert!((record.normal - Vec3::new(1., 0., 0.)).length() < 1e-4);
+        assert_eq!(record.mat, Some(1));
+        // limited to before the solid
+        assert!(!solid.hit(&along_x(), 0.001, 3.9, &mut record));
+    }
+
+    #[test]
+    fn moved_and_rotated_solids_combine() {
+        let moved = Translate::new(Box::new(ball(0., 1)), Vec3::new(1., 0., 0.));
+        assert_extents(&moved, &[(5., 7.)]);
+        assert_extents(&Csg::difference(ball(0., 0), moved), &[(4., 5.)]);
+
+        // a 2 x 2 x 2 box turned 45 degrees reaches sqrt(2) along x
+        let cube = Closed::new(Cuboid::new(Point::new(-1., -1., -1.), Point::new(1., 1., 1.), 0));
+        let turned = RotateY::new(Box::new(cube), 45.);
+        let s = consts::SQRT_2;
+        assert_extents(&turned, &[(5. - s, 5. + s)]);
+        // the ray meets the edge between two faces
+        let spans = turned.spans(&along_x());
+        let h = consts::FRAC_1_SQRT_2;
+        assert!((spans[0].enter.normal - Vec3::new(-h, 0., h)).length() < 1e-4 ||
+                (spans[0].enter.normal - Vec3::new(-h, 0., -h)).length() < 1e-4);
+        assert!((spans[0].enter.p - Point::new(-s, 0., 0.)).length() < 1e-4);
+    }
+}
+
+}
@@ -0,0 +1,159 @@
+pub mod module {
+
+// piecewise constant distribution over [0, 1) built from non-negative
+// weights, sampled by inverting its CDF
+#[derive(Clone)]
+pub struct Distribution1D {
+    func: Vec<f32>,
+    cdf: Vec<f32>, // func.len() + 1 entries, cdf[0] = 0, last = 1
+    integral: f32
+}
+
+impl Distribution1D {
+    // None when there are no weights, or they or their sum aren't finite
+    pub fn new(func: Vec<f32>) -> Option<Distribution1D> {
+        let n = func.len();
+        if n == 0 || func.iter().any(|f| !f.is_finite()) {
+            return None;
+        }
+        let mut cdf = vec![0.; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i].max(0.) / n as f32;
+        }
+        let integral = cdf[n];
+        if !integral.is_finite() {
+            return None;
+        }
+        for (i, c) in cdf.iter_mut().enumerate().skip(1) {
+            // all zero weights fall back to uniform
+            *c = if integral > 0. { *c / integral } else { i as f32 / n as f32 };
+        }
+        Some(Distribution1D { func, cdf, integral })
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    // average of the weights
+    pub fn integral(&self) -> f32 {
+        self.integral
+    }
+
+    // Returns the sampled position in [0, 1), its pdf with respect to
+    // that position and the index of the segment it fell in.
+    pub fn sample(&self, u: f32) -> (f32, f32, usize) {
+        // last cdf entry <= u
+        let offset = self.cdf.partition_point(|&c| c <= u).saturating_sub(1).min(self.count() - 1);
+        let mut du = u - self.cdf[offset];
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        if width > 0. {
+            du /= width;
+        }
+        let pdf = self.pdf(offset);
+        ((offset as f32 + du) / self.count() as f32, pdf, offset)
+    }
+
+    // density of segment i with respect to the [0, 1) domain
+    pub fn pdf(&self, i: usize) -> f32 {
+        if self.integral > 0. { self.func[i].max(0.) / self.integral } else { 1. }
+    }
+}
+
+// piecewise constant distribution over [0, 1)^2 from a grid of weights
+// func[row][col]: a marginal over rows and a conditional per row
+#[derive(Clone)]
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D
+}
+
+impl Distribution2D {
+    // None when there are no rows, an empty row or weights that aren't
+    // finite
+    pub fn new(func: &[Vec<f32>]) -> Option<Distribution2D> {
+        let conditional: Vec<Distribution1D> =
+            func.iter().map(|row| Distribution1D::new(row.clone())).collect::<Option<_>>()?;
+        let marginal = Distribution1D::new(conditional.iter().map(|d| d.integral()).collect())?;
+        Some(Distribution2D { conditional, marginal })
+    }
+
+    // Returns (col position, row position) in [0, 1)^2 and the pdf
+    // with respect to that unit square.
+    pub fn sample(&self, u1: f32, u2: f32) -> ((f32, f32), f32) {
+        let (y, pdf_row, row) = self.marginal.sample(u2);
+        let (x, pdf_col, _) = self.conditional[row].sample(u1);
+        ((x, y), pdf_row * pdf_col)
+    }
+
+    // pdf at (x, y) in [0, 1)^2
+    pub fn pdf(&self, x: f32, y: f32) -> f32 {
+        let rows = self.marginal.count();
+        let row = ((y * rows as f32) as usize).min(rows - 1);
+        let cols = self.conditional[row].count();
+        let col = ((x * cols as f32) as usize).min(cols - 1);
+        if self.marginal.integral() > 0. {
+            self.conditional[row].func[col].max(0.) / self.marginal.integral()
+        }
+        else {
+            1.
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_land_in_proportion_to_the_weights() {
+        let dist = Distribution1D::new(vec![1., 3., 0., 4.]).unwrap();
+        assert_eq!(dist.integral(), 2.);
+        let n = 8000;
+        let mut counts = [0; 4];
+        for i in 0..n {
+            let (x, pdf, offset) = dist.sample((i as f32 + 0.5) / n as f32);
+            assert!((0. ..1.).contains(&x));
+            assert_eq!(offset, (x * 4.) as usize);
+            assert_eq!(pdf, dist.pdf(offset));
+            counts[offset] += 1;
+        }
+        assert_eq!(counts, [1000, 3000, 0, 4000]);
+        assert_eq!(dist.sample(0.).2, 0);
+        assert_eq!(dist.sample(1. - f32::EPSILON).2, 3);
+    }
+
+    #[test]
+    fn zero_weights_sample_uniformly() {
+        let dist = Distribution1D::new(vec![0.; 4]).unwrap();
+        for (u, offset) in [(0.1, 0), (0.3, 1), (0.6, 2), (0.9, 3)] {
+            let (x, pdf, i) = dist.sample(u);
+            assert_eq!((i, pdf), (offset, 1.));
+            assert!((x - u).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn rejects_empty_and_non_finite_weights() {
+        assert!(Distribution1D::new(vec![]).is_none());
+        assert!(Distribution1D::new(vec![1., f32::NAN]).is_none());
+        assert!(Distribution1D::new(vec![f32::INFINITY, 1.]).is_none());
+        assert!(Distribution2D::new(&[]).is_none());
+        assert!(Distribution2D::new(&[vec![1.], vec![]]).is_none());
+    }
+
+    #[test]
+    fn two_dimensional_samples_follow_rows_then_columns() {
+        // all the weight in the bottom right cell
+        let dist = Distribution2D::new(&[vec![0., 0.], vec![0., 1.]]).unwrap();
+        for (u1, u2) in [(0., 0.), (0.5, 0.5), (0.99, 0.2)] {
+            let ((x, y), pdf) = dist.sample(u1, u2);
+            assert!(x >= 0.5 && y >= 0.5);
+            assert_eq!(pdf, 4.);
+            assert_eq!(dist.pdf(x, y), 4.);
+        }
+        assert_eq!(dist.pdf(0.2, 0.2), 0.);
+    }
+}
+
+}
@@ -0,0 +1,160 @@
+pub mod module {
+
+use crate::types::module::*;
+use crate::vec3::module::*;
+use crate::distribution::module::*;
+use crate::hdr::module::*;
+
+use std::f32::consts;
+use std::io::{Error, ErrorKind};
+
+// Equirectangular (latitude/longitude) environment map surrounding the
+// scene.  The top row looks straight up (+y) and the center of the
+// image looks down -z.  It can be importance sampled in proportion to
+// its luminance so small bright regions like the sun are found by
+// light samples instead of by chance.
+pub struct EnvironmentMap {
+    img: FloatImage,
+    intensity: f32,
+    sin_rot: f32,
+    cos_rot: f32,
+    dist: Distribution2D
+}
+
+impl EnvironmentMap {
+    // rotation: about the y axis in degrees
+    // intensity: scales the radiance of the map
+    // None for an image without pixels or with ones that aren't finite
+    pub fn new(img: FloatImage, rotation: f32, intensity: f32) -> Option<EnvironmentMap> {
+        let height = img.len();
+        // weight by sin(theta), the area each row covers on the sphere
+        let weights: Vec<Vec<f32>> = img.iter().enumerate().map(|(row, cols)| {
+            let sin_theta = (consts::PI * (row as f32 + 0.5) / height as f32).sin();
+            cols.iter().map(|c| luminance(*c) * sin_theta).collect()
+        }).collect();
+        let dist = Distribution2D::new(&weights)?;
+        let radians = rotation.to_radians();
+        Some(EnvironmentMap {
+            img,
+            intensity,
+            sin_rot: radians.sin(),
+            cos_rot: radians.cos(),
+            dist
+        })
+    }
+
+    pub fn load(path: &str, rotation: f32, intensity: f32) -> std::io::Result<EnvironmentMap> {
+        EnvironmentMap::new(read_hdr(path)?, rotation, intensity).ok_or_else(|| Error::new(
+            ErrorKind::InvalidData, format!("{}: pixels too bright to sample", path)))
+    }
+
+    // world direction -> map direction
+    fn to_map(&self, d: Vec3) -> Vec3 {
+        Vec3::new(self.cos_rot * d.x() - self.sin_rot * d.z(),
+                  d.y(),
+                  self.sin_rot * d.x() + self.cos_rot * d.z())
+    }
+
+    fn to_world(&self, d: Vec3) -> Vec3 {
+        Vec3::new(self.cos_rot * d.x() + self.sin_rot * d.z(),
+                  d.y(),
+                  -self.sin_rot * d.x() + self.cos_rot * d.z())
+    }
+
+    // map direction -> (x, y) in [0, 1)^2, y = 0 at the top
+    fn direction_to_uv(d: Vec3) -> (f32, f32) {
+        let d = Vec3::unit_vector(d);
+        let phi = d.x().atan2(-d.z()); // 0 looking down -z
+        let theta = d.y().clamp(-1., 1.).acos();
+        (0.5 + phi / (2. * consts::PI), theta / consts::PI)
+    }
+
+    fn uv_to_direction(x: f32, y: f32) -> Vec3 {
+        let phi = (x - 0.5) * 2. * consts::PI;
+        let theta = y * consts::PI;
+        Vec3::new(theta.sin() * phi.sin(), theta.cos(), -theta.sin() * phi.cos())
+    }
+
+    pub fn radiance(&self, dir: Vec3) -> Color {
+        let (x, y) = EnvironmentMap::direction_to_uv(self.to_map(dir));
+        let height = self.img.len();
+        let width = self.img[0].len();
+        let row = ((y * height as f32) as usize).min(height - 1);
+        let col = ((x * width as f32) as usize).min(width - 1);
+        self.intensity * self.img[row][col]
+    }
+
+    // Picks a direction towards the map in proportion to its luminance.
+    // Returns the world direction and its pdf per unit solid angle.
+    pub fn sample(&self, u1: f32, u2: f32) -> (Vec3, f32) {
+        let ((x, y), pdf_uv) = self.dist.sample(u1, u2);
+        let dir = EnvironmentMap::uv_to_direction(x, y);
+        let sin_theta = (y * consts::PI).sin();
+        let pdf = if sin_theta > 0. {
+            pdf_uv / (2. * consts::PI * consts::PI * sin_theta)
+        }
+        else {
+            0.
+        };
+        (self.to_world(dir), pdf)
+    }
+
+    // pdf per unit solid angle of sample() returning dir
+    pub fn pdf(&self, dir: Vec3) -> f32 {
+        let (x, y) = EnvironmentMap::direction_to_uv(self.to_map(dir));
+        let sin_theta = (y * consts::PI).sin();
+        if sin_theta <= 0. {
+            return 0.;
+        }
+        self.dist.pdf(x, y) / (2. * consts::PI * consts::PI * sin_theta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checker_map(rotation: f32) -> EnvironmentMap {
+        let img: FloatImage = (0..4).map(|row| (0..8).map(|col| {
+            let v = (1 + row * 8 + col) as f32 / 8.;
+            Color::new(v, v, v)
+        }).collect()).collect();
+        EnvironmentMap::new(img, rotation, 1.).unwrap()
+    }
+
+    #[test]
+    fn sampled_pdfs_match_the_pdf_of_their_direction() {
+        let map = checker_map(30.);
+        for i in 0..64 {
+            let (u1, u2) = ((i % 8) as f32 / 8. + 0.05, (i / 8) as f32 / 8. + 0.03);
+            let (dir, pdf) = map.sample(u1, u2);
+            assert!((dir.length() - 1.).abs() < 1e-4);
+            assert!((map.pdf(dir) - pdf).abs() < 1e-3 * pdf, "{} vs {}", map.pdf(dir), pdf);
+        }
+    }
+
+    // stratified estimates of the sphere's solid angle and of the map's
+    // radiance over it, weighting each sample by 1 / pdf
+    #[test]
+    fn sampling_integrates_the_map_over_the_sphere() {
+        let map = checker_map(0.);
+        let n = 128;
+        let (mut area, mut power) = (0., 0.);
+        for i in 0..n * n {
+            let (u1, u2) = (((i % n) as f32 + 0.5) / n as f32, ((i / n) as f32 + 0.5) / n as f32);
+            let (dir, pdf) = map.sample(u1, u2);
+            area += 1. / pdf;
+            power += map.radiance(dir).r() / pdf;
+        }
+        let (area, power) = (area / (n * n) as f32, power / (n * n) as f32);
+        // each pixel covers 2 pi / 8 in azimuth and a band of cos(theta)
+        let exact: f32 = (0..4).map(|row| (0..8).map(|col| {
+            let band = (consts::PI * row as f32 / 4.).cos() - (consts::PI * (row + 1) as f32 / 4.).cos();
+            (1 + row * 8 + col) as f32 / 8. * band * 2. * consts::PI / 8.
+        }).sum::<f32>()).sum();
+        assert!((area - 4. * consts::PI).abs() < 0.01 * 4. * consts::PI, "{}", area);
+        assert!((power - exact).abs() < 0.01 * exact, "{} vs {}", power, exact);
+    }
+}
+
+}
@@ -0,0 +1,290 @@
+pub mod module {
+
+use crate::types::module::*;
+
+use std::f32::consts;
+
+// A reconstruction filter weights a sample by its offset (x, y) in
+// pixels from a pixel's center.  It is zero at or beyond radius().
+pub trait Filter {
+    fn radius(&self) -> f32;
+    fn evaluate(&self, x: f32, y: f32) -> f32;
+}
+
+pub const FILTER_NAMES: [&str; 5] = ["box", "tent", "gaussian", "mitchell", "lanczos"];
+
+// the radius each filter is usually used with, in pixels
+pub fn default_filter_radius(name: &str) -> f32 {
+    match name {
+        "box"      => 0.5,
+        "tent"     => 1.,
+        "gaussian" => 1.5,
+        "mitchell" => 2.,
+        "lanczos"  => 3.,
+        _ => 1.
+    }
+}
+
+pub fn filter_by_name(name: &str, radius: f32) -> Option<Box<dyn Filter>> {
+    let filter: Box<dyn Filter> = match name {
+        "box"      => Box::new(BoxFilter::new(radius)),
+        "tent"     => Box::new(TentFilter::new(radius)),
+        "gaussian" => Box::new(GaussianFilter::new(radius, 2.)),
+        "mitchell" => Box::new(MitchellFilter::new(radius, 1. / 3., 1. / 3.)),
+        "lanczos"  => Box::new(LanczosFilter::new(radius)),
+        _ => return None
+    };
+    Some(filter)
+}
+
+// equal weight everywhere inside the radius.  With radius 0.5 each
+// sample only counts towards the pixel it was taken in.
+pub struct BoxFilter {
+    radius: f32
+}
+
+impl BoxFilter {
+    pub fn new(radius: f32) -> BoxFilter {
+        BoxFilter { radius }
+    }
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> f32 { self.radius }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        // half open, so a sample on the edge between two pixels counts
+        // towards exactly one of them
+        let inside = |v: f32| -self.radius <= v && v < self.radius;
+        if inside(x) && inside(y) { 1. } else { 0. }
+    }
+}
+
+// linear falloff to zero at the radius
+pub struct TentFilter {
+    radius: f32
+}
+
+impl TentFilter {
+    pub fn new(radius: f32) -> TentFilter {
+        TentFilter { radius }
+    }
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> f32 { self.radius }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        (self.radius - x.abs()).max(0.) * (self.radius - y.abs()).max(0.)
+    }
+}
+
+// gaussian shifted down so it reaches zero at the radius
+pub struct GaussianFilter {
+    radius: f32,
+    alpha: f32, // falloff rate
+    exp_r: f32  // value at the radius
+}
+
+impl GaussianFilter {
+    pub fn new(radius: f32, alpha: f32) -> GaussianFilter {
+        GaussianFilter { radius, alpha, exp_r: (-alpha * radius * radius).exp() }
+    }
+
+    fn gaussian(&self, d: f32) -> f32 {
+        ((-self.alpha * d * d).exp() - self.exp_r).max(0.)
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> f32 { self.radius }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.gaussian(x) * self.gaussian(y)
+    }
+}
+
+// Mitchell and Netravali's cubic family ("Reconstruction Filters in
+// Computer Graphics", 1988).  b = c = 1/3 is their recommendation.
+// It has small negative lobes that sharpen edges.
+pub struct MitchellFilter {
+    radius: f32,
+    b: f32,
+    c: f32
+}
+
+impl MitchellFilter {
+    pub fn new(radius: f32, b: f32, c: f32) -> MitchellFilter {
+        MitchellFilter { radius, b, c }
+    }
+
+    // x: offset scaled to [-2, 2]
+    fn mitchell_1d(&self, x: f32) -> f32 {
+        let (b, c) = (self.b, self.c);
+        let x = x.abs();
+        let value = if x > 2. {
+            0.
+        }
+        else if x > 1. {
+            (-b - 6. * c) * x * x * x + (6. * b + 30. * c) * x * x +
+                (-12. * b - 48. * c) * x + (8. * b + 24. * c)
+        }
+        else {
+            (12. - 9. * b - 6. * c) * x * x * x + (-18. + 12. * b + 6. * c) * x * x +
+                (6. - 2. * b)
+        };
+        value / 6.
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> f32 { self.radius }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.mitchell_1d(2. * x / self.radius) * self.mitchell_1d(2. * y / self.radius)
+    }
+}
+
+// sinc windowed by a wider sinc reaching its first zero at the radius
+pub struct LanczosFilter {
+    radius: f32
+}
+
+impl LanczosFilter {
+    pub fn new(radius: f32) -> LanczosFilter {
+        LanczosFilter { radius }
+    }
+
+    fn lanczos_1d(&self, x: f32) -> f32 {
+        if x.abs() >= self.radius {
+            return 0.;
+        }
+        sinc(x) * sinc(x / self.radius)
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    let px = consts::PI * x;
+    if px.abs() < 1e-5 { 1. } else { px.sin() / px }
+}
+
+impl Filter for LanczosFilter {
+    fn radius(&self) -> f32 { self.radius }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.lanczos_1d(x) * self.lanczos_1d(y)
+    }
+}
+
+// least filter weight per sample taken in a pixel for its filtered
+// average to be trusted
+const MIN_WEIGHT_PER_SAMPLE: f32 = 0.1;
+
+// Collects samples at continuous raster positions and reconstructs
+// pixels as the filter weighted average of the samples around them.
+// A sample counts towards every pixel whose center is within the
+// filter radius, not just the one it was taken in.
+pub struct Film {
+    pub nx: u32,
+    pub ny: u32,
+    pub filter: Box<dyn Filter>,
+    pub sums: Vec<Color>, // [row * nx + col], row 0 at the top
+    pub weights: Vec<f32>,
+    // plain sum and count of the samples taken in each pixel, for
+    // pixels whose filter weights cancel out
+    pub box_sums: Vec<Color>,
+    pub box_counts: Vec<u32>
+}
+
+impl Film {
+    pub fn new(nx: u32, ny: u32, filter: Box<dyn Filter>) -> Film {
+        let n = (nx * ny) as usize;
+        Film {
+            nx, ny, filter,
+            sums: vec![Color::init(); n],
+            weights: vec![0.; n],
+            box_sums: vec![Color::init(); n],
+            box_counts: vec![0; n]
+        }
+    }
+
+    // x, y: raster position with (0, 0) the top left corner of the image
+    // and pixel centers at half integers
+    pub fn add_sample(&mut self, x: f32, y: f32, c: Color) {
+        let radius = self.filter.radius();
+        let col0 = (x - 0.5 - radius).floor().max(0.) as u32;
+        let row0 = (y - 0.5 - radius).floor().max(0.) as u32;
+        let col1 = ((x - 0.5 + radius).ceil().max(-1.) as i64).min(self.nx as i64 - 1);
+        let row1 = ((y - 0.5 + radius).ceil().max(-1.) as i64).min(self.ny as i64 - 1);
+        for row in row0 as i64..=row1 {
+            for col in col0 as i64..=col1 {
+                let w = self.filter.evaluate(x - (col as f32 + 0.5), y - (row as f32 + 0.5));
+                if w != 0. {
+                    let idx = (row * self.nx as i64 + col) as usize;
+                    self.sums[idx] += w * c;
+                    self.weights[idx] += w;
+                }
+            }
+        }
+        if x >= 0. && y >= 0. && x < self.nx as f32 && y < self.ny as f32 {
+            let idx = y as usize * self.nx as usize + x as usize;
+            self.box_sums[idx] += c;
+            self.box_counts[idx] += 1;
+        }
+    }
+
+    pub fn image(&self) -> FloatImage {
+        (0..self.ny as usize).map(|row| (0..self.nx as usize).map(|col| {
+            let idx = row * self.nx as usize + col;
+            let (w, n) = (self.weights[idx], self.box_counts[idx]);
+            // negative lobes can cancel out the weight, leaving a
+            // filtered average that's black or blown up
+            if w <= 0. || w < MIN_WEIGHT_PER_SAMPLE * n as f32 {
+                return if n > 0 { self.box_sums[idx] / n as f32 } else { Color::init() };
+            }
+            // negative lobes can undershoot next to bright edges
+            let c = self.sums[idx] / w;
+            Color::new(c.r().max(0.), c.g().max(0.), c.b().max(0.))
+        }).collect()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn box_filter_keeps_samples_on_pixel_edges() {
+        let mut film = Film::new(2, 2, Box::new(BoxFilter::new(0.5)));
+        // on the corner shared by all four pixels
+        film.add_sample(1., 1., Color::new(1., 1., 1.));
+        let total: f32 = film.weights.iter().sum();
+        assert_eq!(total, 1.);
+    }
+
+    #[test]
+    fn mitchell_weights_vanish_at_the_radius() {
+        let mut film = Film::new(3, 1, filter_by_name("mitchell", 2.).unwrap());
+        // two pixels from the first pixel's center
+        film.add_sample(2.5, 0.5, Color::new(1., 1., 1.));
+        assert!(film.weights[0].abs() < 1e-6);
+        assert!(film.weights[1] > 0.);
+    }
+
+    // A pixel's own sample near its edge, and its neighbours' in the
+    // negative lobe near the radius, almost cancel out: the pixel takes
+    // the plain average of its own samples rather than blowing up
+    #[test]
+    fn pixels_with_cancelled_weights_fall_back_to_their_own_samples() {
+        let mut film = Film::new(3, 1, filter_by_name("mitchell", 1.).unwrap());
+        film.add_sample(1.05, 0.5, Color::new(1., 1., 1.));
+        film.add_sample(0.75, 0.5, Color::init());
+        film.add_sample(2.25, 0.5, Color::init());
+        assert!(film.weights[1] > 0. && film.weights[1] < MIN_WEIGHT_PER_SAMPLE);
+        let img = film.image();
+        assert!(film.sums[1].r() / film.weights[1] > 2.);
+        assert!((img[0][1].r() - 1.).abs() < 1e-6);
+    }
+}
+
+}
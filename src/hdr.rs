@@ -0,0 +1,109 @@
+pub mod module {
+
+use crate::types::module::*;
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read};
+
+// Reads a Radiance RGBE (.hdr / .pic) image into img[row][col], row 0
+// at the top.  Handles flat and run length encoded scanlines.
+pub fn read_hdr(path: &str) -> std::io::Result<FloatImage> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let bad = |msg: &str| Error::new(ErrorKind::InvalidData, format!("{}: {}", path, msg));
+
+    // text header up to a blank line, then the resolution line
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if !line.starts_with("#?") {
+        return Err(bad("not a Radiance HDR file"));
+    }
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(bad("truncated header"));
+        }
+        let l = line.trim();
+        if l.is_empty() {
+            break;
+        }
+        if let Some(format) = l.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(bad("only 32-bit_rle_rgbe is supported"));
+            }
+        }
+    }
+    line.clear();
+    reader.read_line(&mut line)?;
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() != 4 || fields[0] != "-Y" || fields[2] != "+X" {
+        return Err(bad("only -Y h +X w orientation is supported"));
+    }
+    let height: usize = fields[1].parse().map_err(|_| bad("bad height"))?;
+    let width: usize = fields[3].parse().map_err(|_| bad("bad width"))?;
+    if width == 0 || height == 0 {
+        return Err(bad("image has no pixels"));
+    }
+
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let mut pos = 0;
+    let mut img = Vec::with_capacity(height);
+    let mut scanline = vec![[0u8; 4]; width];
+    for _ in 0..height {
+        pos = read_scanline(&data, pos, &mut scanline).ok_or_else(|| bad("truncated pixel data"))?;
+        img.push(scanline.iter().map(|&rgbe| rgbe_to_color(rgbe)).collect());
+    }
+    Ok(img)
+}
+
+// decodes one scanline starting at data[pos], returning the position
+// after it
+fn read_scanline(data: &[u8], pos: usize, scanline: &mut [[u8; 4]]) -> Option<usize> {
+    let width = scanline.len();
+    let header = data.get(pos..pos + 4)?;
+    let rle = (8..0x8000).contains(&width) && header[0] == 2 && header[1] == 2 &&
+              ((header[2] as usize) << 8 | header[3] as usize) == width;
+    if !rle {
+        for (i, px) in scanline.iter_mut().enumerate() {
+            px.copy_from_slice(data.get(pos + 4 * i..pos + 4 * i + 4)?);
+        }
+        return Some(pos + 4 * width);
+    }
+
+    // each of the four channels is run length encoded in turn
+    let mut pos = pos + 4;
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = *data.get(pos)? as usize;
+            pos += 1;
+            if count > 128 {
+                let value = *data.get(pos)?;
+                pos += 1;
+                for _ in 0..count - 128 {
+                    scanline.get_mut(x)?[channel] = value;
+                    x += 1;
+                }
+            }
+            else {
+                for _ in 0..count {
+                    scanline.get_mut(x)?[channel] = *data.get(pos)?;
+                    pos += 1;
+                    x += 1;
+                }
+            }
+        }
+    }
+    Some(pos)
+}
+
+// shared exponent to float
+fn rgbe_to_color(rgbe: [u8; 4]) -> Color {
+    if rgbe[3] == 0 {
+        return Color::init();
+    }
+    let f = 2f32.powi(rgbe[3] as i32 - (128 + 8));
+    Color::new(rgbe[0] as f32 * f, rgbe[1] as f32 * f, rgbe[2] as f32 * f)
+}
+
+}
@@ -0,0 +1,335 @@
+pub mod module {
+
+use crate::types::module::*;
+use crate::vec3::module::*;
+use crate::ray::module::*;
+use crate::hittable::module::*;
+use crate::ppm::module::*;
+
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read};
+
+// half the thickness of a flat heightfield's bounding box
+const FLAT_PAD: f32 = 1e-4;
+
+// a zero size would leave cells nothing to step across
+fn positive(size: Vec3) -> bool {
+    size.x() > 0. && size.y() > 0. && size.z() > 0.
+}
+
+// Terrain from a grid of elevations, split into two triangles per cell
+// and traced by stepping through the cells under the ray, so even a
+// large grid never becomes a mesh.  The grid lies in x and z from the
+// origin to size.x, size.z, with elevations times size.y as heights.
+// Normals are smoothed across the triangles from normals at the grid
+// points.  u runs along x and v along z, with v = 1 at z = 0 so an
+// image of the same size textures it the way it's laid out.
+pub struct Heightfield {
+    heights: Vec<f32>, // row by row along z, in world units
+    normals: Vec<Vec3>,
+    nx: usize, // grid points along x and z
+    nz: usize,
+    cell_x: f32, // size of a cell
+    cell_z: f32,
+    bounds: Aabb,
+    material: u32
+}
+
+impl Heightfield {
+    // elevations[row][col]: rows along z and columns along x, at least
+    // 2 x 2, and size positive in every dimension
+    pub fn new(elevations: &[Vec<f32>], size: Vec3, material: u32) -> Heightfield {
+        let nz = elevations.len();
+        let nx = elevations.first().map_or(0, |row| row.len());
+        assert!(nx >= 2 && nz >= 2, "a heightfield needs at least 2 x 2 elevations");
+        assert!(positive(size), "a heightfield's size must be positive");
+        assert!(elevations.iter().all(|row| row.len() == nx), "heightfield rows differ in length");
+        let heights: Vec<f32> = elevations.iter()
+            .flat_map(|row| row.iter().map(|e| e * size.y()))
+            .collect();
+        let (cell_x, cell_z) = (size.x() / (nx - 1) as f32, size.z() / (nz - 1) as f32);
+
+        // normals from the slope across each grid point's neighbours
+        let mut normals = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let (i0, i1) = (i.saturating_sub(1), (i + 1).min(nx - 1));
+                let (j0, j1) = (j.saturating_sub(1), (j + 1).min(nz - 1));
+                let dx = (heights[j * nx + i1] - heights[j * nx + i0]) / ((i1 - i0) as f32 * cell_x);
+                let dz = (heights[j1 * nx + i] - heights[j0 * nx + i]) / ((j1 - j0) as f32 * cell_z);
+                normals.push(Vec3::unit_vector(Vec3::new(-dx, 1., -dz)));
+            }
+        }
+
+        let low = heights.iter().cloned().fold(f32::INFINITY, f32::min);
+        let high = heights.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let bounds = Aabb::new(Point::new(0., low - FLAT_PAD, 0.),
+                               Point::new(size.x(), high + FLAT_PAD, size.z()));
+        Heightfield { heights, normals, nx, nz, cell_x, cell_z, bounds, material }
+    }
+
+    // elevations from the luminance of an image, row 0 at z = 0, at
+    // least 2 x 2 pixels
+    pub fn from_image(img: &FloatImage, size: Vec3, material: u32) -> Heightfield {
+        let elevations: Vec<Vec<f32>> = img.iter()
+            .map(|row| row.iter().map(|c| luminance(*c)).collect())
+            .collect();
+        Heightfield::new(&elevations, size, material)
+    }
+
+    // a PPM or PGM image, see read_ppm
+    pub fn load(path: &str, size: Vec3, material: u32) -> std::io::Result<Heightfield> {
+        if !positive(size) {
+            return Err(Error::new(ErrorKind::InvalidData,
+                                  format!("{}: a heightfield's size must be positive", path)));
+        }
+        let img = read_ppm(path)?;
+        if img.len() < 2 || img[0].len() < 2 {
+            return Err(Error::new(ErrorKind::InvalidData,
+                                  format!("{}: a heightfield needs at least 2 x 2 pixels", path)));
+        }
+        Ok(Heightfield::from_image(&img, size, material))
+    }
+
+    // A raw file of little endian 32-bit floats, row after row of width
+    // elevations each, as DEM tools export them
+    pub fn load_raw(path: &str, width: usize, size: Vec3, material: u32) -> std::io::Result<Heightfield> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        let bad = |msg: &str| Error::new(ErrorKind::InvalidData, format!("{}: {}", path, msg));
+        if !positive(size) {
+            return Err(bad("a heightfield's size must be positive"));
+        }
+        if width < 2 || bytes.len() % (4 * width) != 0 || bytes.len() < 8 * width {
+            return Err(bad("not whole rows of at least 2 x 2 floats"));
+        }
+        let values: Vec<f32> = bytes.chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        if values.iter().any(|v| !v.is_finite()) {
+            return Err(bad("elevations must be finite"));
+        }
+        let elevations: Vec<Vec<f32>> = values.chunks(width).map(|row| row.to_vec()).collect();
+        Ok(Heightfield::new(&elevations, size, material))
+    }
+
+    fn point(&self, i: usize, j: usize) -> Point {
+        Point::new(i as f32 * self.cell_x, self.heights[j * self.nx + i], j as f32 * self.cell_z)
+    }
+
+    // Intersects the triangle a, b, c (Moller-Trumbore), filling in
+    // the record with normals blended from na, nb, nc
+    #[allow(clippy::too_many_arguments)]
+    fn hit_triangle(&self, r: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord,
+                    (a, b, c): (Point, Point, Point),
+                    (na, nb, nc): (Vec3, Vec3, Vec3)) -> bool {
+        let (e1, e2) = (b - a, c - a);
+        let pvec = Vec3::cross(&r.dir(), &e2);
+        let det = Vec3::dot(&e1, &pvec);
+        if det == 0. {
+            return false;
+        }
+        let inv_det = 1. / det;
+        let tvec = r.origin() - a;
+        let beta = Vec3::dot(&tvec, &pvec) * inv_det;
+        if !(0. ..=1.).contains(&beta) {
+            return false;
+        }
+        let qvec = Vec3::cross(&tvec, &e1);
+        let gamma = Vec3::dot(&r.dir(), &qvec) * inv_det;
+        if gamma < 0. || beta + gamma > 1. {
+            return false;
+        }
+        let t = Vec3::dot(&e2, &qvec) * inv_det;
+        if !(t > t_min && t < t_max) {
+            return false;
+        }
+        let p = r.point_at_parameter(t);
+        let normal = Vec3::unit_vector((1. - beta - gamma) * na + beta * nb + gamma * nc);
+        let x_axis = Vec3::new(1., 0., 0.);
+        let tangent = x_axis - Vec3::dot(&x_axis, &normal) * normal;
+        record.t = t;
+        record.p = p;
+        record.normal = normal;
+        record.u = p.x() / (self.cell_x * (self.nx - 1) as f32);
+        record.v = 1. - p.z() / (self.cell_z * (self.nz - 1) as f32);
+        record.tangent = Vec3::unit_vector(tangent);
+        record.mat = Some(self.material);
+        true
+    }
+
+    // the nearest hit on either triangle of cell i, j
+    fn hit_cell(&self, r: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord, i: usize, j: usize) -> bool {
+        let (p00, p10) = (self.point(i, j), self.point(i + 1, j));
+        let (p01, p11) = (self.point(i, j + 1), self.point(i + 1, j + 1));
+        let n = |i: usize, j: usize| self.normals[j * self.nx + i];
+        let (n00, n10, n01, n11) = (n(i, j), n(i + 1, j), n(i, j + 1), n(i + 1, j + 1));
+        let mut closest = t_max;
+        let mut hit = false;
+        if self.hit_triangle(r, t_min, closest, record, (p00, p10, p11), (n00, n10, n11)) {
+            hit = true;
+            closest = record.t;
+        }
+        if self.hit_triangle(r, t_min, closest, record, (p00, p11, p01), (n00, n11, n01)) {
+            hit = true;
+        }
+        hit
+    }
+}
+
+impl Hittable for Heightfield {
+    // Walks the cells the ray passes over in order (a 2D DDA), so the
+    // first cell with a hit has the nearest one
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        let (t_start, t_end) = match self.bounds.hit(r, t_min, t_max) {
+            Some(span) => span,
+            None => return false
+        };
+        let (o, d) = (r.origin(), r.dir());
+        let (cells_x, cells_z) = (self.nx - 1, self.nz - 1);
+        let start = r.point_at_parameter(t_start);
+        let mut i = ((start.x() / self.cell_x).floor().max(0.) as usize).min(cells_x - 1);
+        let mut j = ((start.z() / self.cell_z).floor().max(0.) as usize).min(cells_z - 1);
+
+        // t of the next cell boundary along each axis, and between them
+        let next = |cell: usize, size: f32, o: f32, d: f32| {
+            if d > 0. {
+                ((cell + 1) as f32 * size - o) / d
+            } else if d < 0. {
+                (cell as f32 * size - o) / d
+            } else {
+                f32::INFINITY
+            }
+        };
+        let mut next_x = next(i, self.cell_x, o.x(), d.x());
+        let mut next_z = next(j, self.cell_z, o.z(), d.z());
+        let delta_x = if d.x() != 0. { self.cell_x / d.x().abs() } else { f32::INFINITY };
+        let delta_z = if d.z() != 0. { self.cell_z / d.z().abs() } else { f32::INFINITY };
+
+        let mut t_enter = t_start;
+        loop {
+            let t_exit = next_x.min(next_z).min(t_end);
+            // skip cells the ray passes wholly above or below
+            let heights = [self.heights[j * self.nx + i], self.heights[j * self.nx + i + 1],
+                           self.heights[(j + 1) * self.nx + i], self.heights[(j + 1) * self.nx + i + 1]];
+            let low = heights.iter().cloned().fold(f32::INFINITY, f32::min);
+            let high = heights.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+            let (y0, y1) = (o.y() + t_enter * d.y(), o.y() + t_exit * d.y());
+            if y0.min(y1) <= high && y0.max(y1) >= low
+                && self.hit_cell(r, t_min, t_max, record, i, j) {
+                return true;
+            }
+            if t_exit >= t_end {
+                return false;
+            }
+            t_enter = t_exit;
+            if next_x < next_z {
+                if d.x() > 0. {
+                    if i + 1 >= cells_x { return false; }
+                    i += 1;
+                } else {
+                    if i == 0 { return false; }
+                    i -= 1;
+                }
+                next_x += delta_x;
+            } else {
+                if d.z() > 0. {
+                    if j + 1 >= cells_z { return false; }
+                    j += 1;
+                } else {
+                    if j == 0 { return false; }
+                    j -= 1;
+                }
+                next_z += delta_z;
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_rejects_images_smaller_than_2_x_2() {
+        let path = std::env::temp_dir().join(format!("heightfield-test-{}.pgm", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "P2 1 3 255 0 128 255").unwrap();
+        let result = Heightfield::load(path, Vec3::new(1., 1., 1.), 0);
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(result.err().map(|e| e.kind()), Some(ErrorKind::InvalidData));
+    }
+
+    #[test]
+    #[should_panic(expected = "rows differ")]
+    fn new_rejects_ragged_rows() {
+        let elevations = vec![vec![0., 1., 2.], vec![0., 1.]];
+        Heightfield::new(&elevations, Vec3::new(1., 1., 1.), 0);
+    }
+
+    // 3 x 3 points over 2 x 2, sloping up along x and z, so the smoothed
+    // normals are the plane's everywhere
+    fn slope() -> Heightfield {
+        let elevations: Vec<Vec<f32>> = (0..3)
+            .map(|j| (0..3).map(|i| 0.5 * i as f32 + 0.25 * j as f32).collect())
+            .collect();
+        Heightfield::new(&elevations, Vec3::new(2., 1., 2.), 0)
+    }
+
+    #[test]
+    fn rays_hit_the_cell_under_them() {
+        let field = slope();
+        let normal = Vec3::unit_vector(Vec3::new(-0.5, 1., -0.25));
+        // origin, direction, t, cell
+        let cases = [
+            // straight down
+            (Point::new(1.5, 5., 1.5), Vec3::new(0., -1., 0.), 3.875, (1, 1)),
+            // diagonally across cells
+            (Point::new(-0.5, 3., -0.5), Vec3::new(1., -1., 1.), 3.375 / 1.75, (1, 1)),
+            // in through the z = 0 side, over the first cell into the next
+            (Point::new(0.5, 0.625, -1.), Vec3::new(0., 0., 1.), 2.5, (0, 1)),
+            // parallel to x
+            (Point::new(-1., 1., 0.5), Vec3::new(1., 0., 0.), 2.75, (1, 0)),
+        ];
+        let mut record = HitRecord::default();
+        for (origin, dir, t, (i, j)) in cases {
+            assert!(field.hit(&Ray::new(origin, dir), 0.001, f32::MAX, &mut record), "{:?}", origin);
+            assert!((record.t - t).abs() < 1e-4, "{:?}: {} != {}", origin, record.t, t);
+            assert!((record.normal - normal).length() < 1e-5);
+            assert_eq!((record.p.x().floor() as usize, record.p.z().floor() as usize), (i, j));
+            assert!((record.u - record.p.x() / 2.).abs() < 1e-6);
+            assert!((record.v - (1. - record.p.z() / 2.)).abs() < 1e-6);
+        }
+        // leaving the slope, passing over it, and beside the grid
+        assert!(!field.hit(&Ray::new(Point::new(1., 5., 1.), Vec3::new(0., 1., 0.)), 0.001, f32::MAX, &mut record));
+        assert!(!field.hit(&Ray::new(Point::new(-1., 2., 0.5), Vec3::new(1., 0., 0.)), 0.001, f32::MAX, &mut record));
+        assert!(!field.hit(&Ray::new(Point::new(3., 5., 1.), Vec3::new(0., -1., 0.)), 0.001, f32::MAX, &mut record));
+    }
+
+    #[test]
+    fn load_rejects_sizes_that_are_not_positive() {
+        let path = std::env::temp_dir().join(format!("heightfield-size-test-{}.pgm", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "P2 2 2 255 0 128 255 64").unwrap();
+        let results: Vec<_> = [Vec3::new(0., 1., 1.), Vec3::new(1., -1., 1.), Vec3::new(1., 1., f32::NAN)]
+            .iter()
+            .map(|size| Heightfield::load(path, *size, 0).err().map(|e| e.kind()))
+            .collect();
+        let ok = Heightfield::load(path, Vec3::new(1., 1., 1.), 0).is_ok();
+        std::fs::remove_file(path).unwrap();
+        assert!(results.iter().all(|kind| *kind == Some(ErrorKind::InvalidData)));
+        assert!(ok);
+    }
+
+    #[test]
+    #[should_panic(expected = "size must be positive")]
+    fn new_rejects_a_flat_size() {
+        Heightfield::new(&[vec![0., 1.], vec![0., 1.]], Vec3::new(1., 1., 0.), 0);
+    }
+}
+
+}
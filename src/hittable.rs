@@ -3,17 +3,104 @@ pub mod module {
 use crate::types::module::*;
 use crate::vec3::module::*;
 use crate::ray::module::*;
+use crate::texture::module::*;
+use crate::rand::module::*;
+
+use std::f32::consts;
+use std::rc::Rc;
 
 #[derive(Clone, Copy, Default)]
 pub struct HitRecord {
     pub t: f32,       // t parameter
     pub p: Point,     // hit location
     pub normal: Vec3, // surface normal
+    pub u: f32,       // surface coordinates
+    pub v: f32,
+    pub tangent: Vec3, // direction u increases in, zero where it's undefined
     pub mat: Option<u32>
 }
 
 pub trait Hittable {
     fn hit(&self, r: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool;
+
+    // a box the whole object fits in, None for unbounded objects
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+// axis aligned bounding box
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    pub min: Point,
+    pub max: Point
+}
+
+impl Aabb {
+    // a, b: any two opposite corners
+    pub fn new(a: Point, b: Point) -> Aabb {
+        Aabb {
+            min: Point::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z())),
+            max: Point::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()))
+        }
+    }
+
+    // the smallest box holding both
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(Point::new(self.min.x().min(other.min.x()),
+                             self.min.y().min(other.min.y()),
+                             self.min.z().min(other.min.z())),
+                  Point::new(self.max.x().max(other.max.x()),
+                             self.max.y().max(other.max.y()),
+                             self.max.z().max(other.max.z())))
+    }
+
+    // the overlap of both, which may be empty (min above max)
+    pub fn intersection(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Point::new(self.min.x().max(other.min.x()),
+                            self.min.y().max(other.min.y()),
+                            self.min.z().max(other.min.z())),
+            max: Point::new(self.max.x().min(other.max.x()),
+                            self.max.y().min(other.max.y()),
+                            self.max.z().min(other.max.z()))
+        }
+    }
+
+    // grown by d on every side
+    pub fn pad(&self, d: f32) -> Aabb {
+        let d = Vec3::new(d, d, d);
+        Aabb { min: self.min - d, max: self.max + d }
+    }
+
+    pub fn corners(&self) -> [Point; 8] {
+        let (a, b) = (self.min, self.max);
+        [Point::new(a.x(), a.y(), a.z()), Point::new(b.x(), a.y(), a.z()),
+         Point::new(a.x(), b.y(), a.z()), Point::new(b.x(), b.y(), a.z()),
+         Point::new(a.x(), a.y(), b.z()), Point::new(b.x(), a.y(), b.z()),
+         Point::new(a.x(), b.y(), b.z()), Point::new(b.x(), b.y(), b.z())]
+    }
+
+    // The part of [t_min, t_max] along r that's inside the box, None if
+    // r misses it (slab test)
+    pub fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<(f32, f32)> {
+        let (mut t0, mut t1) = (t_min, t_max);
+        for axis in 0..3 {
+            let inv_d = 1. / r.dir()[axis];
+            let mut near = (self.min[axis] - r.origin()[axis]) * inv_d;
+            let mut far = (self.max[axis] - r.origin()[axis]) * inv_d;
+            if inv_d < 0. {
+                std::mem::swap(&mut near, &mut far);
+            }
+            // NaN from 0 * inf (ray in the slab's plane) leaves t0, t1 be
+            if near > t0 { t0 = near; }
+            if far < t1 { t1 = far; }
+            if t1 < t0 {
+                return None;
+            }
+        }
+        Some((t0, t1))
+    }
 }
 
 pub struct Sphere {
@@ -53,6 +140,10 @@ impl Hittable for Sphere {
                 record.p = r.point_at_parameter(curr_t);
                 // this is normalized
                 record.normal = (record.p - s.center()) / s.radius();
+                let (u, v) = sphere_uv(&((record.p - s.center()) / s.radius().abs()));
+                record.u = u;
+                record.v = v;
+                record.tangent = sphere_tangent(&(record.p - s.center()));
                 record.mat = Some(self.material);
                 return true;
             }
@@ -63,6 +154,10 @@ impl Hittable for Sphere {
                 record.p = r.point_at_parameter(curr_t);
                 // this is normalized
                 record.normal = (record.p - s.center()) / s.radius();
+                let (u, v) = sphere_uv(&((record.p - s.center()) / s.radius().abs()));
+                record.u = u;
+                record.v = v;
+                record.tangent = sphere_tangent(&(record.p - s.center()));
                 record.mat = Some(self.material);
                 return true;
             }
@@ -70,16 +165,73 @@ impl Hittable for Sphere {
         // failed to hit
         false
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius.abs();
+        Some(Aabb::new(self.center - Vec3::new(r, r, r), self.center + Vec3::new(r, r, r)))
+    }
+}
+
+// p: point on the unit sphere
+// u follows the longitude from -x around through +z, v runs from the
+// south pole (v = 0) to the north pole (v = 1)
+pub fn sphere_uv(p: &Point) -> (f32, f32) {
+    let phi = p.z().atan2(p.x());
+    let theta = p.y().clamp(-1., 1.).asin();
+    let u = 1. - (phi + consts::PI) / (2. * consts::PI);
+    let v = (theta + consts::FRAC_PI_2) / consts::PI;
+    (u, v)
+}
+
+// p: point relative to the center of a sphere.  Returns the unit
+// direction u increases in, zero at the poles.
+pub fn sphere_tangent(p: &Point) -> Vec3 {
+    let t = Vec3::new(p.z(), 0., -p.x());
+    let len = t.length();
+    if len > 0. { t / len } else { Vec3::init() }
 }
 
 // a list of hittable objects
 pub struct HittableList {
-    list: Vec<Box<dyn Hittable>>
+    list: Vec<Box<dyn Hittable>>,
+    // opacity of each material by index, None where it's fully opaque
+    opacity: Vec<Option<Rc<dyn Texture>>>
 }
 
 impl HittableList {
     pub fn new(v: Vec<Box<dyn Hittable>>) -> HittableList {
-        HittableList { list:v }
+        HittableList { list:v, opacity: Vec::new() }
+    }
+
+    // Makes hits on materials with an opacity texture only count where
+    // it's opaque, see Material::opacity.  Only the scene's top-level
+    // list needs this: hit asks each object again from past a
+    // transparent hit, so surfaces inside nested lists, transforms and
+    // CSG solids are cut out too.
+    pub fn set_opacity(&mut self, opacity: Vec<Option<Rc<dyn Texture>>>) {
+        self.opacity = opacity;
+    }
+
+    // Whether a hit stops the ray.  Partly transparent hits stop it with
+    // a chance of their opacity, drawn from a hash of the ray and the
+    // hit so the same ray always decides the same way.
+    fn opaque(&self, r: &Ray, record: &HitRecord) -> bool {
+        let texture = match record.mat.and_then(|m| self.opacity.get(m as usize)) {
+            Some(Some(texture)) => texture,
+            _ => return true
+        };
+        let alpha = luminance(texture.value(record.u, record.v, &record.p));
+        if alpha >= 1. {
+            return true;
+        }
+        if alpha <= 0. {
+            return false;
+        }
+        let (o, d) = (r.origin(), r.dir());
+        let bits = [o.x(), o.y(), o.z(), d.x(), d.y(), d.z(), record.t]
+            .map(|v| v.to_bits() as u64);
+        let u = (hash_values(&bits) >> 40) as f32 / (1u64 << 24) as f32;
+        u < alpha
     }
 }
 
@@ -89,14 +241,58 @@ impl Hittable for HittableList {
         let mut hit_anything = false;
         let mut closest_so_far = t_max;
         for obj in self.list.iter() {
-            if obj.hit(r, t_min, closest_so_far, &mut tmp_record) {
-                hit_anything = true;
-                closest_so_far = tmp_record.t;
-                *record = tmp_record;
+            // look past hits on transparent parts for the next one
+            let mut start = t_min;
+            while obj.hit(r, start, closest_so_far, &mut tmp_record) {
+                if self.opaque(r, &tmp_record) {
+                    hit_anything = true;
+                    closest_so_far = tmp_record.t;
+                    *record = tmp_record;
+                    break;
+                }
+                if tmp_record.t <= start {
+                    break;
+                }
+                start = tmp_record.t;
             }
         }
         hit_anything
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut boxes = self.list.iter().map(|obj| obj.bounding_box());
+        let first = boxes.next()??;
+        boxes.try_fold(first, |acc, b| Some(acc.union(&b?)))
+    }
 }
 
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csg::module::*;
+
+    // material 0 fully transparent, material 1 opaque
+    fn with_cutout(list: Vec<Box<dyn Hittable>>) -> HittableList {
+        let mut world = HittableList::new(list);
+        world.set_opacity(vec![Some(Rc::new(ConstantTexture::new(Color::new(0., 0., 0.)))), None]);
+        world
+    }
+
+    #[test]
+    fn cutouts_apply_inside_nested_objects() {
+        let r = Ray::new(Point::new(0., 0., 5.), Vec3::new(0., 0., -1.));
+        let solid = Csg::difference(Closed::new(Sphere::new(Point::new(0., 0., 0.), 1., 0)),
+                                    Closed::new(Sphere::new(Point::new(0., 2., 0.), 1.5, 1)));
+        let nested = HittableList::new(vec![Box::new(Sphere::new(Point::new(0., 0., 0.), 1., 0))]);
+        for obj in [Box::new(solid) as Box<dyn Hittable>, Box::new(nested)] {
+            let backdrop = Box::new(Sphere::new(Point::new(0., 0., -10.), 1., 1));
+            let world = with_cutout(vec![obj, backdrop]);
+            let mut record = HitRecord::default();
+            assert!(world.hit(&r, 0.001, f32::MAX, &mut record));
+            assert_eq!(record.mat, Some(1));
+            assert!((record.t - 14.).abs() < 1e-3);
+        }
+    }
+}
+
+}
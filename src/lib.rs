@@ -6,4 +6,24 @@ pub mod ray;
 pub mod hittable;
 pub mod camera;
 pub mod material;
-pub mod rand;
\ No newline at end of file
+pub mod rand;
+pub mod texture;
+pub mod perlin;
+pub mod rect;
+pub mod transform;
+pub mod scenes;
+pub mod sampler;
+pub mod checkpoint;
+pub mod film;
+pub mod distribution;
+pub mod animation;
+pub mod hdr;
+pub mod environment;
+pub mod sky;
+pub mod light;
+pub mod microfacet;
+pub mod spectrum;
+pub mod sdf;
+pub mod csg;
+pub mod quadric;
+pub mod heightfield;
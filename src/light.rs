@@ -0,0 +1,162 @@
+pub mod module {
+
+use crate::types::module::*;
+use crate::vec3::module::*;
+
+// Light arriving at a point from a light, found by sample_li
+pub struct LightSample {
+    pub wi: Vec3, // unit direction from the point towards the light
+    pub dist: f32, // how far the light is along wi, f32::MAX if infinitely far
+    pub li: Color // irradiance at the point from the light, on a surface facing it
+}
+
+// Lights that are infinitely small or far away, which rays can't hit
+// and so are only found by sampling them from each shading point.
+// Intensities are in candela and illuminance in lux, to go with scene
+// radiance in cd/m^2 and meters.
+pub trait Light {
+    // None when p gets no light from this light
+    fn sample_li(&self, p: &Point) -> Option<LightSample>;
+}
+
+// Shines equally in every direction from a point
+pub struct PointLight {
+    position: Point,
+    intensity: Color // cd
+}
+
+impl PointLight {
+    pub fn new(position: Point, intensity: Color) -> PointLight {
+        PointLight { position, intensity }
+    }
+}
+
+impl Light for PointLight {
+    fn sample_li(&self, p: &Point) -> Option<LightSample> {
+        let to_light = self.position - *p;
+        let dist2 = to_light.squared_length();
+        if dist2 == 0. {
+            return None;
+        }
+        let dist = dist2.sqrt();
+        Some(LightSample { wi: to_light / dist, dist, li: self.intensity / dist2 })
+    }
+}
+
+// A point light limited to a cone, fading out smoothly between
+// falloff_start and cone_angle away from its axis
+pub struct SpotLight {
+    position: Point,
+    axis: Vec3,
+    intensity: Color, // cd, along the axis
+    cos_cone: f32,
+    cos_falloff: f32
+}
+
+impl SpotLight {
+    // cone_angle, falloff_start: degrees from the axis, the light is at
+    // full intensity inside falloff_start and dark outside cone_angle
+    pub fn new(position: Point, lookat: Point, intensity: Color,
+               cone_angle: f32, falloff_start: f32) -> SpotLight {
+        let falloff_start = falloff_start.min(cone_angle);
+        SpotLight {
+            position,
+            axis: Vec3::unit_vector(lookat - position),
+            intensity,
+            cos_cone: cone_angle.to_radians().cos(),
+            cos_falloff: falloff_start.to_radians().cos()
+        }
+    }
+
+    fn falloff(&self, cos_theta: f32) -> f32 {
+        if cos_theta <= self.cos_cone {
+            return 0.;
+        }
+        if cos_theta >= self.cos_falloff {
+            return 1.;
+        }
+        // smoothstep across the edge of the cone
+        let t = (cos_theta - self.cos_cone) / (self.cos_falloff - self.cos_cone);
+        t * t * (3. - 2. * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample_li(&self, p: &Point) -> Option<LightSample> {
+        let to_light = self.position - *p;
+        let dist2 = to_light.squared_length();
+        if dist2 == 0. {
+            return None;
+        }
+        let dist = dist2.sqrt();
+        let wi = to_light / dist;
+        let falloff = self.falloff(-Vec3::dot(&wi, &self.axis));
+        if falloff == 0. {
+            return None;
+        }
+        Some(LightSample { wi, dist, li: falloff * self.intensity / dist2 })
+    }
+}
+
+// Parallel light from infinitely far away, like the sun
+pub struct DirectionalLight {
+    wi: Vec3, // towards the light
+    illuminance: Color // lux, on a surface facing the light
+}
+
+impl DirectionalLight {
+    // direction: the way the light travels
+    pub fn new(direction: Vec3, illuminance: Color) -> DirectionalLight {
+        DirectionalLight { wi: -Vec3::unit_vector(direction), illuminance }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample_li(&self, _p: &Point) -> Option<LightSample> {
+        Some(LightSample { wi: self.wi, dist: f32::MAX, li: self.illuminance })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point_lights_fall_off_with_the_square_of_distance() {
+        let light = PointLight::new(Point::new(0., 4., 0.), Color::new(8., 8., 8.));
+        let s = light.sample_li(&Point::new(0., 2., 0.)).unwrap();
+        assert_eq!((s.wi.y(), s.dist, s.li.r()), (1., 2., 2.));
+        let s = light.sample_li(&Point::new(0., 0., 0.)).unwrap();
+        assert_eq!((s.dist, s.li.r()), (4., 0.5));
+        assert!(light.sample_li(&Point::new(0., 4., 0.)).is_none());
+    }
+
+    #[test]
+    fn spot_lights_fade_across_the_edge_of_their_cone() {
+        let light = SpotLight::new(Point::new(0., 1., 0.), Point::new(0., 0., 0.),
+                                   Color::new(1., 1., 1.), 45., 30.);
+        // 1 below the light, at angles from the axis on the floor
+        let at = |degrees: f32| {
+            let p = Point::new(degrees.to_radians().tan(), 0., 0.);
+            let dist2 = (Point::new(0., 1., 0.) - p).squared_length();
+            light.sample_li(&p).map_or(0., |s| s.li.r() * dist2)
+        };
+        assert!((at(0.) - 1.).abs() < 1e-6);
+        assert!((at(29.) - 1.).abs() < 1e-6);
+        let (a, b) = (at(33.), at(40.));
+        assert!(1. > a && a > b && b > 0.);
+        assert_eq!(at(46.), 0.);
+        assert!(light.sample_li(&Point::new(0., 2., 0.)).is_none());
+    }
+
+    #[test]
+    fn directional_lights_are_the_same_everywhere() {
+        let light = DirectionalLight::new(Vec3::new(0., -2., 0.), Color::new(3., 3., 3.));
+        for p in [Point::new(0., 0., 0.), Point::new(100., -5., 7.)] {
+            let s = light.sample_li(&p).unwrap();
+            assert_eq!((s.wi.y(), s.dist, s.li.r()), (1., f32::MAX, 3.));
+        }
+    }
+}
+
+}
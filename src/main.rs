@@ -1,15 +1,462 @@
 use rayutils::ramp::module as ramp;
 use rayutils::ppm::module as ppm;
+use rayutils::scenes::module as scenes;
+use rayutils::sampler::module as sampler;
+use rayutils::rand::module as rand;
+use rayutils::checkpoint::module as checkpoint;
+use rayutils::film::module as film;
+use rayutils::camera::module as camera;
+use rayutils::animation::module as animation;
+use rayutils::environment::module as environment;
+use rayutils::sky::module as sky;
 
-fn generate_image(path: &str) -> std::io::Result<()> {
-    let img = ramp::raytrace();
-    ppm::write_ppm(&img, path)?;
-    Ok(())
+use std::io::{Error, ErrorKind};
+
+struct Options {
+    scene: String,
+    output: String,
+    nx: u32,
+    ny: u32,
+    ns: u32, // num samples / pixel
+    sampler: String,
+    seed: u64,
+    compare_samplers: bool,
+    spectral: bool,
+    adaptive: Option<f32>, // target relative error, spp becomes the max
+    batch: u32,
+    heatmap: Option<String>,
+    progressive: Option<u32>, // samples per pass
+    checkpoint: Option<String>,
+    checkpoint_every: u32,
+    resume: Option<String>,
+    filter: String,
+    filter_radius: Option<f32>, // defaults per filter
+    camera: String,
+    fov: Option<f32>, // overrides the scene's field of view
+    lens: Option<camera::PhysicalLens>,
+    blades: Option<u32>,
+    blade_rotation: f32,
+    aperture_mask: Option<String>,
+    stereo: Option<camera::StereoLayout>,
+    interocular: f32,
+    convergence: Option<f32>, // defaults to the scene's focus distance
+    frames: Option<(u32, u32)>, // inclusive range
+    keyframes: Option<String>,
+    turntable: Option<u32>, // frames per turn
+    interpolation: animation::Interpolation,
+    environment: Option<String>, // .hdr replacing the scene's background
+    env_rotation: f32,
+    env_intensity: f32,
+    sun: Option<(f32, f32)>, // elevation, azimuth in degrees; enables the physical sky
+    turbidity: f32,
+    sky_intensity: Option<f32> // defaults to 1 with a physical lens, 1e-4 otherwise
+}
+
+const USAGE: &str = "usage: ray_trace_weekend [scene] [--output path] [--width n] [--height n]
+                         [--spp n] [--sampler name] [--seed n] [--compare-samplers] [--spectral]
+                         [--adaptive error] [--batch n] [--heatmap path]
+                         [--progressive n] [--checkpoint path] [--checkpoint-every n]
+                         [--resume path] [--filter name] [--filter-radius r]
+                         [--camera name] [--fov degrees]
+                         [--focal-length mm] [--sensor WxH] [--f-stop n] [--shutter s] [--iso n]
+                         [--blades n] [--blade-rotation degrees] [--aperture-mask path]
+                         [--stereo sbs|tb] [--interocular d] [--convergence d]
+                         [--frames first:last] [--keyframes path] [--turntable n]
+                         [--interpolation linear|catmull-rom]
+                         [--environment path.hdr] [--env-rotation degrees] [--env-intensity x]
+                         [--sun elevation:azimuth] [--turbidity t] [--sky-intensity x]";
+
+fn invalid(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, format!("{}\n{}", msg, USAGE))
+}
+
+fn parse_args(args: &[String]) -> std::io::Result<Options> {
+    let mut opts = Options {
+        scene: "random_spheres".to_string(),
+        output: "output.ppm".to_string(),
+        nx: 1200,
+        ny: 800,
+        ns: 60,
+        sampler: "independent".to_string(),
+        seed: 0,
+        compare_samplers: false,
+        spectral: false,
+        adaptive: None,
+        batch: 8,
+        heatmap: None,
+        progressive: None,
+        checkpoint: None,
+        checkpoint_every: 1,
+        resume: None,
+        filter: "box".to_string(),
+        filter_radius: None,
+        camera: "perspective".to_string(),
+        fov: None,
+        lens: None,
+        blades: None,
+        blade_rotation: 0.,
+        aperture_mask: None,
+        stereo: None,
+        interocular: 0.064,
+        convergence: None,
+        frames: None,
+        keyframes: None,
+        turntable: None,
+        interpolation: animation::Interpolation::CatmullRom,
+        environment: None,
+        env_rotation: 0.,
+        env_intensity: 1.,
+        sun: None,
+        turbidity: 3.,
+        sky_intensity: None
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if !arg.starts_with("--") {
+            opts.scene = arg.clone();
+            continue;
+        }
+        if arg == "--compare-samplers" {
+            opts.compare_samplers = true;
+            continue;
+        }
+        if arg == "--spectral" {
+            opts.spectral = true;
+            continue;
+        }
+        let value = iter.next().ok_or_else(|| invalid(format!("missing value for {}", arg)))?;
+        let number = || value.parse::<u32>()
+            .map_err(|_| invalid(format!("expected a number for {}, got '{}'", arg, value)));
+        let positive = || match number()? {
+            0 => Err(invalid(format!("{} must be at least 1", arg))),
+            n => Ok(n)
+        };
+        let real = || value.parse::<f32>()
+            .map_err(|_| invalid(format!("expected a number for {}, got '{}'", arg, value)));
+        // also accepts fractions like 1/125
+        let fraction = || match value.split_once('/') {
+            Some((num, den)) => match (num.parse::<f32>(), den.parse::<f32>()) {
+                (Ok(num), Ok(den)) => Ok(num / den),
+                _ => Err(invalid(format!("expected a number for {}, got '{}'", arg, value)))
+            },
+            None => real()
+        };
+        // any lens setting switches to a physical lens, defaulting the rest
+        let mut lens = opts.lens.unwrap_or_else(|| camera::PhysicalLens::new(50., 8., 1. / 125., 100.));
+        match arg.as_str() {
+            "--output"  => opts.output = value.clone(),
+            "--width"   => opts.nx = number()?,
+            "--height"  => opts.ny = number()?,
+            "--spp"     => opts.ns = positive()?,
+            "--sampler" => opts.sampler = value.clone(),
+            "--seed"    => opts.seed = number()? as u64,
+            "--adaptive" => opts.adaptive = Some(real()?),
+            "--batch"   => opts.batch = number()?,
+            "--heatmap" => opts.heatmap = Some(value.clone()),
+            "--progressive" => opts.progressive = Some(number()?),
+            "--checkpoint" => opts.checkpoint = Some(value.clone()),
+            "--checkpoint-every" => opts.checkpoint_every = number()?,
+            "--resume"  => opts.resume = Some(value.clone()),
+            "--filter"  => opts.filter = value.clone(),
+            "--filter-radius" => opts.filter_radius = Some(real()?),
+            "--camera"  => opts.camera = value.clone(),
+            "--fov"     => opts.fov = Some(real()?),
+            "--focal-length" => { lens.focal_length = real()?; opts.lens = Some(lens) }
+            "--f-stop"  => { lens.f_stop = real()?; opts.lens = Some(lens) }
+            "--shutter" => { lens.shutter = fraction()?; opts.lens = Some(lens) }
+            "--iso"     => { lens.iso = real()?; opts.lens = Some(lens) }
+            "--sensor"  => {
+                let (w, h) = value.split_once('x')
+                    .and_then(|(w, h)| Some((w.parse::<f32>().ok()?, h.parse::<f32>().ok()?)))
+                    .ok_or_else(|| invalid(format!("expected WxH for {}, got '{}'", arg, value)))?;
+                lens.sensor_width = w;
+                lens.sensor_height = h;
+                opts.lens = Some(lens)
+            }
+            "--blades"  => opts.blades = Some(number()?),
+            "--blade-rotation" => opts.blade_rotation = real()?,
+            "--aperture-mask" => opts.aperture_mask = Some(value.clone()),
+            "--stereo"  => opts.stereo = Some(match value.as_str() {
+                "sbs" => camera::StereoLayout::SideBySide,
+                "tb"  => camera::StereoLayout::TopBottom,
+                _ => return Err(invalid(format!("expected sbs or tb for --stereo, got '{}'", value)))
+            }),
+            "--interocular" => opts.interocular = real()?,
+            "--convergence" => opts.convergence = Some(real()?),
+            "--frames"  => {
+                let range = value.split_once(':')
+                    .and_then(|(a, b)| Some((a.parse::<u32>().ok()?, b.parse::<u32>().ok()?)))
+                    .ok_or_else(|| invalid(format!("expected first:last for {}, got '{}'", arg, value)))?;
+                opts.frames = Some(range)
+            }
+            "--environment" => opts.environment = Some(value.clone()),
+            "--env-rotation" => opts.env_rotation = real()?,
+            "--env-intensity" => opts.env_intensity = real()?,
+            "--sun"     => {
+                let angles = value.split_once(':')
+                    .and_then(|(a, b)| Some((a.parse::<f32>().ok()?, b.parse::<f32>().ok()?)))
+                    .ok_or_else(|| invalid(format!("expected elevation:azimuth for {}, got '{}'", arg, value)))?;
+                opts.sun = Some(angles)
+            }
+            "--turbidity" => opts.turbidity = real()?,
+            "--sky-intensity" => opts.sky_intensity = Some(real()?),
+            "--keyframes" => opts.keyframes = Some(value.clone()),
+            "--turntable" => opts.turntable = Some(number()?),
+            "--interpolation" => opts.interpolation = match value.as_str() {
+                "linear" => animation::Interpolation::Linear,
+                "catmull-rom" => animation::Interpolation::CatmullRom,
+                _ => return Err(invalid(format!(
+                    "expected linear or catmull-rom for {}, got '{}'", arg, value)))
+            },
+            _ => return Err(invalid(format!("unknown option {}", arg)))
+        }
+    }
+
+    Ok(opts)
+}
+
+fn build_camera(opts: &Options, view: &camera::View, aspect: f32)
+    -> std::io::Result<Box<dyn camera::Camera>> {
+    let mut view = *view;
+    view.vfov = match (opts.fov, opts.camera.as_str()) {
+        (Some(fov), _) => fov,
+        // a scene's fov is meant for a perspective lens
+        (None, "fisheye") => 180.,
+        (None, _) => view.vfov
+    };
+
+    let aperture = match (&opts.aperture_mask, opts.blades) {
+        (Some(path), _) => Some(camera::Aperture::Mask(camera::ApertureMask::load(path)?)),
+        (None, Some(blades)) => Some(camera::Aperture::Polygon {
+            blades, rotation: opts.blade_rotation
+        }),
+        (None, None) => None
+    };
+
+    if let Some(layout) = opts.stereo {
+        let convergence = opts.convergence.unwrap_or(view.focus_dist);
+        return match opts.camera.as_str() {
+            "perspective" => Ok(Box::new(camera::StereoCamera::perspective(
+                &view, aspect, opts.lens.as_ref(), aperture.as_ref(),
+                opts.interocular, convergence, layout))),
+            "equirectangular" => Ok(Box::new(camera::StereoCamera::omnidirectional(
+                &view, opts.interocular, layout))),
+            _ => Err(invalid(format!("stereo needs the perspective or equirectangular camera, not '{}'",
+                                     opts.camera)))
+        };
+    }
+
+    // lens settings and aperture shapes only apply to the thin lens
+    if opts.camera == "perspective" && (opts.lens.is_some() || aperture.is_some()) {
+        let mut camera = match &opts.lens {
+            Some(lens) => camera::ThinLensCamera::physical(&view, aspect, lens),
+            None => camera::ThinLensCamera::from_view(&view, aspect)
+        };
+        if let Some(aperture) = aperture {
+            camera = camera.with_aperture(aperture);
+        }
+        return Ok(Box::new(camera));
+    }
+
+    camera::camera_by_name(&opts.camera, &view, aspect).ok_or_else(|| {
+        invalid(format!("unknown camera '{}', expected one of: {}",
+                        opts.camera, camera::CAMERA_NAMES.join(", ")))
+    })
+}
+
+// Every setting besides those a checkpoint keeps itself that changes
+// the image, to compare a resumed render's against
+fn image_options(opts: &Options) -> checkpoint::ImageOptions {
+    checkpoint::ImageOptions {
+        spectral: opts.spectral,
+        camera: opts.camera.clone(),
+        fov: opts.fov,
+        lens: opts.lens,
+        blades: opts.blades,
+        blade_rotation: opts.blade_rotation,
+        aperture_mask: opts.aperture_mask.clone(),
+        stereo: opts.stereo,
+        interocular: opts.interocular,
+        convergence: opts.convergence,
+        environment: opts.environment.clone(),
+        env_rotation: opts.env_rotation,
+        env_intensity: opts.env_intensity,
+        sun: opts.sun,
+        turbidity: opts.turbidity,
+        sky_intensity: opts.sky_intensity
+    }
+}
+
+fn generate_image(opts: &Options) -> std::io::Result<()> {
+    // scenes with randomly placed objects are reproducible too
+    rand::seed_rand(opts.seed);
+    let aspect = opts.nx as f32 / opts.ny as f32;
+    let mut scene = match scenes::by_name(&opts.scene, aspect) {
+        Some(scene) => scene,
+        None => {
+            return Err(invalid(format!("unknown scene '{}', expected one of: {}",
+                                       opts.scene, scenes::SCENE_NAMES.join(", "))));
+        }
+    };
+
+    scene.camera = build_camera(opts, &scene.view, aspect)?;
+    scene.spectral |= opts.spectral;
+    match (&opts.environment, opts.sun) {
+        (Some(_), Some(_)) => return Err(invalid("--environment and --sun are exclusive".to_string())),
+        (Some(path), None) => scene.background = scenes::Background::Environment(
+            environment::EnvironmentMap::load(path, opts.env_rotation, opts.env_intensity)?),
+        (None, Some((elevation, azimuth))) => {
+            // the sky is in cd/m^2, which a physical lens exposes for
+            let intensity = opts.sky_intensity.unwrap_or(if opts.lens.is_some() { 1. } else { 1e-4 });
+            let sky = sky::PhysicalSky::new(elevation, azimuth, opts.turbidity, intensity)
+                .ok_or_else(|| invalid(format!("no sky for --sun elevation {} and --turbidity {}: the sun \
+                                                must be 0 to 90 degrees up", elevation, opts.turbidity)))?;
+            scene.background = scenes::Background::PhysicalSky(sky)
+        }
+        (None, None) => {}
+    }
+
+    if opts.compare_samplers {
+        let reference_spp = 16 * opts.ns;
+        for (name, err) in ramp::compare_samplers(&scene, opts.nx, opts.ny, opts.ns,
+                                                  reference_spp, opts.seed) {
+            println!("{:>12}: rmse {:.5}", name, err);
+        }
+        return Ok(());
+    }
+
+    let mut sampler = match sampler::sampler_by_name(&opts.sampler, opts.ns, opts.seed) {
+        Some(sampler) => sampler,
+        None => {
+            return Err(invalid(format!("unknown sampler '{}', expected one of: {}",
+                                       opts.sampler, sampler::SAMPLER_NAMES.join(", "))));
+        }
+    };
+
+    let filter_radius = opts.filter_radius
+        .unwrap_or_else(|| film::default_filter_radius(&opts.filter));
+    let make_filter = || film::filter_by_name(&opts.filter, filter_radius).ok_or_else(|| {
+        invalid(format!("unknown filter '{}', expected one of: {}",
+                        opts.filter, film::FILTER_NAMES.join(", ")))
+    });
+    let filter = make_filter()?;
+
+    if opts.progressive.is_some() || opts.resume.is_some() {
+        if opts.adaptive.is_some() || opts.keyframes.is_some() || opts.turntable.is_some() ||
+            opts.frames.is_some() {
+            return Err(invalid("--progressive and --resume render a single still, without \
+                                --adaptive, --keyframes, --turntable or --frames".to_string()));
+        }
+        if !ramp::RESUMABLE_SAMPLERS.contains(&opts.sampler.as_str()) {
+            return Err(invalid(format!("progressive rendering needs one of the samplers: {}",
+                                       ramp::RESUMABLE_SAMPLERS.join(", "))));
+        }
+        let mut acc = match &opts.resume {
+            Some(path) => checkpoint::Accumulation::load(path)?,
+            None => checkpoint::Accumulation::new(
+                &opts.scene, &opts.sampler, opts.seed, &opts.filter,
+                opts.progressive.unwrap_or(opts.batch), image_options(opts),
+                film::Film::new(opts.nx, opts.ny, filter))
+        };
+        let settings = ramp::ProgressiveSettings {
+            pass_size: acc.pass_size,
+            target_samples: opts.ns,
+            // keep checkpointing to the file we resumed from by default
+            checkpoint: opts.checkpoint.clone().or_else(|| opts.resume.clone()),
+            checkpoint_every: opts.checkpoint_every
+        };
+        ramp::render_progressive(&scene, sampler.as_mut(), &mut acc, &settings)?;
+        ppm::write_ppm(&ramp::to_image(&acc.image()), &opts.output)?;
+        return Ok(());
+    }
+
+    let path = match (&opts.keyframes, opts.turntable) {
+        (Some(keys), _) => Some(animation::CameraPath::new(
+            animation::load_keyframes(keys)?, opts.interpolation)),
+        (None, Some(frames)) => Some(animation::CameraPath::turntable(&scene.view, frames)),
+        (None, None) => None
+    };
+    let frames = match (opts.frames, &path, opts.turntable) {
+        (Some(range), _, _) => Some(range),
+        (None, Some(_), Some(frames)) => Some((0, frames.max(1) - 1)),
+        (None, Some(path), None) => Some((0, path.end_time().ceil() as u32)),
+        (None, None, _) => None
+    };
+
+    if let Some((first, last)) = frames {
+        for frame in first..=last {
+            let view = match &path {
+                Some(path) => path.view_at(frame as f32),
+                None => scene.view
+            };
+            scene.camera = build_camera(opts, &view, aspect)?;
+            // every frame can be rerendered on its own with the same noise
+            let seed = animation::frame_seed(opts.seed, frame);
+            let mut sampler = sampler::sampler_by_name(&opts.sampler, opts.ns, seed).unwrap();
+            render_still(opts, &scene, sampler.as_mut(), seed, make_filter()?, Some(frame))?;
+            println!("Wrote frame {}", frame);
+        }
+        return Ok(());
+    }
+
+    render_still(opts, &scene, sampler.as_mut(), opts.seed, filter, None)
+}
+
+// renders one image with the plain or adaptive sampling loop, numbering
+// the output files when it is a frame of an animation
+fn render_still(opts: &Options, scene: &scenes::Scene, sampler: &mut dyn sampler::Sampler,
+                seed: u64, filter: Box<dyn film::Filter>, frame: Option<u32>) -> std::io::Result<()> {
+    let numbered = |path: &str| match frame {
+        Some(frame) => animation::frame_path(path, frame),
+        None => path.to_string()
+    };
+    let mut film = film::Film::new(opts.nx, opts.ny, filter);
+    match opts.adaptive {
+        Some(target_error) => {
+            let settings = ramp::AdaptiveSettings {
+                batch_size: opts.batch,
+                max_samples: opts.ns,
+                target_error
+            };
+            let counts = ramp::render_adaptive(scene, sampler, seed, &settings, &mut film);
+            if let Some(path) = &opts.heatmap {
+                ppm::write_ppm(&ramp::sample_heatmap(&counts, opts.ns), &numbered(path))?;
+            }
+        }
+        None => ramp::render(scene, sampler, seed, &mut film)
+    };
+    ppm::write_ppm(&ramp::to_image(&film.image()), &numbered(&opts.output))
 }
 
 fn main() -> std::io::Result<()> {
-    generate_image("output.ppm")?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut opts = parse_args(&args)?;
+
+    // A resumed render continues with the checkpoint's scene and settings,
+    // and refuses any others that would change the image
+    if let Some(path) = &opts.resume {
+        let acc = checkpoint::Accumulation::load(path)?;
+        if opts.progressive.is_some_and(|pass_size| pass_size != acc.pass_size) {
+            return Err(invalid(format!("{} was rendered in passes of {} samples", path, acc.pass_size)));
+        }
+        let options = image_options(&opts);
+        if options != acc.options {
+            return Err(invalid(format!("{} was rendered with other settings\n  checkpoint: {:?}\n  now:        {:?}",
+                                       path, acc.options, options)));
+        }
+        opts.progressive = Some(acc.pass_size);
+        opts.scene = acc.scene;
+        opts.sampler = acc.sampler;
+        opts.seed = acc.seed;
+        opts.filter = acc.filter;
+        opts.filter_radius = Some(acc.film.filter.radius());
+        opts.nx = acc.film.nx;
+        opts.ny = acc.film.ny;
+    }
+
+    generate_image(&opts)?;
 
     println!("Finished tracing!");
     Ok(())
-}
\ No newline at end of file
+}
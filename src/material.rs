@@ -4,93 +4,1385 @@ use crate::ray::module::*;
 use crate::hittable::module::*;
 use crate::vec3::module::*;
 use crate::rand::module::*;
+use crate::types::module::*;
+use crate::texture::module::*;
+use crate::sampler::module::*;
+use crate::microfacet::module::*;
+use crate::spectrum::module::*;
+
+use std::rc::Rc;
+use std::f32::consts;
 
 pub trait Material {
     fn scatter(
         &self,
         r_in: &Ray,
         record: &HitRecord,
+        sampler: &mut dyn Sampler,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray) -> bool;
+
+    // scatter(), also telling whether scattered was picked from a
+    // discrete lobe that eval() leaves out, so lights sampled directly
+    // can't have found it.  None where scatter() returns false.  Only
+    // materials with both kinds of lobes need to override it.
+    fn sample(
+        &self,
+        r_in: &Ray,
+        record: &HitRecord,
+        sampler: &mut dyn Sampler,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray) -> Option<bool> {
+        self.scatter(r_in, record, sampler, attenuation, scattered).then_some(false)
+    }
+
+    // light given off by the surface itself
+    fn emitted(&self, _record: &HitRecord) -> Color {
+        Color::init()
+    }
+
+    // For light arriving from direction wi, returns the brdf times the
+    // cosine term and the pdf that scatter() picks wi with.  Used to
+    // sample lights directly.  None for materials that only scatter in
+    // discrete directions (mirrors, glass), which lights can't be
+    // sampled for.
+    fn eval(&self, _r_in: &Ray, _record: &HitRecord, _wi: Vec3) -> Option<(Color, f32)> {
+        None
+    }
+
+    // True for materials whose scattering depends on the wavelength of
+    // r_in (dispersion), so spectral paths through them can only keep
+    // their hero wavelength
+    fn dispersive(&self) -> bool {
+        false
+    }
+
+    // For materials whose inside scatters light, the medium light that
+    // crosses the surface inwards travels through, at r_in's wavelengths
+    // in spectral mode.  The object has to be closed with outward
+    // normals.
+    fn interior(&self, _r_in: &Ray, _record: &HitRecord) -> Option<Medium> {
+        None
+    }
+
+    // For materials with transparent parts, how opaque the surface is
+    // (the texture's luminance, in [0, 1]).  Rays pass through hits
+    // where it's transparent without the material seeing them.
+    fn opacity(&self) -> Option<Rc<dyn Texture>> {
+        None
+    }
+
+    fn get_idx(&self) -> u32;
+}
+
+#[derive(Clone)]
+pub struct Lambertian {
+    albedo: Rc<dyn Texture>,
+    idx: u32 // index in material table
+}
+
+#[derive(Copy, Clone)]
+pub struct Metal {
+    albedo: Vec3,
+    fuzz: f32,
+    idx: u32 // index in material table
+}
+
+// how an index of refraction varies with wavelength
+#[derive(Copy, Clone)]
+pub enum Dispersion {
+    Constant(f32),
+    // n = a + b / lambda^2, lambda in micrometers
+    Cauchy { a: f32, b: f32 },
+    // n^2 = 1 + sum b_i lambda^2 / (lambda^2 - c_i), lambda in micrometers
+    Sellmeier { b: [f32; 3], c: [f32; 3] }
+}
+
+impl Dispersion {
+    // lambda: in nm
+    pub fn ior(&self, lambda: f32) -> f32 {
+        let um = lambda / 1000.;
+        let um2 = um * um;
+        match self {
+            Dispersion::Constant(n) => *n,
+            Dispersion::Cauchy { a, b } => a + b / um2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f32 = (0..3).map(|i| b[i] * um2 / (um2 - c[i])).sum();
+                (1. + sum).sqrt()
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Dielectric {
+    ref_idx: Dispersion,
+    idx: u32 // index in material table
+}
+
+// Rough metal from a GGX microfacet distribution, optionally stretched
+// along the surface tangent for a brushed look
+#[derive(Copy, Clone)]
+pub struct Conductor {
+    eta: Color, // complex index of refraction eta + ik, per channel
+    k: Color,
+    distribution: TrowbridgeReitz,
+    idx: u32 // index in material table
+}
+
+// Glass that may be rough (frosted) and may absorb light as it travels
+// through, using Walter et al. 2007, "Microfacet Models for Refraction
+// through Rough Surfaces"
+#[derive(Copy, Clone)]
+pub struct RoughDielectric {
+    eta: f32, // index of refraction inside over outside
+    distribution: TrowbridgeReitz,
+    absorption: Color, // per unit distance travelled inside
+    idx: u32 // index in material table
+}
+
+// A material parameter that can vary over the surface.  Parameters
+// that are a single number read the texture's luminance.
+#[derive(Clone)]
+pub struct Parameter(Rc<dyn Texture>);
+
+impl Parameter {
+    fn color(&self, record: &HitRecord) -> Color {
+        self.0.value(record.u, record.v, &record.p)
+    }
+
+    fn scalar(&self, record: &HitRecord) -> f32 {
+        luminance(self.color(record))
+    }
+}
+
+impl From<f32> for Parameter {
+    fn from(v: f32) -> Parameter {
+        Parameter(Rc::new(ConstantTexture::new(Color::new(v, v, v))))
+    }
+}
+
+impl From<Color> for Parameter {
+    fn from(c: Color) -> Parameter {
+        Parameter(Rc::new(ConstantTexture::new(c)))
+    }
+}
+
+impl From<Rc<dyn Texture>> for Parameter {
+    fn from(texture: Rc<dyn Texture>) -> Parameter {
+        Parameter(texture)
+    }
+}
+
+// Disney style "principled" material (Burley 2012 and 2015) blending a
+// diffuse base with sheen, a GGX specular layer, glass-like
+// transmission and a clearcoat on top, so assets authored with those
+// parameters can be used as is.  All parameters are in [0, 1].
+#[derive(Clone)]
+pub struct Principled {
+    base_color: Parameter,
+    metallic: Parameter,
+    roughness: Parameter,
+    specular: Parameter, // 0.5 is the 4% reflectance of common dielectrics
+    sheen: Parameter, // retro-reflective fuzz at grazing angles, for cloth
+    clearcoat: Parameter,
+    clearcoat_roughness: Parameter,
+    transmission: Parameter,
+    ior: f32, // of the transmissive part
+    idx: u32 // index in material table
+}
+
+// Blends two materials, e.g. 30% metal over 70% plastic, by a weight
+// that can vary over the surface.  The children's material indices
+// aren't used.  Cut out parts of either child are cut out of the blend
+// by its share of the weight, and a child's interior is the blend's.
+pub struct Mix {
+    a: Box<dyn Material>,
+    b: Box<dyn Material>,
+    weight: Parameter, // of b, in [0, 1]
+    idx: u32 // index in material table
+}
+
+// A clear dielectric coat, like varnish or lacquer, over any base
+// material.  Light reflects off the coat by its Fresnel term and what
+// gets through reaches the base, is tinted on the way in and out and
+// is Fresnel weighted again leaving (Weidlich and Wilkie 2007,
+// "Arbitrarily Layered Micro-Facet Surfaces", without refracting the
+// base's directions).
+pub struct Coated {
+    base: Box<dyn Material>,
+    eta: f32, // of the coat
+    distribution: TrowbridgeReitz,
+    tint: Color, // left of light crossing the coat once, head on
+    idx: u32 // index in material table
+}
+
+// What light meets travelling through the inside of an object, per
+// unit distance
+#[derive(Copy, Clone)]
+pub struct Medium {
+    pub sigma_s: Vec3, // scattering coefficient
+    pub sigma_t: Vec3  // extinction coefficient, scattering plus absorption
+}
+
+// Translucent material for skin, wax, marble and the like.  Light
+// crossing the surface takes a random walk through the inside,
+// scattering isotropically, and leaves somewhere else.  The surface
+// itself transmits diffusely both ways.  Needs a closed object with
+// outward normals.
+#[derive(Clone)]
+pub struct Subsurface {
+    albedo: Parameter, // color of thick parts under white light
+    mean_free_path: Color, // how far light gets inside, per channel, in scene units
+    idx: u32 // index in material table
+}
+
+// where a Bumped material's normals come from
+enum Bump {
+    // tangent space normals stored as colors, 0.5 * (n + 1), with x
+    // along the hit's tangent, y along normal x tangent and z out of
+    // the surface
+    NormalMap(Rc<dyn Texture>),
+    // a height above the surface, whose luminance times the scale is
+    // the displacement (per unit of u and v for image textures, per
+    // unit distance for solid ones)
+    Height(Rc<dyn Texture>, f32)
+}
+
+// Gives any material finer detail than the geometry has by perturbing
+// the normal it shades with.  The perturbed normal is kept tilted
+// towards the incoming ray, and directions that end up on the other
+// side of the true surface than their shading normal says are dropped,
+// so light doesn't leak through.
+pub struct Bumped {
+    base: Box<dyn Material>,
+    bump: Bump,
+    idx: u32 // index in material table
+}
+
+// Cuts any material out where its opacity is below 1, for leaves,
+// fences and the like modelled as flat shapes with an alpha mask.
+// Partial opacity lets that fraction of rays through.  Inside CSG only
+// the surface is cut out, the solid still counts as filled for the
+// operations.
+pub struct Cutout {
+    base: Box<dyn Material>,
+    opacity: Parameter,
+    idx: u32 // index in material table
+}
+
+// complex indices of refraction of common metals, at 650, 550 and 450 nm
+pub const CONDUCTOR_NAMES: [&str; 6] = ["gold", "silver", "copper", "aluminium", "chromium", "iron"];
+
+fn conductor_ior(name: &str) -> Option<(Color, Color)> {
+    let (eta, k) = match name {
+        "gold"      => ([0.143, 0.374, 1.442], [3.983, 2.385, 1.603]),
+        "silver"    => ([0.155, 0.117, 0.138], [4.828, 3.122, 2.147]),
+        "copper"    => ([0.200, 0.924, 1.102], [3.912, 2.452, 2.142]),
+        "aluminium" => ([1.657, 0.880, 0.521], [9.224, 6.270, 4.837]),
+        "chromium"  => ([3.175, 2.959, 2.252], [3.330, 3.330, 3.040]),
+        "iron"      => ([2.870, 2.910, 2.530], [3.080, 2.930, 2.790]),
+        _ => return None
+    };
+    Some((Color::new(eta[0], eta[1], eta[2]), Color::new(k[0], k[1], k[2])))
+}
+
+// an emitter that doesn't scatter
+#[derive(Clone)]
+pub struct DiffuseLight {
+    emit: Rc<dyn Texture>,
+    idx: u32 // index in material table
+}
+
+// shades by the surface normal only, used for debugging geometry
+#[derive(Copy, Clone)]
+pub struct NormalShade {
+    idx: u32 // index in material table
+}
+
+impl Lambertian {
+    pub fn new(albedo: Vec3, idx: u32) -> Lambertian {
+        Lambertian { albedo: Rc::new(ConstantTexture::new(albedo)), idx }
+    }
+
+    pub fn textured(albedo: Rc<dyn Texture>, idx: u32) -> Lambertian {
+        Lambertian { albedo, idx }
+    }
+}
+
+impl Metal {
+    pub fn new(albedo: Vec3, fuzz:f32, idx: u32) -> Metal {
+        let clamped_fuzz = if fuzz < 1. { fuzz } else { 1. };
+        Metal { albedo, fuzz:clamped_fuzz, idx }
+    }
+}
+
+impl Conductor {
+    // roughness: in [0, 1], 0 is a perfect mirror
+    pub fn new(eta: Color, k: Color, roughness: f32, idx: u32) -> Conductor {
+        Conductor::anisotropic(eta, k, roughness, roughness, idx)
+    }
+
+    // roughness_u, roughness_v: along and across the surface tangent
+    pub fn anisotropic(eta: Color, k: Color, roughness_u: f32, roughness_v: f32, idx: u32) -> Conductor {
+        let distribution = TrowbridgeReitz::new(roughness_to_alpha(roughness_u),
+                                                roughness_to_alpha(roughness_v));
+        Conductor { eta, k, distribution, idx }
+    }
+
+    // one of CONDUCTOR_NAMES
+    pub fn preset(name: &str, roughness_u: f32, roughness_v: f32, idx: u32) -> Option<Conductor> {
+        let (eta, k) = conductor_ior(name)?;
+        Some(Conductor::anisotropic(eta, k, roughness_u, roughness_v, idx))
+    }
+}
+
+impl RoughDielectric {
+    // roughness: in [0, 1], 0 is clear glass
+    pub fn new(eta: f32, roughness: f32, idx: u32) -> RoughDielectric {
+        let alpha = roughness_to_alpha(roughness);
+        RoughDielectric {
+            eta,
+            distribution: TrowbridgeReitz::new(alpha, alpha),
+            absorption: Color::init(),
+            idx
+        }
+    }
+
+    // Tints light passing through by Beer-Lambert's law so that after
+    // distance it has been filtered to tint
+    pub fn with_absorption(mut self, tint: Color, distance: f32) -> RoughDielectric {
+        let sigma = |c: f32| -c.clamp(1e-6, 1.).ln() / distance;
+        self.absorption = Color::new(sigma(tint.r()), sigma(tint.g()), sigma(tint.b()));
+        self
+    }
+
+    // How much of r_in's light survived travelling to record, which is
+    // only absorbed when r_in was inside.  Returns the frame at the hit,
+    // wo in it and the index of refraction across the surface from wo.
+    fn setup(&self, r_in: &Ray, record: &HitRecord) -> (Onb, Vec3, f32, Color) {
+        let wo_world = -Vec3::unit_vector(r_in.dir());
+        let frame = shading_frame(record, wo_world);
+        let wo = frame.to_local(wo_world);
+        let entering = Vec3::dot(&wo_world, &record.normal) > 0.;
+        if entering {
+            return (frame, wo, self.eta, Color::new(1., 1., 1.));
+        }
+        let dist = record.t * r_in.dir().length();
+        let a = self.absorption;
+        let transmittance = Color::new((-a.r() * dist).exp(), (-a.g() * dist).exp(), (-a.b() * dist).exp());
+        (frame, wo, 1. / self.eta, transmittance)
+    }
+
+    // Picks reflection or refraction about a visible microfacet normal
+    // in proportion to the Fresnel term, for wo in the local frame and
+    // eta from its side to the other
+    fn sample_wi(&self, wo: Vec3, eta: f32, (u1, u2): (f32, f32), choice: f32) -> Option<Vec3> {
+        let wm = self.distribution.sample_wm(wo, u1, u2);
+        let fresnel = fresnel_dielectric(Vec3::dot(&wo, &wm), eta);
+        let (wi, reflect) = match refract_about(wo, wm, eta) {
+            Some(wt) if choice >= fresnel => (wt, false),
+            _ => (2. * Vec3::dot(&wo, &wm) * wm - wo, true)
+        };
+        // rough enough microfacets can send either one to the wrong side
+        if (wi.z() > 0.) != reflect { None } else { Some(wi) }
+    }
+
+    // brdf or btdf times |cos_i|, and the pdf of sampling wi, for wo
+    // and wi in the local frame with wo above the surface.  eta: from
+    // wo's side to the other.
+    fn evaluate(&self, wo: Vec3, wi: Vec3, eta: f32) -> (f32, f32) {
+        let reflect = wi.z() > 0.;
+        // the generalized half vector
+        let wm = if reflect { wo + wi } else { wo + eta * wi };
+        if wm.squared_length() == 0. || wi.z() == 0. {
+            return (0., 0.);
+        }
+        let wm = Vec3::unit_vector(wm);
+        let wm = if wm.z() < 0. { -wm } else { wm };
+        let (cos_om, cos_im) = (Vec3::dot(&wo, &wm), Vec3::dot(&wi, &wm));
+        // microfacets backfacing either direction
+        if cos_om <= 0. || cos_im * wi.z() <= 0. {
+            return (0., 0.);
+        }
+
+        let fresnel = fresnel_dielectric(cos_om, eta);
+        let d = self.distribution.d(wm);
+        let g = self.distribution.g(wo, wi);
+        let visible = self.distribution.visible_d(wo, wm);
+        if reflect {
+            (d * g * fresnel / (4. * wo.z()), visible / (4. * cos_om) * fresnel)
+        }
+        else {
+            let denom = cos_im + cos_om / eta;
+            let denom2 = denom * denom;
+            // radiance is compressed into a smaller solid angle entering
+            // a denser medium, hence the 1 / eta^2
+            let f = d * g * (1. - fresnel) * (cos_im * cos_om / (wo.z() * denom2)).abs() / (eta * eta);
+            (f, visible * cos_im.abs() / denom2 * (1. - fresnel))
+        }
+    }
+}
+
+impl Principled {
+    // A rough (0.5) dielectric base of the given color, set the rest with
+    // the with_ methods
+    pub fn new<P: Into<Parameter>>(base_color: P, idx: u32) -> Principled {
+        Principled {
+            base_color: base_color.into(),
+            metallic: 0.0.into(),
+            roughness: 0.5.into(),
+            specular: 0.5.into(),
+            sheen: 0.0.into(),
+            clearcoat: 0.0.into(),
+            clearcoat_roughness: 0.03.into(),
+            transmission: 0.0.into(),
+            ior: 1.5,
+            idx
+        }
+    }
+
+    pub fn with_metallic<P: Into<Parameter>>(mut self, p: P) -> Principled {
+        self.metallic = p.into();
+        self
+    }
+
+    pub fn with_roughness<P: Into<Parameter>>(mut self, p: P) -> Principled {
+        self.roughness = p.into();
+        self
+    }
+
+    pub fn with_specular<P: Into<Parameter>>(mut self, p: P) -> Principled {
+        self.specular = p.into();
+        self
+    }
+
+    pub fn with_sheen<P: Into<Parameter>>(mut self, p: P) -> Principled {
+        self.sheen = p.into();
+        self
+    }
+
+    pub fn with_clearcoat<P: Into<Parameter>, Q: Into<Parameter>>(mut self, amount: P, roughness: Q) -> Principled {
+        self.clearcoat = amount.into();
+        self.clearcoat_roughness = roughness.into();
+        self
+    }
+
+    pub fn with_transmission<P: Into<Parameter>>(mut self, p: P, ior: f32) -> Principled {
+        self.transmission = p.into();
+        self.ior = ior;
+        self
+    }
+
+    // the parameters' values at a hit, seen from wo_world
+    fn lobes(&self, record: &HitRecord, wo_world: Vec3) -> PrincipledLobes {
+        let metallic = self.metallic.scalar(record).clamp(0., 1.);
+        let transmission = self.transmission.scalar(record).clamp(0., 1.);
+        // kept rough enough for every lobe to be a continuous one
+        let alpha = roughness_to_alpha(self.roughness.scalar(record)).max(SMOOTH_ALPHA);
+        let cc_alpha = roughness_to_alpha(self.clearcoat_roughness.scalar(record)).max(SMOOTH_ALPHA);
+        let base = self.base_color.color(record);
+        let dielectric_f0 = 0.08 * self.specular.scalar(record).clamp(0., 1.);
+        let white = Color::new(1., 1., 1.);
+        let entering = Vec3::dot(&wo_world, &record.normal) > 0.;
+        PrincipledLobes {
+            base,
+            roughness: alpha.sqrt(),
+            diffuse: (1. - metallic) * (1. - transmission),
+            glass: (1. - metallic) * transmission,
+            sheen: self.sheen.scalar(record),
+            clearcoat: self.clearcoat.scalar(record).clamp(0., 1.),
+            dielectric_f0,
+            f0: (1. - metallic) * dielectric_f0 * white + metallic * base,
+            specular: TrowbridgeReitz::new(alpha, alpha),
+            coat: TrowbridgeReitz::new(cc_alpha, cc_alpha),
+            glass_lobe: RoughDielectric {
+                eta: self.ior,
+                distribution: TrowbridgeReitz::new(alpha, alpha),
+                absorption: Color::init(),
+                idx: self.idx
+            },
+            eta: if entering { self.ior } else { 1. / self.ior }
+        }
+    }
+}
+
+impl Mix {
+    pub fn new<A, B, P>(a: A, b: B, weight: P, idx: u32) -> Mix
+        where A: Material + 'static, B: Material + 'static, P: Into<Parameter> {
+        Mix { a: Box::new(a), b: Box::new(b), weight: weight.into(), idx }
+    }
+
+    // weight of b where the surface was hit, so given that neither
+    // child's transparent part let the ray through
+    fn weight(&self, record: &HitRecord) -> f32 {
+        let weight = self.weight.scalar(record).clamp(0., 1.);
+        let alpha = |m: &dyn Material| m.opacity()
+            .map_or(1., |texture| luminance(texture.value(record.u, record.v, &record.p)).clamp(0., 1.));
+        let (a, b) = ((1. - weight) * alpha(self.a.as_ref()), weight * alpha(self.b.as_ref()));
+        if a + b > 0. { b / (a + b) } else { weight }
+    }
+
+    // Both children by weight, leaving out one that only scatters in
+    // discrete directions, or None if both do
+    fn blend(weight: f32, a: Option<(Color, f32)>, b: Option<(Color, f32)>) -> Option<(Color, f32)> {
+        if a.is_none() && b.is_none() {
+            return None;
+        }
+        let (fa, pdf_a) = a.unwrap_or((Color::init(), 0.));
+        let (fb, pdf_b) = b.unwrap_or((Color::init(), 0.));
+        Some(((1. - weight) * fa + weight * fb, (1. - weight) * pdf_a + weight * pdf_b))
+    }
+}
+
+// opacity of a Mix, its children's blended by its weight
+struct MixOpacity {
+    a: Option<Rc<dyn Texture>>,
+    b: Option<Rc<dyn Texture>>,
+    weight: Rc<dyn Texture>
+}
+
+impl Texture for MixOpacity {
+    fn value(&self, u: f32, v: f32, p: &Point) -> Color {
+        let weight = luminance(self.weight.value(u, v, p)).clamp(0., 1.);
+        let alpha = |t: &Option<Rc<dyn Texture>>| t.as_ref()
+            .map_or(1., |texture| luminance(texture.value(u, v, p)).clamp(0., 1.));
+        let alpha = (1. - weight) * alpha(&self.a) + weight * alpha(&self.b);
+        Color::new(alpha, alpha, alpha)
+    }
+}
+
+impl Coated {
+    // roughness: of the coat in [0, 1], 0 is a smooth varnish
+    pub fn new<M: Material + 'static>(base: M, eta: f32, roughness: f32, idx: u32) -> Coated {
+        // kept rough enough to stay a continuous lobe, so the base can
+        // still be lit directly
+        let alpha = roughness_to_alpha(roughness).max(SMOOTH_ALPHA);
+        Coated {
+            base: Box::new(base),
+            eta,
+            distribution: TrowbridgeReitz::new(alpha, alpha),
+            tint: Color::new(1., 1., 1.),
+            idx
+        }
+    }
+
+    pub fn with_tint(mut self, tint: Color) -> Coated {
+        self.tint = tint;
+        self
+    }
+
+    // fraction of light crossing the coat's surface at cos to the normal
+    fn transmission(&self, cos: f32) -> f32 {
+        1. - fresnel_dielectric(cos.abs(), self.eta)
+    }
+
+    // tint for crossing the coat at cos outside it
+    fn absorption(&self, cos: f32) -> Color {
+        let sin2_t = (1. - cos * cos).max(0.) / (self.eta * self.eta);
+        let path = 1. / (1. - sin2_t).sqrt();
+        Color::new(self.tint.r().powf(path), self.tint.g().powf(path), self.tint.b().powf(path))
+    }
+
+    // chance of sampling the coat rather than the base
+    fn coat_probability(&self, wo: Vec3) -> f32 {
+        fresnel_dielectric(wo.z(), self.eta).clamp(0.05, 0.95)
+    }
+
+    // the coat and base together, or None if the base can't be evaluated
+    fn evaluate(&self, r_in: &Ray, record: &HitRecord, frame: &Onb, wo: Vec3, wi_world: Vec3)
+        -> Option<(Color, f32)> {
+        let (base_f, base_pdf) = self.base.eval(r_in, record, wi_world)?;
+        let wi = frame.to_local(Vec3::unit_vector(wi_world));
+        let through = self.transmission(wo.z()) * self.transmission(wi.z());
+        let mut f = through * self.absorption(wo.z()) * self.absorption(wi.z()) * base_f;
+        let p_coat = self.coat_probability(wo);
+        let mut pdf = (1. - p_coat) * base_pdf;
+        if let Some((coat, coat_pdf, cos_om)) = self.distribution.reflection(wo, wi) {
+            f += Color::new(1., 1., 1.) * (coat * fresnel_dielectric(cos_om, self.eta));
+            pdf += p_coat * coat_pdf;
+        }
+        Some((f, pdf))
+    }
+}
+
+impl Subsurface {
+    pub fn new<P: Into<Parameter>>(albedo: P, mean_free_path: Color, idx: u32) -> Subsurface {
+        Subsurface { albedo: albedo.into(), mean_free_path, idx }
+    }
+
+    // Single scattering albedo and extinction that give roughly the
+    // multiple scattering albedo a and mean free path d, from the fit
+    // in Chiang, Kutz and Burley 2016, "Practical and Controllable
+    // Subsurface Scattering for Production Path Tracing"
+    fn coefficients(a: f32, d: f32) -> (f32, f32) {
+        let a = a.clamp(0., 0.999);
+        let alpha = 1. - (a * (-5.09406 + a * (2.61188 - a * 4.31805))).exp();
+        let s = 1.9 - a + 3.5 * (a - 0.8) * (a - 0.8);
+        let sigma_t = 1. / (d * s).max(1e-6);
+        (alpha * sigma_t, sigma_t)
+    }
+}
+
+impl Cutout {
+    pub fn new<M: Material + 'static, P: Into<Parameter>>(base: M, opacity: P, idx: u32) -> Cutout {
+        Cutout { base: Box::new(base), opacity: opacity.into(), idx }
+    }
+}
+
+// smallest cosine allowed between a shading normal and the direction
+// light leaves in
+const MIN_SHADING_COS: f32 = 0.01;
+// offset bump maps are differentiated over, in u, v and distance
+const BUMP_DELTA: f32 = 1e-3;
+
+impl Bumped {
+    // normals: tangent space normal map, usually an ImageTexture
+    pub fn normal_map<M: Material + 'static>(base: M, normals: Rc<dyn Texture>, idx: u32) -> Bumped {
+        Bumped { base: Box::new(base), bump: Bump::NormalMap(normals), idx }
+    }
+
+    // height: bump map whose luminance times scale is the height
+    pub fn height<M: Material + 'static>(base: M, height: Rc<dyn Texture>, scale: f32, idx: u32) -> Bumped {
+        Bumped { base: Box::new(base), bump: Bump::Height(height, scale), idx }
+    }
+
+    // the perturbed normal on the same side as record.normal
+    fn perturbed(&self, record: &HitRecord) -> Vec3 {
+        let frame = Onb::from_wu(record.normal, record.tangent);
+        match &self.bump {
+            Bump::NormalMap(normals) => {
+                let c = normals.value(record.u, record.v, &record.p);
+                frame.local(Vec3::new(2. * c.r() - 1., 2. * c.g() - 1., 2. * c.b() - 1.))
+            }
+            Bump::Height(height, scale) => {
+                let h = |du: f32, dv: f32| {
+                    let p = record.p + du * frame.u() + dv * frame.v();
+                    luminance(height.value(record.u + du, record.v + dv, &p))
+                };
+                let h0 = h(0., 0.);
+                let slope_u = scale * (h(BUMP_DELTA, 0.) - h0) / BUMP_DELTA;
+                let slope_v = scale * (h(0., BUMP_DELTA) - h0) / BUMP_DELTA;
+                frame.local(Vec3::new(-slope_u, -slope_v, 1.))
+            }
+        }
+    }
+
+    // The hit as the base material sees it, with the shading normal
+    // tilted back towards the geometric normal until wo is at least
+    // slightly above it
+    fn shaded(&self, r_in: &Ray, record: &HitRecord) -> HitRecord {
+        let wo = -Vec3::unit_vector(r_in.dir());
+        let ng = Vec3::unit_vector(record.normal);
+        let mut n = Vec3::unit_vector(self.perturbed(record));
+        if !n.x().is_finite() {
+            n = ng;
+        }
+        // work on wo's side of the surface
+        let side = if Vec3::dot(&wo, &ng) < 0. { -1. } else { 1. };
+        let (n, ng) = (side * n, side * ng);
+        let cos = Vec3::dot(&wo, &n);
+        let n = if cos < MIN_SHADING_COS {
+            let k = (MIN_SHADING_COS - cos) / Vec3::dot(&wo, &ng).max(1e-4);
+            Vec3::unit_vector(n + k * ng)
+        } else {
+            n
+        };
+        let mut shaded = *record;
+        shaded.normal = side * n;
+        shaded
+    }
+
+    // false when wi is on one side of the shading normal but the other
+    // of the geometric one
+    fn consistent(shaded: &HitRecord, record: &HitRecord, wi: Vec3) -> bool {
+        Vec3::dot(&wi, &shaded.normal) * Vec3::dot(&wi, &record.normal) > 0.
+    }
+}
+
+impl Dielectric {
+    pub fn new(ref_idx: f32, idx: u32) -> Dielectric {
+        Dielectric { ref_idx: Dispersion::Constant(ref_idx), idx }
+    }
+
+    // Glass whose index of refraction varies with wavelength, which
+    // splits light into its colors in spectral mode.  RGB renders use the
+    // index at LAMBDA_D.
+    pub fn from_dispersion(ref_idx: Dispersion, idx: u32) -> Dielectric {
+        Dielectric { ref_idx, idx }
+    }
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color, idx: u32) -> DiffuseLight {
+        DiffuseLight { emit: Rc::new(ConstantTexture::new(emit)), idx }
+    }
+
+    pub fn textured(emit: Rc<dyn Texture>, idx: u32) -> DiffuseLight {
+        DiffuseLight { emit, idx }
+    }
+}
+
+impl NormalShade {
+    pub fn new(idx: u32) -> NormalShade {
+        NormalShade { idx }
+    }
+}
+
+impl Material for Lambertian {
+    fn scatter(
+        &self,
+        _: &Ray,
+        record: &HitRecord,
+        sampler: &mut dyn Sampler,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray) -> bool {
+
+        // cosine weighted directions about the normal.  The pdf cancels
+        // the cosine term and the 1/pi of the brdf, leaving the albedo.
+        let uvw = Onb::from_w(record.normal);
+        let (u1, u2) = sampler.get_2d();
+        *scattered = Ray::new(record.p, uvw.local(sample_cosine_hemisphere(u1, u2)));
+        *attenuation = self.albedo.value(record.u, record.v, &record.p);
+
+        true
+    }
+
+    fn eval(&self, _: &Ray, record: &HitRecord, wi: Vec3) -> Option<(Color, f32)> {
+        let cosine = Vec3::dot(&Vec3::unit_vector(record.normal), &Vec3::unit_vector(wi));
+        let albedo = self.albedo.value(record.u, record.v, &record.p);
+        Some((albedo * (cosine.max(0.) * consts::FRAC_1_PI), cosine_hemisphere_pdf(cosine)))
+    }
+
+    fn get_idx(&self) -> u32 {
+        self.idx
+    }
+}
+
+fn reflect(v: Vec3, n: Vec3) -> Vec3 {
+    v - 2. * Vec3::dot(&v, &n) * n
+}
+
+impl Material for Metal {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        record: &HitRecord,
+        sampler: &mut dyn Sampler,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray) -> bool {
+
+        let reflected = reflect(Vec3::unit_vector(r_in.dir()), record.normal);
+        // uniform point in the unit ball around the reflected direction
+        let (u1, u2) = sampler.get_2d();
+        let radius = sampler.get_1d().cbrt();
+        let fuzz = self.fuzz * radius * sample_uniform_sphere(u1, u2);
+        *scattered = Ray::new(record.p, reflected + fuzz);
+        *attenuation = self.albedo;
+
+        Vec3::dot(&scattered.dir(), &record.normal) > 0.
+    }
+
+    fn get_idx(&self) -> u32 {
+        self.idx
+    }
+}
+
+// Local frame at a hit with the normal on the side wo is on and the
+// tangent as its x axis
+fn shading_frame(record: &HitRecord, wo: Vec3) -> Onb {
+    let n = if Vec3::dot(&wo, &record.normal) < 0. { -record.normal } else { record.normal };
+    Onb::from_wu(n, record.tangent)
+}
+
+impl Material for Conductor {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        record: &HitRecord,
+        sampler: &mut dyn Sampler,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray) -> bool {
+
+        let wo_world = -Vec3::unit_vector(r_in.dir());
+        let frame = shading_frame(record, wo_world);
+        let wo = frame.to_local(wo_world);
+        let (u1, u2) = sampler.get_2d();
+        if wo.z() <= 0. {
+            return false;
+        }
+
+        if self.distribution.is_smooth() {
+            *scattered = Ray::new(record.p, frame.local(Vec3::new(-wo.x(), -wo.y(), wo.z())));
+            *attenuation = fresnel_conductor(wo.z(), self.eta, self.k);
+            return true;
+        }
+
+        // reflect about a visible microfacet normal
+        let wi = match self.distribution.sample_reflection(wo, u1, u2) {
+            Some(wi) => wi,
+            None => return false
+        };
+        let (f, pdf, cos_om) = self.distribution.reflection(wo, wi).unwrap();
+        *scattered = Ray::new(record.p, frame.local(wi));
+        *attenuation = fresnel_conductor(cos_om, self.eta, self.k) * (f / pdf);
+        true
+    }
+
+    fn eval(&self, r_in: &Ray, record: &HitRecord, wi: Vec3) -> Option<(Color, f32)> {
+        if self.distribution.is_smooth() {
+            return None;
+        }
+        let wo_world = -Vec3::unit_vector(r_in.dir());
+        let frame = shading_frame(record, wo_world);
+        let wo = frame.to_local(wo_world);
+        let wi = frame.to_local(Vec3::unit_vector(wi));
+        match self.distribution.reflection(wo, wi) {
+            Some((f, pdf, cos_om)) => Some((fresnel_conductor(cos_om, self.eta, self.k) * f, pdf)),
+            None => Some((Color::init(), 0.))
+        }
+    }
+
+    fn get_idx(&self) -> u32 {
+        self.idx
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        record: &HitRecord,
+        sampler: &mut dyn Sampler,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray) -> bool {
+
+        let (frame, wo, eta, transmittance) = self.setup(r_in, record);
+        let (u1, u2) = sampler.get_2d();
+        let choice = sampler.get_1d();
+        if wo.z() <= 0. {
+            return false;
+        }
+
+        if self.distribution.is_smooth() {
+            let fresnel = fresnel_dielectric(wo.z(), eta);
+            let n = Vec3::new(0., 0., 1.);
+            let wi = match refract_about(wo, n, eta) {
+                Some(wt) if choice >= fresnel => {
+                    *attenuation = transmittance / (eta * eta);
+                    wt
+                }
+                _ => {
+                    *attenuation = transmittance;
+                    Vec3::new(-wo.x(), -wo.y(), wo.z())
+                }
+            };
+            *scattered = Ray::new(record.p, frame.local(wi));
+            return true;
+        }
+
+        let wi = match self.sample_wi(wo, eta, (u1, u2), choice) {
+            Some(wi) => wi,
+            None => return false
+        };
+        let (f, pdf) = self.evaluate(wo, wi, eta);
+        if pdf <= 0. {
+            return false;
+        }
+        *scattered = Ray::new(record.p, frame.local(wi));
+        *attenuation = transmittance * (f / pdf);
+        true
+    }
+
+    fn eval(&self, r_in: &Ray, record: &HitRecord, wi: Vec3) -> Option<(Color, f32)> {
+        if self.distribution.is_smooth() {
+            return None;
+        }
+        let (frame, wo, eta, transmittance) = self.setup(r_in, record);
+        if wo.z() <= 0. {
+            return Some((Color::init(), 0.));
+        }
+        let (f, pdf) = self.evaluate(wo, frame.to_local(Vec3::unit_vector(wi)), eta);
+        Some((transmittance * f, pdf))
+    }
+
+    fn get_idx(&self) -> u32 {
+        self.idx
+    }
+}
+
+fn schlick_color(f0: Color, cosine: f32) -> Color {
+    let m = (1. - cosine).clamp(0., 1.).powi(5);
+    f0 + m * (Color::new(1., 1., 1.) - f0)
+}
+
+// a Principled material evaluated at one hit.  diffuse, glass and
+// clearcoat are the weights of those lobes, the specular lobe gets what
+// glass leaves.
+struct PrincipledLobes {
+    base: Color,
+    roughness: f32,
+    diffuse: f32,
+    glass: f32,
+    sheen: f32,
+    clearcoat: f32,
+    dielectric_f0: f32,
+    f0: Color, // of the specular lobe
+    specular: TrowbridgeReitz,
+    coat: TrowbridgeReitz,
+    glass_lobe: RoughDielectric,
+    eta: f32 // across the surface from wo
+}
+
+impl PrincipledLobes {
+    // chances of sampling the diffuse, specular, glass and clearcoat
+    // lobes, roughly in proportion to how much each reflects
+    fn lobe_probabilities(&self, wo: Vec3) -> [f32; 4] {
+        let weights = [
+            self.diffuse * luminance(self.base).max(0.1),
+            (1. - self.glass) * luminance(schlick_color(self.f0, wo.z())),
+            self.glass,
+            self.clearcoat * schlick(wo.z(), 1.5)
+        ];
+        let total: f32 = weights.iter().sum();
+        if total <= 0. {
+            return [0.; 4];
+        }
+        weights.map(|w| w / total)
+    }
+
+    // the brdf/btdf times |cos_i| and the pdf of sample() picking wi
+    fn evaluate(&self, wo: Vec3, wi: Vec3) -> (Color, f32) {
+        let probs = self.lobe_probabilities(wo);
+        let mut f = Color::init();
+        let mut pdf = 0.;
+
+        if wi.z() > 0. {
+            // the base layers lose what the specular layer reflects
+            let wh = Vec3::unit_vector(wo + wi);
+            let cos_d = Vec3::dot(&wi, &wh);
+            if self.diffuse > 0. {
+                let fd90 = 0.5 + 2. * self.roughness * cos_d * cos_d;
+                let retro = |c: f32| 1. + (fd90 - 1.) * (1. - c).powi(5);
+                let disney = self.base * (consts::FRAC_1_PI * retro(wo.z()) * retro(wi.z()));
+                let sheen = self.sheen * (1. - cos_d).powi(5);
+                let layer = 1. - self.dielectric_f0 - (1. - self.dielectric_f0) * (1. - wo.z()).powi(5);
+                f += self.diffuse * layer * wi.z() * (disney + Color::new(sheen, sheen, sheen));
+                pdf += probs[0] * cosine_hemisphere_pdf(wi.z());
+            }
+            if let Some((spec, spec_pdf, cos_om)) = self.specular.reflection(wo, wi) {
+                f += (1. - self.glass) * spec * schlick_color(self.f0, cos_om);
+                pdf += probs[1] * spec_pdf;
+            }
+        }
+        if self.glass > 0. {
+            let (glass, glass_pdf) = self.glass_lobe.evaluate(wo, wi, self.eta);
+            let tint = if wi.z() < 0. { self.base } else { Color::new(1., 1., 1.) };
+            f += self.glass * glass * tint;
+            pdf += probs[2] * glass_pdf;
+        }
+
+        // the clearcoat covers everything else
+        let coat_f = schlick(wo.z(), 1.5);
+        f *= 1. - self.clearcoat * coat_f;
+        if self.clearcoat > 0. {
+            if let Some((coat, coat_pdf, cos_om)) = self.coat.reflection(wo, wi) {
+                f += Color::new(1., 1., 1.) * (self.clearcoat * coat * schlick(cos_om, 1.5));
+                pdf += probs[3] * coat_pdf;
+            }
+        }
+        (f, pdf)
+    }
+
+    // picks a lobe with choice, then a direction from it
+    fn sample(&self, wo: Vec3, choice: f32, (u1, u2): (f32, f32), u3: f32) -> Option<Vec3> {
+        let probs = self.lobe_probabilities(wo);
+        if choice < probs[0] {
+            return Some(sample_cosine_hemisphere(u1, u2));
+        }
+        if choice < probs[0] + probs[1] {
+            return self.specular.sample_reflection(wo, u1, u2);
+        }
+        if choice < probs[0] + probs[1] + probs[2] {
+            return self.glass_lobe.sample_wi(wo, self.eta, (u1, u2), u3);
+        }
+        if probs[3] > 0. {
+            return self.coat.sample_reflection(wo, u1, u2);
+        }
+        None
+    }
+}
+
+impl Material for Principled {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        record: &HitRecord,
+        sampler: &mut dyn Sampler,
         attenuation: &mut Vec3,
-        scattered: &mut Ray) -> bool;
+        scattered: &mut Ray) -> bool {
 
-    fn get_idx(&self) -> u32;
-}
+        let wo_world = -Vec3::unit_vector(r_in.dir());
+        let frame = shading_frame(record, wo_world);
+        let wo = frame.to_local(wo_world);
+        let choice = sampler.get_1d();
+        let u = sampler.get_2d();
+        let u3 = sampler.get_1d();
+        if wo.z() <= 0. {
+            return false;
+        }
 
-#[derive(Copy, Clone)]
-pub struct Lambertian {
-    albedo: Vec3,
-    idx: u32 // index in material table
-}
+        let lobes = self.lobes(record, wo_world);
+        let wi = match lobes.sample(wo, choice, u, u3) {
+            Some(wi) => wi,
+            None => return false
+        };
+        // weighted by every lobe that could have picked wi
+        let (f, pdf) = lobes.evaluate(wo, wi);
+        if pdf <= 0. {
+            return false;
+        }
+        *scattered = Ray::new(record.p, frame.local(wi));
+        *attenuation = f / pdf;
+        true
+    }
 
-#[derive(Copy, Clone)]
-pub struct Metal {
-    albedo: Vec3,
-    fuzz: f32,
-    idx: u32 // index in material table
-}
+    fn eval(&self, r_in: &Ray, record: &HitRecord, wi: Vec3) -> Option<(Color, f32)> {
+        let wo_world = -Vec3::unit_vector(r_in.dir());
+        let frame = shading_frame(record, wo_world);
+        let wo = frame.to_local(wo_world);
+        if wo.z() <= 0. {
+            return Some((Color::init(), 0.));
+        }
+        let lobes = self.lobes(record, wo_world);
+        Some(lobes.evaluate(wo, frame.to_local(Vec3::unit_vector(wi))))
+    }
 
-#[derive(Copy, Clone)]
-pub struct Dielectric {
-    ref_idx: f32,
-    idx: u32 // index in material table
+    fn get_idx(&self) -> u32 {
+        self.idx
+    }
 }
 
-impl Lambertian {
-    pub fn new(albedo: Vec3, idx: u32) -> Lambertian {
-        Lambertian { albedo, idx }
+impl Material for Mix {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        record: &HitRecord,
+        sampler: &mut dyn Sampler,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray) -> bool {
+        self.sample(r_in, record, sampler, attenuation, scattered).is_some()
     }
-}
 
-impl Metal {
-    pub fn new(albedo: Vec3, fuzz:f32, idx: u32) -> Metal {
-        let clamped_fuzz = if fuzz < 1. { fuzz } else { 1. };
-        Metal { albedo, fuzz:clamped_fuzz, idx }
+    fn sample(
+        &self,
+        r_in: &Ray,
+        record: &HitRecord,
+        sampler: &mut dyn Sampler,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray) -> Option<bool> {
+
+        let weight = self.weight(record);
+        let u = sampler.get_1d();
+        let picked_b = u < weight;
+        let (chosen, u) = if picked_b {
+            (&self.b, remap(u, 0., weight))
+        } else {
+            (&self.a, remap(u, weight, 1.))
+        };
+        let mut sampler = ReusedSampler::new(sampler, u);
+        let discrete = chosen.sample(r_in, record, &mut sampler, attenuation, scattered)?;
+        let wi = scattered.dir();
+        let (eval_a, eval_b) = (self.a.eval(r_in, record, wi), self.b.eval(r_in, record, wi));
+        // the pick's odds cancel the weight for a discrete direction,
+        // others weigh by both as either could have picked them
+        let chosen_eval = if picked_b { eval_b } else { eval_a };
+        if discrete || chosen_eval.is_none() {
+            return Some(true);
+        }
+        let (f, pdf) = Mix::blend(weight, eval_a, eval_b)?;
+        if pdf <= 0. {
+            return None;
+        }
+        *attenuation = f / pdf;
+        Some(false)
+    }
+
+    fn emitted(&self, record: &HitRecord) -> Color {
+        let weight = self.weight(record);
+        (1. - weight) * self.a.emitted(record) + weight * self.b.emitted(record)
+    }
+
+    fn eval(&self, r_in: &Ray, record: &HitRecord, wi: Vec3) -> Option<(Color, f32)> {
+        Mix::blend(self.weight(record), self.a.eval(r_in, record, wi), self.b.eval(r_in, record, wi))
+    }
+
+    fn dispersive(&self) -> bool {
+        self.a.dispersive() || self.b.dispersive()
+    }
+
+    fn interior(&self, r_in: &Ray, record: &HitRecord) -> Option<Medium> {
+        self.a.interior(r_in, record).or_else(|| self.b.interior(r_in, record))
+    }
+
+    fn opacity(&self) -> Option<Rc<dyn Texture>> {
+        let (a, b) = (self.a.opacity(), self.b.opacity());
+        if a.is_none() && b.is_none() {
+            return None;
+        }
+        Some(Rc::new(MixOpacity { a, b, weight: self.weight.0.clone() }))
+    }
+
+    fn get_idx(&self) -> u32 {
+        self.idx
     }
 }
 
-impl Dielectric {
-    pub fn new(ref_idx: f32, idx: u32) -> Dielectric {
-        Dielectric { ref_idx, idx }
+impl Material for Coated {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        record: &HitRecord,
+        sampler: &mut dyn Sampler,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray) -> bool {
+        self.sample(r_in, record, sampler, attenuation, scattered).is_some()
+    }
+
+    fn sample(
+        &self,
+        r_in: &Ray,
+        record: &HitRecord,
+        sampler: &mut dyn Sampler,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray) -> Option<bool> {
+
+        let wo_world = -Vec3::unit_vector(r_in.dir());
+        let frame = shading_frame(record, wo_world);
+        let wo = frame.to_local(wo_world);
+        if wo.z() <= 0. {
+            return None;
+        }
+        let p_coat = self.coat_probability(wo);
+        let u = sampler.get_1d();
+        let on_coat = u < p_coat;
+        let u = if on_coat { remap(u, 0., p_coat) } else { remap(u, p_coat, 1.) };
+        let mut sampler = ReusedSampler::new(sampler, u);
+        let mut discrete = false;
+        if on_coat {
+            let (u1, u2) = sampler.get_2d();
+            *scattered = Ray::new(record.p, frame.local(self.distribution.sample_reflection(wo, u1, u2)?));
+        }
+        else {
+            discrete = self.base.sample(r_in, record, &mut sampler, attenuation, scattered)?;
+        }
+
+        if !discrete {
+            if let Some((f, pdf)) = self.evaluate(r_in, record, &frame, wo, scattered.dir()) {
+                if pdf <= 0. {
+                    return None;
+                }
+                *attenuation = f / pdf;
+                return Some(false);
+            }
+        }
+
+        // a discrete direction from the base: weigh each layer by itself
+        // and the odds of picking it
+        let wi = frame.to_local(Vec3::unit_vector(scattered.dir()));
+        if on_coat {
+            let (coat, coat_pdf, cos_om) = self.distribution.reflection(wo, wi).unwrap();
+            let weight = coat * fresnel_dielectric(cos_om, self.eta) / (coat_pdf * p_coat);
+            *attenuation = Color::new(weight, weight, weight);
+        }
+        else {
+            let through = self.transmission(wo.z()) * self.transmission(wi.z());
+            *attenuation = *attenuation * self.absorption(wo.z()) * self.absorption(wi.z()) *
+                (through / (1. - p_coat));
+        }
+        Some(!on_coat)
+    }
+
+    fn emitted(&self, record: &HitRecord) -> Color {
+        self.base.emitted(record)
+    }
+
+    fn eval(&self, r_in: &Ray, record: &HitRecord, wi: Vec3) -> Option<(Color, f32)> {
+        let wo_world = -Vec3::unit_vector(r_in.dir());
+        let frame = shading_frame(record, wo_world);
+        let wo = frame.to_local(wo_world);
+        if wo.z() <= 0. {
+            return Some((Color::init(), 0.));
+        }
+        self.evaluate(r_in, record, &frame, wo, wi)
+    }
+
+    fn dispersive(&self) -> bool {
+        self.base.dispersive()
+    }
+
+    fn interior(&self, r_in: &Ray, record: &HitRecord) -> Option<Medium> {
+        self.base.interior(r_in, record)
+    }
+
+    fn opacity(&self) -> Option<Rc<dyn Texture>> {
+        self.base.opacity()
+    }
+
+    fn get_idx(&self) -> u32 {
+        self.idx
     }
 }
 
-impl Material for Lambertian {
+impl Material for Subsurface {
     fn scatter(
         &self,
-        _: &Ray,
+        r_in: &Ray,
         record: &HitRecord,
+        sampler: &mut dyn Sampler,
         attenuation: &mut Vec3,
         scattered: &mut Ray) -> bool {
 
-        let target = record.p + record.normal + random_in_unit_sphere();
-        *scattered = Ray::new(record.p, target - record.p);
-        *attenuation = self.albedo;
+        // cosine weighted about the normal on the far side from r_in
+        let n = if Vec3::dot(&r_in.dir(), &record.normal) < 0. { -record.normal } else { record.normal };
+        let (u1, u2) = sampler.get_2d();
+        *scattered = Ray::new(record.p, Onb::from_w(n).local(sample_cosine_hemisphere(u1, u2)));
+        *attenuation = Color::new(1., 1., 1.);
 
         true
     }
 
+    fn eval(&self, r_in: &Ray, record: &HitRecord, wi: Vec3) -> Option<(Color, f32)> {
+        let cos_o = Vec3::dot(&r_in.dir(), &record.normal);
+        let cos_i = Vec3::dot(&Vec3::unit_vector(wi), &Vec3::unit_vector(record.normal));
+        // only transmits
+        if cos_o * cos_i <= 0. {
+            return Some((Color::init(), 0.));
+        }
+        let cosine = cos_i.abs();
+        Some((Color::new(1., 1., 1.) * (cosine * consts::FRAC_1_PI), cosine_hemisphere_pdf(cosine)))
+    }
+
+    fn interior(&self, r_in: &Ray, record: &HitRecord) -> Option<Medium> {
+        let albedo = self.albedo.color(record);
+        let (a, d) = match r_in.wavelengths {
+            Some(lambdas) => (reflectance(albedo, &lambdas), reflectance(self.mean_free_path, &lambdas)),
+            None => (albedo, self.mean_free_path)
+        };
+        let channel = |i: usize| Subsurface::coefficients(a[i], d[i]);
+        let (c0, c1, c2) = (channel(0), channel(1), channel(2));
+        Some(Medium {
+            sigma_s: Vec3::new(c0.0, c1.0, c2.0),
+            sigma_t: Vec3::new(c0.1, c1.1, c2.1)
+        })
+    }
+
     fn get_idx(&self) -> u32 {
         self.idx
     }
 }
 
-fn reflect(v: Vec3, n: Vec3) -> Vec3 {
-    v - 2. * Vec3::dot(&v, &n) * n
+impl Material for Bumped {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        record: &HitRecord,
+        sampler: &mut dyn Sampler,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray) -> bool {
+
+        self.sample(r_in, record, sampler, attenuation, scattered).is_some()
+    }
+
+    fn sample(
+        &self,
+        r_in: &Ray,
+        record: &HitRecord,
+        sampler: &mut dyn Sampler,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray) -> Option<bool> {
+
+        let shaded = self.shaded(r_in, record);
+        let discrete = self.base.sample(r_in, &shaded, sampler, attenuation, scattered)?;
+        // still lit directly, but nothing comes back along a leaking ray
+        if !Bumped::consistent(&shaded, record, scattered.dir()) {
+            *attenuation = Color::init();
+        }
+        Some(discrete)
+    }
+
+    fn emitted(&self, record: &HitRecord) -> Color {
+        self.base.emitted(record)
+    }
+
+    fn eval(&self, r_in: &Ray, record: &HitRecord, wi: Vec3) -> Option<(Color, f32)> {
+        let shaded = self.shaded(r_in, record);
+        let (f, pdf) = self.base.eval(r_in, &shaded, wi)?;
+        if Bumped::consistent(&shaded, record, wi) {
+            Some((f, pdf))
+        } else {
+            Some((Color::init(), pdf))
+        }
+    }
+
+    fn dispersive(&self) -> bool {
+        self.base.dispersive()
+    }
+
+    fn interior(&self, r_in: &Ray, record: &HitRecord) -> Option<Medium> {
+        self.base.interior(r_in, record)
+    }
+
+    fn opacity(&self) -> Option<Rc<dyn Texture>> {
+        self.base.opacity()
+    }
+
+    fn get_idx(&self) -> u32 {
+        self.idx
+    }
 }
 
-impl Material for Metal {
+// the scene's intersection does the cutting out, this only passes the
+// surface on to the base material
+impl Material for Cutout {
     fn scatter(
         &self,
         r_in: &Ray,
         record: &HitRecord,
+        sampler: &mut dyn Sampler,
         attenuation: &mut Vec3,
         scattered: &mut Ray) -> bool {
+        self.base.scatter(r_in, record, sampler, attenuation, scattered)
+    }
 
-        let reflected = reflect(Vec3::unit_vector(r_in.dir()), record.normal);
-        *scattered = Ray::new(record.p, reflected + self.fuzz * random_in_unit_sphere());
-        *attenuation = self.albedo;
+    fn sample(
+        &self,
+        r_in: &Ray,
+        record: &HitRecord,
+        sampler: &mut dyn Sampler,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray) -> Option<bool> {
+        self.base.sample(r_in, record, sampler, attenuation, scattered)
+    }
 
-        Vec3::dot(&scattered.dir(), &record.normal) > 0.
+    fn emitted(&self, record: &HitRecord) -> Color {
+        self.base.emitted(record)
+    }
+
+    fn eval(&self, r_in: &Ray, record: &HitRecord, wi: Vec3) -> Option<(Color, f32)> {
+        self.base.eval(r_in, record, wi)
+    }
+
+    fn dispersive(&self) -> bool {
+        self.base.dispersive()
+    }
+
+    fn interior(&self, r_in: &Ray, record: &HitRecord) -> Option<Medium> {
+        self.base.interior(r_in, record)
+    }
+
+    fn opacity(&self) -> Option<Rc<dyn Texture>> {
+        Some(self.opacity.0.clone())
     }
 
     fn get_idx(&self) -> u32 {
@@ -103,10 +1395,12 @@ impl Material for Dielectric {
         &self,
         r_in: &Ray,
         record: &HitRecord,
+        sampler: &mut dyn Sampler,
         attenuation: &mut Vec3,
         scattered: &mut Ray) -> bool {
 
         *attenuation = Vec3::new(1., 1., 1.);
+        let ref_idx = self.ref_idx.ior(r_in.wavelengths.map_or(LAMBDA_D, |l| l.x()));
 
         let outward_normal: Vec3;
         let ni_over_nt: f32;
@@ -115,13 +1409,13 @@ impl Material for Dielectric {
 
         if Vec3::dot(&r_in.dir(), &record.normal) > 0. {
             outward_normal = -record.normal;
-            ni_over_nt     = self.ref_idx;
-            cosine = self.ref_idx *
+            ni_over_nt     = ref_idx;
+            cosine = ref_idx *
                 Vec3::dot(&r_in.dir(), &record.normal) / r_in.dir().length();
         }
         else {
             outward_normal = record.normal;
-            ni_over_nt     = 1. / self.ref_idx;
+            ni_over_nt     = 1. / ref_idx;
             cosine = -Vec3::dot(&r_in.dir(), &record.normal) / r_in.dir().length();
         }
 
@@ -129,13 +1423,13 @@ impl Material for Dielectric {
 
         if let Some(refrac) = refract(r_in.dir(), outward_normal, ni_over_nt) {
             refracted = refrac;
-            reflect_prob = schlick(cosine, self.ref_idx);
+            reflect_prob = schlick(cosine, ref_idx);
         }
         else {
             reflect_prob = 1.;
         }
 
-        if rand_unit() < reflect_prob {
+        if sampler.get_1d() < reflect_prob {
             let reflected = reflect(r_in.dir(), record.normal);
             *scattered = Ray::new(record.p, reflected);
         }
@@ -146,6 +1440,52 @@ impl Material for Dielectric {
         true
     }
 
+    fn dispersive(&self) -> bool {
+        !matches!(self.ref_idx, Dispersion::Constant(_))
+    }
+
+    fn get_idx(&self) -> u32 {
+        self.idx
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _: &Ray,
+        _: &HitRecord,
+        _: &mut dyn Sampler,
+        _: &mut Vec3,
+        _: &mut Ray) -> bool {
+        false
+    }
+
+    fn emitted(&self, record: &HitRecord) -> Color {
+        self.emit.value(record.u, record.v, &record.p)
+    }
+
+    fn get_idx(&self) -> u32 {
+        self.idx
+    }
+}
+
+impl Material for NormalShade {
+    fn scatter(
+        &self,
+        _: &Ray,
+        _: &HitRecord,
+        _: &mut dyn Sampler,
+        _: &mut Vec3,
+        _: &mut Ray) -> bool {
+        false
+    }
+
+    // map each normal component from [-1, 1] to [0, 1]
+    fn emitted(&self, record: &HitRecord) -> Color {
+        let n = Vec3::unit_vector(record.normal);
+        0.5 * Color::new(n.x() + 1., n.y() + 1., n.z() + 1.)
+    }
+
     fn get_idx(&self) -> u32 {
         self.idx
     }
@@ -176,4 +1516,302 @@ fn refract(v: Vec3, n: Vec3, ni_over_nt: f32) -> Option<Vec3>
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(v: f32) -> Color {
+        Color::new(v, v, v)
+    }
+
+    // looking straight down at a surface facing up
+    fn setup() -> (Ray, HitRecord) {
+        let r_in = Ray::new(Point::new(0., 0., 1.), Vec3::new(0., 0., -1.));
+        let record = HitRecord {
+            t: 1.,
+            normal: Vec3::new(0., 0., 1.),
+            tangent: Vec3::new(1., 0., 0.),
+            u: 0.5,
+            v: 0.5,
+            ..HitRecord::default()
+        };
+        (r_in, record)
+    }
+
+    // Kolmogorov-Smirnov test of the directions Lambertian scatters into
+    // against the cosine lobe about the normal, whose angle to the
+    // normal has the distribution 1 - cos^2 theta
+    #[test]
+    fn lambertian_scatters_by_the_cosine_lobe() {
+        const N: usize = 100_000;
+        seed_rand(27);
+        let n = Vec3::new(1., 2., 2.) / 3.;
+        let record = HitRecord {
+            normal: n,
+            tangent: Vec3::unit_vector(Vec3::new(2., -1., 0.)),
+            ..HitRecord::default()
+        };
+        let r_in = Ray::new(n, -n);
+        let lambertian = Lambertian::new(gray(0.5), 0);
+        let mut sampler = IndependentSampler::new(1);
+        let mut cos_theta: Vec<f32> = (0..N).map(|_| {
+            let (mut attenuation, mut scattered) = (Vec3::default(), Ray::default());
+            assert!(lambertian.scatter(&r_in, &record, &mut sampler, &mut attenuation, &mut scattered));
+            let cos = Vec3::dot(&Vec3::unit_vector(scattered.dir()), &n);
+            assert!(cos >= -1e-6);
+            cos
+        }).collect();
+        // increasing theta
+        cos_theta.sort_by(|a, b| b.total_cmp(a));
+        let mut worst: f32 = 0.;
+        for (i, c) in cos_theta.iter().enumerate() {
+            let cdf = 1. - c * c;
+            let below = i as f32 / N as f32;
+            let above = (i + 1) as f32 / N as f32;
+            worst = worst.max((cdf - below).abs()).max((above - cdf).abs());
+        }
+        // critical value at the 0.1% significance level
+        let bound = 1.95 / (N as f32).sqrt();
+        assert!(worst < bound, "KS distance {} over {}", worst, bound);
+    }
+
+    #[test]
+    fn mix_blends_children_by_weight() {
+        let (r_in, record) = setup();
+        let mix = Mix::new(Lambertian::new(gray(0.8), 0), Lambertian::new(gray(0.2), 0), 0.25, 0);
+        let wi = Vec3::new(0.3, 0., 1.);
+        let (f, pdf) = mix.eval(&r_in, &record, wi).unwrap();
+        let (fa, pdf_a) = Lambertian::new(gray(0.8), 0).eval(&r_in, &record, wi).unwrap();
+        assert!((f.r() - 0.65 / 0.8 * fa.r()).abs() < 1e-6);
+        assert!((pdf - pdf_a).abs() < 1e-6);
+
+        // either pick weighs the direction by both children
+        let mut sampler = IndependentSampler::new(1);
+        for s in 0..64 {
+            sampler.start_sample(0, 0, s);
+            let (mut attenuation, mut scattered) = (Vec3::default(), Ray::default());
+            assert!(mix.scatter(&r_in, &record, &mut sampler, &mut attenuation, &mut scattered));
+            assert!((attenuation.r() - 0.65).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn mix_evaluates_the_continuous_child_of_a_discrete_one() {
+        let (r_in, record) = setup();
+        let mix = Mix::new(Metal::new(gray(0.9), 0., 0), Lambertian::new(gray(0.5), 0), 0.4, 0);
+        let wi = Vec3::new(0.3, 0., 1.);
+        let (f, pdf) = mix.eval(&r_in, &record, wi).unwrap();
+        let (fb, pdf_b) = Lambertian::new(gray(0.5), 0).eval(&r_in, &record, wi).unwrap();
+        assert!((f.r() - 0.4 * fb.r()).abs() < 1e-6);
+        assert!((pdf - 0.4 * pdf_b).abs() < 1e-6);
+    }
+
+    #[test]
+    fn layers_pass_on_interior_and_opacity() {
+        let (r_in, record) = setup();
+        let skin = || Subsurface::new(gray(0.8), gray(0.1), 0);
+        let mix = Mix::new(Lambertian::new(gray(0.5), 0), skin(), 0.5, 0);
+        assert!(mix.interior(&r_in, &record).is_some());
+        assert!(Coated::new(skin(), 1.5, 0.1, 0).interior(&r_in, &record).is_some());
+
+        let alpha = |m: &dyn Material| m.opacity().map(|t| t.value(record.u, record.v, &record.p).r());
+        let leaf = || Cutout::new(Lambertian::new(gray(0.5), 0), 0.2, 0);
+        let mix = Mix::new(leaf(), Lambertian::new(gray(0.5), 0), 0.5, 0);
+        assert!((alpha(&mix).unwrap() - 0.6).abs() < 1e-6);
+        assert!((alpha(&Coated::new(leaf(), 1.5, 0.1, 0)).unwrap() - 0.2).abs() < 1e-6);
+        let plain = Mix::new(Lambertian::new(gray(0.5), 0), Lambertian::new(gray(0.5), 0), 0.5, 0);
+        assert!(alpha(&plain).is_none());
+    }
+
+    // light arriving theta degrees off the normal of setup()'s surface
+    fn incoming(theta: f32) -> Ray {
+        let (s, c) = theta.to_radians().sin_cos();
+        Ray::new(Point::new(-s, 0., c), Vec3::new(s, 0., -c))
+    }
+
+    // Fraction of the light material sends back out of the surface,
+    // estimated by averaging scatter()'s weights and by integrating
+    // eval() over the sphere.  Also checks that every sampled weight is
+    // eval()'s brdf over its pdf.
+    fn albedo(material: &dyn Material, r_in: &Ray, record: &HitRecord) -> (f32, f32) {
+        const N: usize = 100_000;
+        let mut sampler = IndependentSampler::new(1);
+        let (mut sampled, mut integrated) = (0f64, 0f64);
+        for _ in 0..N {
+            let (mut attenuation, mut scattered) = (Vec3::default(), Ray::default());
+            if material.scatter(r_in, record, &mut sampler, &mut attenuation, &mut scattered) {
+                let (f, pdf) = material.eval(r_in, record, scattered.dir()).unwrap();
+                assert!((f.r() / pdf - attenuation.r()).abs() < 1e-3 * attenuation.r().max(1.));
+                sampled += attenuation.r() as f64;
+            }
+            // uniform over the sphere
+            let (u1, u2) = sampler.get_2d();
+            let z = 1. - 2. * u1;
+            let r = (1. - z * z).max(0.).sqrt();
+            let phi = 2. * consts::PI * u2;
+            let wi = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+            integrated += (material.eval(r_in, record, wi).unwrap().0.r() * 4. * consts::PI) as f64;
+        }
+        ((sampled / N as f64) as f32, (integrated / N as f64) as f32)
+    }
+
+    // A furnace test: a conductor that reflects everything only loses
+    // light to masked microfacets, more so the rougher it is.  Uniform
+    // sampling is too noisy to integrate the narrowest lobe.
+    #[test]
+    fn conductors_reflect_no_more_light_than_arrives() {
+        seed_rand(39);
+        let (_, record) = setup();
+        let mut previous = 1.;
+        for roughness in [0.2, 0.5, 1.] {
+            let metal = Conductor::new(gray(1.), gray(1000.), roughness, 0);
+            for theta in [0., 45., 80.] {
+                let (sampled, integrated) = albedo(&metal, &incoming(theta), &record);
+                assert!(sampled < 1.01, "{} at {}: {}", roughness, theta, sampled);
+                if roughness >= 0.5 {
+                    assert!((sampled - integrated).abs() < 0.02, "{} vs {}", sampled, integrated);
+                }
+            }
+            let (sampled, _) = albedo(&metal, &incoming(0.), &record);
+            assert!(sampled < previous, "{}: {}", roughness, sampled);
+            previous = sampled;
+        }
+        // single scattering GGX at alpha 1 reflects 0.307 of the light
+        // arriving along the normal
+        assert!((previous - 0.307).abs() < 0.01, "{}", previous);
+    }
+
+    // Glass neither makes nor, without absorption, loses light: what it
+    // reflects plus what it transmits, scaled back by eta^2 for the
+    // change in solid angle, is all that arrives less what's lost to
+    // masked microfacets
+    #[test]
+    fn rough_glass_conserves_energy_on_both_sides() {
+        seed_rand(40);
+        let (_, record) = setup();
+        let mut sampler = IndependentSampler::new(1);
+        for roughness in [0.3, 0.7] {
+            let glass = RoughDielectric::new(1.5, roughness, 0);
+            for (theta, eta) in [(0., 1.5), (60., 1.5), (180., 1. / 1.5), (140., 1. / 1.5)] {
+                let r_in = incoming(theta);
+                let (mut reflected, mut transmitted) = (0., 0.);
+                for _ in 0..100_000 {
+                    let (mut attenuation, mut scattered) = (Vec3::default(), Ray::default());
+                    if glass.scatter(&r_in, &record, &mut sampler, &mut attenuation, &mut scattered) {
+                        if (scattered.dir().z() > 0.) == (theta < 90.) {
+                            reflected += attenuation.r() / 100_000.;
+                        }
+                        else {
+                            transmitted += eta * eta * attenuation.r() / 100_000.;
+                        }
+                    }
+                }
+                let total = reflected + transmitted;
+                assert!(total < 1.01, "{} at {}: {}", roughness, theta, total);
+                // masking loses little at low roughness
+                if roughness < 0.5 {
+                    assert!(total > 0.95, "{} at {}: {}", roughness, theta, total);
+                }
+                else {
+                    let (sampled, integrated) = albedo(&glass, &r_in, &record);
+                    assert!((sampled - integrated).abs() < 0.05 * sampled, "{} vs {}", sampled, integrated);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn principled_lobes_sample_what_they_evaluate() {
+        seed_rand(41);
+        let (_, record) = setup();
+        // a white fully metallic surface is a conductor reflecting all
+        let metal = Principled::new(gray(1.), 0).with_metallic(1.);
+        let conductor = Conductor::new(gray(1.), gray(1000.), 0.5, 0);
+        let (a, _) = albedo(&metal, &incoming(30.), &record);
+        let (b, _) = albedo(&conductor, &incoming(30.), &record);
+        assert!((a - b).abs() < 0.01, "{} vs {}", a, b);
+
+        let plastic = Principled::new(gray(0.8), 0).with_sheen(0.5).with_clearcoat(1., 0.3);
+        for theta in [0., 60.] {
+            let (sampled, integrated) = albedo(&plastic, &incoming(theta), &record);
+            assert!(sampled < 1.01, "{}", sampled);
+            assert!((sampled - integrated).abs() < 0.03, "{} vs {}", sampled, integrated);
+        }
+        let glass = Principled::new(gray(1.), 0).with_roughness(0.7).with_transmission(1., 1.5);
+        let (sampled, integrated) = albedo(&glass, &incoming(0.), &record);
+        assert!((sampled - integrated).abs() < 0.05 * sampled, "{} vs {}", sampled, integrated);
+    }
+
+    // the surface of a translucent object lets all the light through,
+    // diffusely, to be scattered by the medium inside
+    #[test]
+    fn subsurface_surfaces_only_transmit() {
+        seed_rand(44);
+        let (r_in, record) = setup();
+        let skin = Subsurface::new(gray(0.8), gray(0.1), 0);
+        let (sampled, integrated) = albedo(&skin, &r_in, &record);
+        assert!((sampled - 1.).abs() < 1e-4 && (integrated - 1.).abs() < 0.02, "{} {}", sampled, integrated);
+        assert_eq!(skin.eval(&r_in, &record, Vec3::new(0.2, 0., 1.)).unwrap().1, 0.);
+        let medium = skin.interior(&r_in, &record).unwrap();
+        for k in 0..3 {
+            assert!(medium.sigma_s[k] < medium.sigma_t[k] && medium.sigma_s[k] > 0.9 * medium.sigma_t[k]);
+        }
+    }
+
+    // height rising half a unit per unit of u
+    struct Ramp;
+
+    impl Texture for Ramp {
+        fn value(&self, u: f32, _: f32, _: &Point) -> Color {
+            gray(0.5 * u)
+        }
+    }
+
+    #[test]
+    fn bumps_tilt_the_shading_normal() {
+        let (r_in, record) = setup();
+        let normal = |bumped: &Bumped| bumped.shaded(&r_in, &record).normal;
+        // (0.6, 0, 0.8) encoded as a color, x along the tangent
+        let mapped = Bumped::normal_map(Lambertian::new(gray(0.5), 0),
+                                        Rc::new(ConstantTexture::new(Color::new(0.8, 0.5, 0.9))), 0);
+        assert!((normal(&mapped) - Vec3::new(0.6, 0., 0.8)).length() < 1e-5);
+        // a slope of 1 along u tilts the normal 45 degrees back along the tangent
+        let h = consts::FRAC_1_SQRT_2;
+        let ramp = Bumped::height(Lambertian::new(gray(0.5), 0), Rc::new(Ramp), 2., 0);
+        assert!((normal(&ramp) - Vec3::new(-h, 0., h)).length() < 1e-3);
+
+        // the base material shades with the tilted normal
+        let (f, _) = mapped.eval(&r_in, &record, Vec3::new(0.6, 0., 0.8)).unwrap();
+        assert!((f.r() - 0.5 * consts::FRAC_1_PI).abs() < 1e-5);
+    }
+
+    // directions above the tilted normal but below the surface would let
+    // light through solid objects, so they carry none
+    #[test]
+    fn bumps_leak_no_light_through_the_surface() {
+        seed_rand(45);
+        let (r_in, record) = setup();
+        let mapped = Bumped::normal_map(Lambertian::new(gray(0.5), 0),
+                                        Rc::new(ConstantTexture::new(Color::new(0.8, 0.5, 0.9))), 0);
+        let (f, _) = mapped.eval(&r_in, &record, Vec3::new(1., 0., -0.1)).unwrap();
+        assert_eq!(f.r(), 0.);
+        let mut sampler = IndependentSampler::new(1);
+        let mut kept = 0;
+        for _ in 0..1000 {
+            let (mut attenuation, mut scattered) = (Vec3::default(), Ray::default());
+            assert!(mapped.scatter(&r_in, &record, &mut sampler, &mut attenuation, &mut scattered));
+            if scattered.dir().z() > 0. {
+                assert!(attenuation.r() > 0.);
+                kept += 1;
+            }
+            else {
+                assert_eq!(attenuation.r(), 0.);
+            }
+        }
+        // the cosine lobe about a normal tilted by theta dips below the
+        // surface for (1 - cos theta) / 2 of its directions, a tenth here
+        assert!(kept > 870 && kept < 930, "{}", kept);
+    }
+}
+
 }
\ No newline at end of file
@@ -0,0 +1,180 @@
+pub mod module {
+
+use crate::types::module::*;
+use crate::vec3::module::*;
+
+use std::f32::consts;
+
+// Below this alpha a surface is treated as perfectly smooth, as the
+// distribution gets too narrow to evaluate in f32
+pub const SMOOTH_ALPHA: f32 = 1e-3;
+
+// Maps the perceptually linear roughness artists set, in [0, 1], to the
+// alpha of the distribution (Burley 2012)
+pub fn roughness_to_alpha(roughness: f32) -> f32 {
+    let r = roughness.clamp(0., 1.);
+    r * r
+}
+
+// The GGX / Trowbridge-Reitz distribution of microfacet normals.  All
+// directions are in the local shading frame: z is the normal and x the
+// tangent, which alpha_x is the roughness along.
+#[derive(Copy, Clone)]
+pub struct TrowbridgeReitz {
+    alpha_x: f32,
+    alpha_y: f32
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: f32, alpha_y: f32) -> TrowbridgeReitz {
+        TrowbridgeReitz { alpha_x, alpha_y }
+    }
+
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < SMOOTH_ALPHA
+    }
+
+    // density of microfacets facing wm, per unit projected area
+    pub fn d(&self, wm: Vec3) -> f32 {
+        let cos2 = wm.z() * wm.z();
+        if cos2 == 0. {
+            return 0.;
+        }
+        let (ax, ay) = (self.alpha_x, self.alpha_y);
+        let e = (wm.x() * wm.x() / (ax * ax) + wm.y() * wm.y() / (ay * ay)) / cos2;
+        1. / (consts::PI * ax * ay * cos2 * cos2 * (1. + e) * (1. + e))
+    }
+
+    // Smith's auxiliary function, the hidden microfacet area per
+    // visible area seen from w
+    fn lambda(&self, w: Vec3) -> f32 {
+        let cos2 = w.z() * w.z();
+        if cos2 == 0. {
+            return f32::INFINITY;
+        }
+        let alpha2_tan2 = (self.alpha_x * self.alpha_x * w.x() * w.x() +
+                           self.alpha_y * self.alpha_y * w.y() * w.y()) / cos2;
+        ((1. + alpha2_tan2).sqrt() - 1.) / 2.
+    }
+
+    // fraction of microfacets visible from w
+    pub fn g1(&self, w: Vec3) -> f32 {
+        1. / (1. + self.lambda(w))
+    }
+
+    // fraction of microfacets visible from both wo and wi, the height
+    // correlated form
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f32 {
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // density of the normals visible from w
+    pub fn visible_d(&self, w: Vec3, wm: Vec3) -> f32 {
+        if w.z() == 0. {
+            return 0.;
+        }
+        self.g1(w) / w.z().abs() * self.d(wm) * Vec3::dot(&w, &wm).abs()
+    }
+
+    // Picks a microfacet normal visible from w in proportion to
+    // visible_d, Heitz 2018, "Sampling the GGX Distribution of Visible
+    // Normals".  w must be above the surface.
+    pub fn sample_wm(&self, w: Vec3, u1: f32, u2: f32) -> Vec3 {
+        let (ax, ay) = (self.alpha_x, self.alpha_y);
+        // stretch to the hemisphere configuration
+        let vh = Vec3::unit_vector(Vec3::new(ax * w.x(), ay * w.y(), w.z()));
+        let len2 = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if len2 > 0. {
+            Vec3::new(-vh.y(), vh.x(), 0.) / len2.sqrt()
+        }
+        else {
+            Vec3::new(1., 0., 0.)
+        };
+        let t2 = Vec3::cross(&vh, &t1);
+
+        // point on the projected half disk
+        let r = u1.sqrt();
+        let phi = 2. * consts::PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1. + vh.z());
+        let p2 = (1. - s) * (1. - p1 * p1).max(0.).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1. - p1 * p1 - p2 * p2).max(0.).sqrt() * vh;
+
+        // and back to the ellipsoid
+        Vec3::unit_vector(Vec3::new(ax * nh.x(), ay * nh.y(), nh.z().max(1e-6)))
+    }
+
+    // Mirrors wo about a sampled visible normal, None when that ends up
+    // below the surface
+    pub fn sample_reflection(&self, wo: Vec3, u1: f32, u2: f32) -> Option<Vec3> {
+        let wm = self.sample_wm(wo, u1, u2);
+        let wi = 2. * Vec3::dot(&wo, &wm) * wm - wo;
+        if wi.z() > 0. { Some(wi) } else { None }
+    }
+
+    // For reflecting from wo to wi, both above the surface: the brdf
+    // times cos_i without the Fresnel term, D G / (4 cos_o), the pdf of
+    // sample_reflection picking wi and the cosine between wo and the
+    // half vector, for the caller's Fresnel term
+    pub fn reflection(&self, wo: Vec3, wi: Vec3) -> Option<(f32, f32, f32)> {
+        if wo.z() <= 0. || wi.z() <= 0. {
+            return None;
+        }
+        let wm = Vec3::unit_vector(wo + wi);
+        let cos_om = Vec3::dot(&wo, &wm);
+        let f = self.d(wm) * self.g(wo, wi) / (4. * wo.z());
+        let pdf = self.visible_d(wo, wm) / (4. * cos_om);
+        Some((f, pdf, cos_om))
+    }
+}
+
+// Fresnel reflectance of a conductor with complex index of refraction
+// eta + ik, per channel.  cos_i: cosine of the incident angle.
+pub fn fresnel_conductor(cos_i: f32, eta: Color, k: Color) -> Color {
+    let channel = |eta: f32, k: f32| {
+        let cos2 = cos_i * cos_i;
+        let sin2 = 1. - cos2;
+        let (eta2, k2) = (eta * eta, k * k);
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4. * eta2 * k2).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.).sqrt();
+        let t2 = 2. * cos_i * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        0.5 * (rp + rs)
+    };
+    Color::new(channel(eta.r(), k.r()), channel(eta.g(), k.g()), channel(eta.b(), k.b()))
+}
+
+// Fresnel reflectance of an interface between dielectrics, unpolarized.
+// cos_i: cosine of the incident angle, negative from the inside
+// eta: index of refraction of the inside over the outside
+pub fn fresnel_dielectric(cos_i: f32, eta: f32) -> f32 {
+    let (cos_i, eta) = if cos_i < 0. { (-cos_i, 1. / eta) } else { (cos_i.min(1.), eta) };
+    let sin2_t = (1. - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1. {
+        return 1.; // total internal reflection
+    }
+    let cos_t = (1. - sin2_t).sqrt();
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (r_parallel * r_parallel + r_perpendicular * r_perpendicular)
+}
+
+// Refracts w (pointing away from the surface) through an interface with
+// normal n on the same side as w.  eta: index of refraction of the far
+// side over w's side.  None on total internal reflection.
+pub fn refract_about(w: Vec3, n: Vec3, eta: f32) -> Option<Vec3> {
+    let cos_i = Vec3::dot(&n, &w);
+    let sin2_t = (1. - cos_i * cos_i).max(0.) / (eta * eta);
+    if sin2_t >= 1. {
+        return None;
+    }
+    let cos_t = (1. - sin2_t).sqrt();
+    Some(-w / eta + (cos_i / eta - cos_t) * n)
+}
+
+}
@@ -0,0 +1,106 @@
+pub mod module {
+
+use crate::vec3::module::*;
+use crate::types::module::*;
+use crate::rand::module::*;
+
+const POINT_COUNT: usize = 256;
+
+// gradient noise with random unit vectors at the lattice points
+pub struct Perlin {
+    ranvec: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>
+}
+
+impl Perlin {
+    pub fn new() -> Perlin {
+        let ranvec = (0..POINT_COUNT)
+            .map(|_| Vec3::unit_vector(
+                2. * Vec3::new(rand_unit(), rand_unit(), rand_unit()) - Vec3::new(1.,1.,1.)))
+            .collect();
+        Perlin {
+            ranvec,
+            perm_x: generate_perm(),
+            perm_y: generate_perm(),
+            perm_z: generate_perm()
+        }
+    }
+
+    // returns a value in roughly [-1, 1]
+    pub fn noise(&self, p: &Point) -> f32 {
+        let u = p.x() - p.x().floor();
+        let v = p.y() - p.y().floor();
+        let w = p.z() - p.z().floor();
+        let i = p.x().floor() as i32;
+        let j = p.y().floor() as i32;
+        let k = p.z().floor() as i32;
+
+        let mut c = [[[Vec3::default(); 2]; 2]; 2];
+        for (di, plane) in c.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, cell) in row.iter_mut().enumerate() {
+                    let idx =
+                        self.perm_x[((i + di as i32) & 255) as usize] ^
+                        self.perm_y[((j + dj as i32) & 255) as usize] ^
+                        self.perm_z[((k + dk as i32) & 255) as usize];
+                    *cell = self.ranvec[idx];
+                }
+            }
+        }
+
+        perlin_interp(&c, u, v, w)
+    }
+
+    // sum of several octaves of noise
+    pub fn turb(&self, p: &Point, depth: u32) -> f32 {
+        let mut accum = 0.;
+        let mut temp_p = *p;
+        let mut weight = 1.;
+        for _ in 0..depth {
+            accum += weight * self.noise(&temp_p);
+            weight *= 0.5;
+            temp_p *= 2.;
+        }
+        accum.abs()
+    }
+}
+
+impl Default for Perlin {
+    fn default() -> Perlin {
+        Perlin::new()
+    }
+}
+
+fn generate_perm() -> Vec<usize> {
+    let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+    for i in (1..POINT_COUNT).rev() {
+        let target = ((rand_unit() * (i + 1) as f32) as usize).min(i);
+        p.swap(i, target);
+    }
+    p
+}
+
+fn perlin_interp(c: &[[[Vec3; 2]; 2]; 2], u: f32, v: f32, w: f32) -> f32 {
+    // hermite cubic to round off the lattice artifacts
+    let uu = u * u * (3. - 2. * u);
+    let vv = v * v * (3. - 2. * v);
+    let ww = w * w * (3. - 2. * w);
+    let mut accum = 0.;
+    for (i, plane) in c.iter().enumerate() {
+        for (j, row) in plane.iter().enumerate() {
+            for (k, cell) in row.iter().enumerate() {
+                let (fi, fj, fk) = (i as f32, j as f32, k as f32);
+                let weight_v = Vec3::new(u - fi, v - fj, w - fk);
+                accum += (fi * uu + (1. - fi) * (1. - uu)) *
+                         (fj * vv + (1. - fj) * (1. - vv)) *
+                         (fk * ww + (1. - fk) * (1. - ww)) *
+                         Vec3::dot(cell, &weight_v);
+            }
+        }
+    }
+    accum
+}
+
+}
@@ -1,6 +1,6 @@
 pub mod module {
 
-use std::io::Write;
+use std::io::{Error, ErrorKind, Read, Write};
 use std::fs::File;
 
 use crate::types::module::*;
@@ -11,18 +11,99 @@ pub fn write_ppm(img: &Image, path: &str) -> std::io::Result<()> {
 
     let mut file = File::create(path)?;
 
-    file.write(b"P3\n")?;
-    file.write(format!("{} {}\n", num_cols, num_rows).as_bytes())?;
-    file.write(b"255\n")?;
+    file.write_all(b"P3\n")?;
+    file.write_all(format!("{} {}\n", num_cols, num_rows).as_bytes())?;
+    file.write_all(b"255\n")?;
 
-    for row in 0..num_rows {
-        for col in 0..num_cols {
-            let (r,g,b) = img[row][col];
-            file.write(format!("{} {} {}\n", r, g, b).as_bytes())?;
+    for row in img.iter() {
+        for &(r,g,b) in row.iter() {
+            file.write_all(format!("{} {} {}\n", r, g, b).as_bytes())?;
         }
     }
 
     Ok(())
 }
 
+// Reads a binary or ascii PPM (P6/P3) or PGM (P5/P2) image into
+// img[row][col] with values scaled to [0, 1].  No gamma is removed, so
+// this suits data images like masks, normal maps and height fields.
+// Gray images repeat their value in all three channels.
+pub fn read_ppm(path: &str) -> std::io::Result<FloatImage> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    let bad = |msg: &str| Error::new(ErrorKind::InvalidData, format!("{}: {}", path, msg));
+
+    // header: magic, width, height, maxval separated by whitespace with
+    // optional # comments
+    let mut pos = 0;
+    let mut header = Vec::new();
+    while header.len() < 4 {
+        while pos < bytes.len() && (bytes[pos].is_ascii_whitespace() || bytes[pos] == b'#') {
+            if bytes[pos] == b'#' {
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    pos += 1;
+                }
+            }
+            else {
+                pos += 1;
+            }
+        }
+        let start = pos;
+        while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if start == pos {
+            return Err(bad("truncated header"));
+        }
+        header.push(String::from_utf8_lossy(&bytes[start..pos]).to_string());
+    }
+    // exactly one whitespace byte separates the header from binary data
+    pos += 1;
+
+    let magic = header[0].as_str();
+    let channels = match magic {
+        "P3" | "P6" => 3,
+        "P2" | "P5" => 1,
+        _ => return Err(bad("not a PPM or PGM image"))
+    };
+    let parse = |s: &str| s.parse::<usize>().map_err(|_| bad("bad header value"));
+    let (width, height, maxval) = (parse(&header[1])?, parse(&header[2])?, parse(&header[3])?);
+    if maxval == 0 || maxval > 65535 {
+        return Err(bad("bad maxval"));
+    }
+
+    let count = width * height * channels;
+    let values: Vec<f32> = if magic == "P3" || magic == "P2" {
+        String::from_utf8_lossy(&bytes[pos.min(bytes.len())..])
+            .split_ascii_whitespace()
+            .take(count)
+            .map(|v| v.parse::<f32>().unwrap_or(0.))
+            .collect()
+    }
+    else if maxval < 256 {
+        bytes.iter().skip(pos).take(count).map(|&b| b as f32).collect()
+    }
+    else {
+        // 16 bit samples are big endian
+        bytes[pos.min(bytes.len())..].chunks(2).take(count)
+            .map(|c| (((c[0] as u32) << 8) | *c.get(1).unwrap_or(&0) as u32) as f32)
+            .collect()
+    };
+    if values.len() < count {
+        return Err(bad("truncated image data"));
+    }
+
+    let scale = 1. / maxval as f32;
+    Ok((0..height).map(|row| (0..width).map(|col| {
+        let idx = (row * width + col) * channels;
+        if channels == 3 {
+            Color::new(values[idx] * scale, values[idx + 1] * scale, values[idx + 2] * scale)
+        }
+        else {
+            let v = values[idx] * scale;
+            Color::new(v, v, v)
+        }
+    }).collect()).collect())
+}
+
 }
@@ -0,0 +1,512 @@
+pub mod module {
+
+use crate::types::module::*;
+use crate::vec3::module::*;
+use crate::ray::module::*;
+use crate::hittable::module::*;
+use crate::transform::module::*;
+
+use std::f32::consts;
+
+// half the thickness of a disk's bounding box
+const DISK_PAD: f32 = 1e-4;
+// Newton steps taken to polish each root of the torus' quartic
+const POLISH_STEPS: u32 = 2;
+
+// All the shapes here are centered on the y axis and sweep around it,
+// from +x toward +z, through phi_max (all the way by default, like
+// pbrt's phimax).  u follows the sweep from 0 to 1.  Place them with
+// Translate and RotateY.
+
+fn full_sweep() -> f32 {
+    2. * consts::PI
+}
+
+// degrees: how far around the shape goes, clamped to [0, 360]
+fn sweep(degrees: f32) -> f32 {
+    degrees.clamp(0., 360.).to_radians()
+}
+
+// angle of p around the y axis in [0, 2 pi)
+fn phi(p: &Point) -> f32 {
+    let phi = p.z().atan2(p.x());
+    if phi < 0. { phi + 2. * consts::PI } else { phi }
+}
+
+// unit direction phi increases in at p, zero on the axis
+fn sweep_tangent(p: &Point) -> Vec3 {
+    let t = Vec3::new(-p.z(), 0., p.x());
+    let len = t.length();
+    if len > 0. { t / len } else { Vec3::init() }
+}
+
+// Real roots of a x^2 + b x + c in increasing order, in the form that
+// avoids cancellation between b and the square root
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a == 0. {
+        return if b == 0. { Vec::new() } else { vec![-c / b] };
+    }
+    let discriminant = b * b - 4. * a * c;
+    if discriminant < 0. {
+        return Vec::new();
+    }
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    if q == 0. {
+        // b and c are both zero
+        return vec![0.];
+    }
+    let (t0, t1) = (q / a, c / q);
+    if t0 < t1 { vec![t0, t1] } else { vec![t1, t0] }
+}
+
+// the largest real root of x^3 + a x^2 + b x + c
+fn largest_cubic_root(a: f64, b: f64, c: f64) -> f64 {
+    // depressed to y^3 + p y + q with x = y - a / 3
+    let p = b - a * a / 3.;
+    let q = 2. * a * a * a / 27. - a * b / 3. + c;
+    let shift = -a / 3.;
+    let discriminant = q * q / 4. + p * p * p / 27.;
+    if discriminant > 0. {
+        // one real root (Cardano)
+        let s = discriminant.sqrt();
+        (-q / 2. + s).cbrt() + (-q / 2. - s).cbrt() + shift
+    } else if p == 0. {
+        shift
+    } else {
+        // three real roots, the largest from the trigonometric form
+        let m = 2. * (-p / 3.).sqrt();
+        let theta = (3. * q / (p * m)).clamp(-1., 1.).acos() / 3.;
+        m * theta.cos() + shift
+    }
+}
+
+// Real roots of x^4 + a x^3 + b x^2 + c x + d in increasing order, by
+// Ferrari's method and then polished with Newton's method, which
+// takes back most of the precision the closed form loses
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    // depressed to y^4 + p y^2 + q y + r with x = y - a / 4
+    let (a2, a3) = (a * a, a * a * a);
+    let p = b - 3. * a2 / 8.;
+    let q = c - a * b / 2. + a3 / 8.;
+    let r = d - a * c / 4. + a2 * b / 16. - 3. * a2 * a2 / 256.;
+    let shift = -a / 4.;
+
+    let mut roots = Vec::new();
+    if q.abs() < 1e-12 {
+        // biquadratic, quadratic in y^2
+        for z in solve_quadratic(1., p, r) {
+            if z >= 0. {
+                roots.push(z.sqrt());
+                roots.push(-z.sqrt());
+            }
+        }
+    } else {
+        // m from the resolvent cubic makes both sides of
+        // (y^2 + p/2 + m)^2 = 2m y^2 - q y + m^2 + m p + p^2/4 - r
+        // squares, which splits the quartic into two quadratics
+        let m = largest_cubic_root(p, p * p / 4. - r, -q * q / 8.);
+        if m <= 0. {
+            return Vec::new();
+        }
+        let s = (2. * m).sqrt();
+        roots.extend(solve_quadratic(1., -s, p / 2. + m + q / (2. * s)));
+        roots.extend(solve_quadratic(1., s, p / 2. + m - q / (2. * s)));
+    }
+
+    let mut roots: Vec<f64> = roots.into_iter().map(|y| {
+        let mut x = y + shift;
+        for _ in 0..POLISH_STEPS {
+            let f = (((x + a) * x + b) * x + c) * x + d;
+            let df = ((4. * x + 3. * a) * x + 2. * b) * x + c;
+            if df == 0. {
+                break;
+            }
+            x -= f / df;
+        }
+        x
+    }).collect();
+    roots.sort_by(|x, y| x.total_cmp(y));
+    roots
+}
+
+// A flat disk facing +y at height y, with an optional hole in the
+// middle (an annulus).  v runs from the outer edge in.
+pub struct Disk {
+    y: f32,
+    radius: f32,
+    inner_radius: f32,
+    phi_max: f32,
+    material: u32
+}
+
+impl Disk {
+    pub fn new(y: f32, radius: f32, material: u32) -> Disk {
+        Disk::annulus(y, 0., radius, material)
+    }
+
+    pub fn annulus(y: f32, inner_radius: f32, radius: f32, material: u32) -> Disk {
+        assert!(0. <= inner_radius && inner_radius < radius, "an annulus needs 0 <= inner radius < radius");
+        Disk { y, radius, inner_radius, phi_max: full_sweep(), material }
+    }
+
+    pub fn with_phi_max(mut self, degrees: f32) -> Disk {
+        self.phi_max = sweep(degrees);
+        self
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        if r.dir().y() == 0. {
+            return false;
+        }
+        let t = (self.y - r.origin().y()) / r.dir().y();
+        if !(t > t_min && t < t_max) {
+            return false;
+        }
+        let p = r.point_at_parameter(t);
+        let dist = (p.x() * p.x() + p.z() * p.z()).sqrt();
+        if dist > self.radius || dist < self.inner_radius {
+            return false;
+        }
+        let phi = phi(&p);
+        if phi > self.phi_max {
+            return false;
+        }
+        record.t = t;
+        record.p = p;
+        record.normal = Vec3::new(0., 1., 0.);
+        record.u = phi / self.phi_max;
+        record.v = (self.radius - dist) / (self.radius - self.inner_radius);
+        record.tangent = sweep_tangent(&p);
+        record.mat = Some(self.material);
+        true
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(Point::new(-self.radius, self.y - DISK_PAD, -self.radius),
+                       Point::new(self.radius, self.y + DISK_PAD, self.radius)))
+    }
+}
+
+// A quadric surface of revolution, where the squared distance from the
+// axis is a quadratic in y, a + b y + c y^2, cut off at y_min and y_max.
+// Normals point away from the axis.  v runs from y_min to y_max.
+// Capping closes the ends with disks, though a partial sweep leaves
+// its sides open.
+pub struct Quadric {
+    profile: (f32, f32, f32),
+    y_min: f32,
+    y_max: f32,
+    phi_max: f32,
+    capped: bool,
+    caps: HittableList,
+    material: u32
+}
+
+impl Quadric {
+    fn new(profile: (f32, f32, f32), y_min: f32, y_max: f32, material: u32) -> Quadric {
+        assert!(y_min < y_max, "a quadric needs y_min below y_max");
+        Quadric {
+            profile, y_min, y_max,
+            phi_max: full_sweep(),
+            capped: false,
+            caps: HittableList::new(Vec::new()),
+            material
+        }
+    }
+
+    pub fn cylinder(radius: f32, y_min: f32, y_max: f32, material: u32) -> Quadric {
+        Quadric::new((radius * radius, 0., 0.), y_min, y_max, material)
+    }
+
+    // base of the given radius at y = 0 narrowing to a point at height
+    pub fn cone(radius: f32, height: f32, material: u32) -> Quadric {
+        assert!(height > 0., "a cone needs a positive height");
+        let k = radius * radius / (height * height);
+        Quadric::new((radius * radius, -2. * k * height, k), 0., height, material)
+    }
+
+    // A bowl with its bottom at the origin, radius wide at y_max.  y_min
+    // above 0 cuts off the bottom.
+    pub fn paraboloid(radius: f32, y_min: f32, y_max: f32, material: u32) -> Quadric {
+        assert!(y_max > 0., "a paraboloid needs y_max above 0");
+        Quadric::new((0., radius * radius / y_max, 0.), y_min, y_max, material)
+    }
+
+    // A hyperboloid of one sheet, waist: radius at y = 0, slope: how
+    // fast the radius grows away from there (0 makes a cylinder).
+    pub fn hyperboloid(waist: f32, slope: f32, y_min: f32, y_max: f32, material: u32) -> Quadric {
+        Quadric::new((waist * waist, 0., slope * slope), y_min, y_max, material)
+    }
+
+    pub fn with_phi_max(mut self, degrees: f32) -> Quadric {
+        self.phi_max = sweep(degrees);
+        self.build_caps();
+        self
+    }
+
+    pub fn capped(mut self) -> Quadric {
+        self.capped = true;
+        self.build_caps();
+        self
+    }
+
+    // squared distance from the axis at height y
+    fn radius_sq(&self, y: f32) -> f32 {
+        let (a, b, c) = self.profile;
+        a + b * y + c * y * y
+    }
+
+    fn build_caps(&mut self) {
+        let mut caps: Vec<Box<dyn Hittable>> = Vec::new();
+        if self.capped {
+            let degrees = self.phi_max.to_degrees();
+            let bottom = self.radius_sq(self.y_min).max(0.).sqrt();
+            if bottom > 0. {
+                // facing down, the same way round once flipped
+                caps.push(Box::new(FlipNormals::new(Box::new(
+                    Disk::new(self.y_min, bottom, self.material).with_phi_max(degrees)))));
+            }
+            let top = self.radius_sq(self.y_max).max(0.).sqrt();
+            if top > 0. {
+                caps.push(Box::new(Disk::new(self.y_max, top, self.material).with_phi_max(degrees)));
+            }
+        }
+        self.caps = HittableList::new(caps);
+    }
+
+    fn hit_side(&self, r: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        let (a, b, c) = (self.profile.0 as f64, self.profile.1 as f64, self.profile.2 as f64);
+        let (o, d) = (r.origin(), r.dir());
+        let (ox, oy, oz) = (o.x() as f64, o.y() as f64, o.z() as f64);
+        let (dx, dy, dz) = (d.x() as f64, d.y() as f64, d.z() as f64);
+        // x^2 + z^2 = a + b y + c y^2 along the ray
+        let qa = dx * dx + dz * dz - c * dy * dy;
+        let qb = 2. * (ox * dx + oz * dz) - b * dy - 2. * c * oy * dy;
+        let qc = ox * ox + oz * oz - a - b * oy - c * oy * oy;
+        for t in solve_quadratic(qa, qb, qc) {
+            let t = t as f32;
+            if !(t > t_min && t < t_max) {
+                continue;
+            }
+            let p = r.point_at_parameter(t);
+            if p.y() < self.y_min || p.y() > self.y_max {
+                continue;
+            }
+            let phi = phi(&p);
+            if phi > self.phi_max {
+                continue;
+            }
+            // the gradient of x^2 + z^2 - (a + b y + c y^2)
+            let slope = self.profile.1 + 2. * self.profile.2 * p.y();
+            let normal = Vec3::new(p.x(), -0.5 * slope, p.z());
+            let len = normal.length();
+            record.t = t;
+            record.p = p;
+            record.normal = if len > 0. { normal / len } else { Vec3::new(0., 1., 0.) };
+            record.u = phi / self.phi_max;
+            record.v = (p.y() - self.y_min) / (self.y_max - self.y_min);
+            record.tangent = sweep_tangent(&p);
+            record.mat = Some(self.material);
+            return true;
+        }
+        false
+    }
+}
+
+impl Hittable for Quadric {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        let mut closest = t_max;
+        let mut hit = false;
+        if self.hit_side(r, t_min, closest, record) {
+            hit = true;
+            closest = record.t;
+        }
+        if self.caps.hit(r, t_min, closest, record) {
+            hit = true;
+        }
+        hit
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // the widest point is at an end or where the radius turns round
+        let (_, b, c) = self.profile;
+        let mut widest = self.radius_sq(self.y_min).max(self.radius_sq(self.y_max));
+        if c != 0. {
+            let turn = -b / (2. * c);
+            if turn > self.y_min && turn < self.y_max {
+                widest = widest.max(self.radius_sq(turn));
+            }
+        }
+        let w = widest.max(0.).sqrt();
+        Some(Aabb::new(Point::new(-w, self.y_min, -w), Point::new(w, self.y_max, w)))
+    }
+}
+
+// A ring around the y axis.  major: radius of the ring, minor: of its
+// tube.  v goes around the tube from the outside edge, upward.
+pub struct Torus {
+    major: f32,
+    minor: f32,
+    phi_max: f32,
+    material: u32
+}
+
+impl Torus {
+    pub fn new(major: f32, minor: f32, material: u32) -> Torus {
+        Torus { major, minor, phi_max: full_sweep(), material }
+    }
+
+    pub fn with_phi_max(mut self, degrees: f32) -> Torus {
+        self.phi_max = sweep(degrees);
+        self
+    }
+
+    fn bounds(&self) -> Aabb {
+        let w = self.major + self.minor;
+        Aabb::new(Point::new(-w, -self.minor, -w), Point::new(w, self.minor, w))
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        // Solve from where the ray enters the bounding box along a unit
+        // direction, which keeps the quartic's coefficients small
+        let (start, _) = match self.bounds().hit(r, t_min, t_max) {
+            Some(span) => span,
+            None => return false
+        };
+        let speed = r.dir().length();
+        if speed == 0. {
+            return false;
+        }
+        let (o, d) = (r.point_at_parameter(start), r.dir() / speed);
+        let (ox, oy, oz) = (o.x() as f64, o.y() as f64, o.z() as f64);
+        let (dx, dy, dz) = (d.x() as f64, d.y() as f64, d.z() as f64);
+        let (big, small) = (self.major as f64, self.minor as f64);
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2) along the ray
+        let e = ox * dx + oy * dy + oz * dz;
+        let f = ox * ox + oy * oy + oz * oz + big * big - small * small;
+        let four_r2 = 4. * big * big;
+        let roots = solve_quartic(
+            4. * e,
+            4. * e * e + 2. * f - four_r2 * (dx * dx + dz * dz),
+            4. * e * f - 2. * four_r2 * (ox * dx + oz * dz),
+            f * f - four_r2 * (ox * ox + oz * oz));
+
+        for s in roots {
+            let t = start + s as f32 / speed;
+            if !(t > t_min && t < t_max) {
+                continue;
+            }
+            let p = r.point_at_parameter(t);
+            let phi = phi(&p);
+            if phi > self.phi_max {
+                continue;
+            }
+            // out from the middle of the tube
+            let dist = (p.x() * p.x() + p.z() * p.z()).sqrt();
+            let center = if dist > 0. {
+                Vec3::new(p.x(), 0., p.z()) * (self.major / dist)
+            } else {
+                Vec3::init()
+            };
+            let normal = (p - center) / self.minor;
+            let theta = normal.y().atan2(dist - self.major);
+            let theta = if theta < 0. { theta + 2. * consts::PI } else { theta };
+            record.t = t;
+            record.p = p;
+            record.normal = Vec3::unit_vector(normal);
+            record.u = phi / self.phi_max;
+            record.v = theta / (2. * consts::PI);
+            record.tangent = sweep_tangent(&p);
+            record.mat = Some(self.material);
+            return true;
+        }
+        false
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(roots: Vec<f64>, expected: &[f64]) {
+        assert_eq!(roots.len(), expected.len(), "{:?}", roots);
+        for (x, y) in roots.iter().zip(expected) {
+            assert!((x - y).abs() < 1e-6, "{:?}", roots);
+        }
+    }
+
+    #[test]
+    fn quartic_roots_come_out_in_order() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(solve_quartic(-10., 35., -50., 24.), &[1., 2., 3., 4.]);
+        // (x^2 - 4)(x^2 - 9), with no odd terms
+        assert_roots(solve_quartic(0., -13., 0., 36.), &[-3., -2., 2., 3.]);
+        // (x + 0.5)(x - 7)(x^2 + 1)
+        let (p, q) = (-6.5, -3.5); // x^2 + p x + q
+        assert_roots(solve_quartic(p, q + 1., p, q), &[-0.5, 7.]);
+        // (x^2 + 1)(x^2 + 4)
+        assert_roots(solve_quartic(0., 5., 0., 4.), &[]);
+        assert_roots(solve_quadratic(0., 2., -1.), &[0.5]);
+    }
+
+    #[test]
+    fn rays_hit_the_torus_tube() {
+        let torus = Torus::new(2., 0.5, 0);
+        let mut record = HitRecord::default();
+        let hit = |torus: &Torus, origin: Point, dir: Vec3, record: &mut HitRecord| {
+            torus.hit(&Ray::new(origin, dir), 0.001, f32::MAX, record)
+        };
+        // through the middle of the ring, onto the outside of the tube
+        assert!(hit(&torus, Point::new(-5., 0., 0.), Vec3::new(1., 0., 0.), &mut record));
+        assert!((record.t - 2.5).abs() < 1e-4);
+        assert!((record.normal - Vec3::new(-1., 0., 0.)).length() < 1e-4);
+        // down onto the top of the tube
+        assert!(hit(&torus, Point::new(0., 5., 2.), Vec3::new(0., -1., 0.), &mut record));
+        assert!((record.t - 4.5).abs() < 1e-4);
+        assert!((record.normal - Vec3::new(0., 1., 0.)).length() < 1e-4);
+        assert!((record.v - 0.25).abs() < 1e-4);
+        // down the hole
+        assert!(!hit(&torus, Point::new(0., 5., 0.), Vec3::new(0., -1., 0.), &mut record));
+        // from inside the tube, out the far side
+        assert!(hit(&torus, Point::new(2., 0., 0.), Vec3::new(1., 0., 0.), &mut record));
+        assert!((record.t - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn annulus_v_runs_from_the_outer_edge_in() {
+        let ring = Disk::annulus(0., 1., 3., 0);
+        let mut record = HitRecord::default();
+        assert!(ring.hit(&Ray::new(Point::new(2.5, 1., 0.), Vec3::new(0., -1., 0.)), 0.001, f32::MAX, &mut record));
+        assert!((record.v - 0.25).abs() < 1e-5);
+        assert!(!ring.hit(&Ray::new(Point::new(0.5, 1., 0.), Vec3::new(0., -1., 0.)), 0.001, f32::MAX, &mut record));
+    }
+
+    #[test]
+    #[should_panic]
+    fn annulus_needs_a_hole_smaller_than_itself() {
+        let _ = Disk::annulus(0., 1., 1., 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn cylinder_needs_y_min_below_y_max() {
+        let _ = Quadric::cylinder(1., 2., 2., 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn hyperboloid_needs_y_min_below_y_max() {
+        let _ = Quadric::hyperboloid(0.5, 1., 1., -1., 0);
+    }
+}
+
+}
@@ -4,158 +4,618 @@ use crate::types::module::*;
 use crate::vec3::module::*;
 use crate::ray::module::*;
 use crate::hittable::module::*;
-use crate::camera::module::*;
-use crate::material::module::*;
 use crate::rand::module::*;
+use crate::sampler::module::*;
+use crate::scenes::module::*;
+use crate::material::module::*;
+use crate::checkpoint::module::*;
+use crate::film::module::*;
+use crate::spectrum::module::*;
 
 use std::f32;
 
-fn get_color(
-    r: &Ray, world: &dyn Hittable, materials: &Vec<Box<dyn Material>>, depth: u32) -> Color {
+// sampler dimensions 0-1 pick the position in the pixel, 2-3 the point
+// on the lens, 4 the wavelengths in spectral mode, then every bounce
+// gets its own block of dimensions: the first half for the material
+// (as many as any takes, materials wrapping others reuse their choice
+// sample, see ReusedSampler), the second half for light sampling
+const CAMERA_DIMS: u32 = 5;
+const DIMS_PER_BOUNCE: u32 = 8;
+const LIGHT_DIMS: u32 = 4;
+
+const MAX_DEPTH: u32 = 50;
+
+// random walks through translucent objects take their dimensions from
+// after every bounce's, a block per step of the walk at each depth
+const WALK_DIMS: u32 = CAMERA_DIMS + (MAX_DEPTH + 1) * DIMS_PER_BOUNCE;
+const WALK_STEP_DIMS: u32 = 4;
+const MAX_WALK_STEPS: u32 = 256;
+
+// An RGB ratio (reflectance, attenuation) in the units r carries: as is
+// in RGB mode, at r's wavelengths in spectral mode
+fn as_reflectance(c: Color, r: &Ray) -> Vec3 {
+    match r.wavelengths {
+        Some(lambdas) => reflectance(c, &lambdas),
+        None => c
+    }
+}
+
+// RGB light in the units r carries
+fn as_light(c: Color, r: &Ray) -> Vec3 {
+    match r.wavelengths {
+        Some(lambdas) => illuminant(c, &lambdas),
+        None => c
+    }
+}
+
+// weight for combining two sampling strategies (Veach's power heuristic)
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b > 0. { a / (a + b) } else { 0. }
+}
+
+// Light from the background arriving at record directly, found by
+// sampling the background and weighted against the chance of the
+// material sampling the same direction.
+fn sample_background(r: &Ray, record: &HitRecord, mat: &dyn Material,
+                     scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+    let (u1, u2) = sampler.get_2d();
+    let (wi, light_pdf) = match scene.background.sample(u1, u2) {
+        Some(sample) => sample,
+        None => return Color::init()
+    };
+    if light_pdf <= 0. {
+        return Color::init();
+    }
+    let (f, mat_pdf) = match mat.eval(r, record, wi) {
+        Some(value) => value,
+        None => return Color::init()
+    };
+    if f.squared_length() == 0. {
+        return Color::init();
+    }
+    let shadow = Ray::new(record.p, wi);
+    let mut blocker = HitRecord::default();
+    if scene.world.hit(&shadow, 0.001, f32::MAX, &mut blocker) {
+        return Color::init();
+    }
+    as_reflectance(f, r) * as_light(scene.background.color(&shadow), r) *
+        (power_heuristic(light_pdf, mat_pdf) / light_pdf)
+}
+
+// Light from the scene's point-like lights arriving at record, which
+// rays can't find on their own
+fn sample_lights(r: &Ray, record: &HitRecord, mat: &dyn Material, scene: &Scene) -> Color {
+    let mut total = Color::init();
+    for light in scene.lights.iter() {
+        let sample = match light.sample_li(&record.p) {
+            Some(sample) => sample,
+            None => continue
+        };
+        let f = match mat.eval(r, record, sample.wi) {
+            Some((f, _)) => f,
+            None => continue
+        };
+        if f.squared_length() == 0. {
+            continue;
+        }
+        let shadow = Ray::new(record.p, sample.wi);
+        let mut blocker = HitRecord::default();
+        if scene.world.hit(&shadow, 0.001, sample.dist * (1. - 1e-4), &mut blocker) {
+            continue;
+        }
+        total += as_reflectance(f, r) * as_light(sample.li, r);
+    }
+    total
+}
+
+// Walks a ray that just crossed into a translucent object through its
+// inside.  Distances to scattering events are sampled by the
+// extinction of a randomly picked channel and weighted by all three
+// (spectral MIS).  Returns the ray from the last scattering event,
+// which next hits the boundary where the light leaves, and the
+// throughput along the walk, or None if the light was absorbed or the
+// walk went on too long.
+fn random_walk(entry: &Ray, medium: &Medium, scene: &Scene, sampler: &mut dyn Sampler,
+               depth: u32) -> Option<(Ray, Vec3)> {
+    let mut ray = Ray::new(entry.origin(), Vec3::unit_vector(entry.dir()));
+    ray.wavelengths = entry.wavelengths;
+    let mut throughput = Vec3::new(1., 1., 1.);
+    let average = |v: Vec3| (v.x() + v.y() + v.z()) / 3.;
+    let transmittance = |dist: f32| {
+        let t = medium.sigma_t;
+        Vec3::new((-t.x() * dist).exp(), (-t.y() * dist).exp(), (-t.z() * dist).exp())
+    };
+
+    for step in 0..MAX_WALK_STEPS {
+        sampler.set_dimension(WALK_DIMS + (depth * MAX_WALK_STEPS + step) * WALK_STEP_DIMS);
+        let channel = ((sampler.get_1d() * 3.) as usize).min(2);
+        let dist = -(1. - sampler.get_1d()).ln() / medium.sigma_t[channel];
+
+        // only the entry point is on a surface, scattering events are
+        // free to be as close to the boundary as they like without
+        // stepping over it
+        let t_min = if step == 0 { 0.001 } else { 0. };
+        let mut record = HitRecord::default();
+        if scene.world.hit(&ray, t_min, dist, &mut record) {
+            // passed through to the boundary, with the chance of that
+            // averaged over the channels
+            let tr = transmittance(record.t);
+            // backed up from events right by the boundary, for rays that
+            // skip hits closer than 0.001 to still find it
+            let mut exit = Ray::new(ray.point_at_parameter(-(0.002 - record.t).max(0.)), ray.dir());
+            exit.wavelengths = ray.wavelengths;
+            return Some((exit, throughput * tr / average(tr)));
+        }
+
+        let tr = transmittance(dist);
+        throughput *= medium.sigma_s * tr / average(medium.sigma_t * tr);
+        if throughput.squared_length() == 0. || !throughput.x().is_finite() {
+            return None;
+        }
+        let (u1, u2) = sampler.get_2d();
+        let wavelengths = ray.wavelengths;
+        ray = Ray::new(ray.point_at_parameter(dist), sample_uniform_sphere(u1, u2));
+        ray.wavelengths = wavelengths;
+    }
+    None
+}
+
+// mat_pdf: pdf the previous material picked r with, None for camera rays
+// and discrete (mirror, glass) bounces
+fn get_color(r: &Ray, scene: &Scene, sampler: &mut dyn Sampler,
+             depth: u32, mat_pdf: Option<f32>) -> Color {
     let mut record = HitRecord::default();
     // use a small t_min value here to avoid "shadow acne"
-    if world.hit(r, 0.001, f32::MAX, &mut record) {
+    if scene.world.hit(r, 0.001, f32::MAX, &mut record) {
+        let mat = scene.materials[record.mat.unwrap() as usize].as_ref();
+        let emitted = as_light(mat.emitted(&record), r);
+        if depth >= MAX_DEPTH {
+            return emitted;
+        }
         let mut scattered   = Ray::default();
         let mut attenuation = Vec3::default();
-        if depth < 50 &&
-           materials[record.mat.unwrap() as usize].scatter(
-               r, &record, &mut attenuation, &mut scattered) {
-            return attenuation * get_color(&scattered, world, materials, depth + 1);
-        }
-        let black = Color::new(0.,0.,0.);
-        return black;
-    }
-
-    // make it so -1 < y < 1
-    let unit_direction = Vec3::unit_vector(r.dir());
-    // shift and scale so 0 < t < 1
-    // so y = 1  => t = 1
-    //    y = -1 => t = 0
-    let t = 0.5f32 * (unit_direction.y() + 1.);
-    let blue  = Color::new(0.5, 0.7, 1.0); // a light blue
-    let white = Color::new(1.,1.,1.);
-    (1.-t)*white + t*blue
-}
-
-// chap8
-pub fn raytrace() -> Image {
-    let nx = 1200;
-    let ny = 800;
-    let ns = 60; // num samples / pixel
-
-    let mut mat_idx = 0;
-
-    let ground_mat = Lambertian::new(Color::new(0.5,0.5,0.5), mat_idx);
-    mat_idx += 1;
-    let dielectric = Dielectric::new(1.5, mat_idx);
-    mat_idx += 1;
-    let lambertian = Lambertian::new(Color::new(0.4,0.2,0.1), mat_idx);
-    mat_idx += 1;
-    let metal = Metal::new(Color::new(0.7,0.6,0.5), 0.0, mat_idx);
-    mat_idx += 1;
-
-    let mut materials:Vec<Box<dyn Material>> = vec![
-        Box::new(ground_mat),
-        Box::new(dielectric),
-        Box::new(lambertian),
-        Box::new(metal)];
-
-    let ground_sphere = Sphere::new(Point::new(0.,-1000.,0.), 1000., ground_mat.get_idx());
-    let sphere1 = Sphere::new(Point::new(0.,1.,0.), 1., dielectric.get_idx());
-    let sphere2 = Sphere::new(Point::new(-4.,1.,0.), 1., lambertian.get_idx());
-    let sphere3 = Sphere::new(Point::new(4.,1.,0.), 1., metal.get_idx());
-
-    let mut list:Vec<Box<dyn Hittable>> = vec![
-        Box::new(ground_sphere),
-        Box::new(sphere1),
-        Box::new(sphere2),
-        Box::new(sphere3)];
-
-    let num_spheres = 11;
-
-    for a in -num_spheres..num_spheres {
-        for b in -num_spheres..num_spheres {
-            let choose_mat = rand_unit();
-            let center = Point::new(a as f32+0.9*rand_unit(),0.2,b as f32+0.9*rand_unit());
-
-            if (center - Point::new(4.,0.2,0.)).length() > 0.9 {
-                if choose_mat < 0.8 { // diffuse
-                    let lambertian = Lambertian::new(
-                        Color::new(
-                            rand_unit()*rand_unit(),
-                            rand_unit()*rand_unit(),
-                            rand_unit()*rand_unit()), mat_idx);
-                    materials.push(Box::new(lambertian));
-                    let sphere = Sphere::new(center, 0.2, lambertian.get_idx());
-                    list.push(Box::new(sphere));
-                }
-                else if choose_mat < 0.95 { // metal
-                    let metal = Metal::new(
-                        Color::new(
-                            0.5*(1. + rand_unit()),
-                            0.5*(1. + rand_unit()),
-                            0.5*(1. + rand_unit())), 0., mat_idx);
-                    materials.push(Box::new(metal));
-                    let sphere = Sphere::new(center, 0.2, metal.get_idx());
-                    list.push(Box::new(sphere));
-                }
-                else { // glass
-                    let dielectric = Dielectric::new(1.5, mat_idx);
-                    materials.push(Box::new(dielectric));
-                    let sphere = Sphere::new(center, 0.2, dielectric.get_idx());
-                    list.push(Box::new(sphere));
+        let dim = CAMERA_DIMS + depth * DIMS_PER_BOUNCE;
+        sampler.set_dimension(dim);
+        let discrete = match mat.sample(r, &record, sampler, &mut attenuation, &mut scattered) {
+            Some(discrete) => discrete,
+            None => return emitted
+        };
+        let eval = mat.eval(r, &record, scattered.dir());
+        // lights are sampled for any continuous lobes, but a direction
+        // from a discrete one can't be weighed against that
+        let pdf = eval.filter(|_| !discrete).map(|(_, pdf)| pdf);
+        let direct = match eval {
+            Some(_) => {
+                sampler.set_dimension(dim + LIGHT_DIMS);
+                sample_background(r, &record, mat, scene, sampler) +
+                    sample_lights(r, &record, mat, scene)
+            }
+            None => Color::init()
+        };
+        scattered.wavelengths = r.wavelengths;
+        // light crossing into a translucent object carries on from
+        // where it comes back out
+        let medium = mat.interior(r, &record)
+            .filter(|_| Vec3::dot(&scattered.dir(), &record.normal) < 0.);
+        if let Some(medium) = medium {
+            let incoming = match random_walk(&scattered, &medium, scene, sampler, depth) {
+                Some((inside, weight)) => weight * get_color(&inside, scene, sampler, depth + 1, None),
+                None => Color::init()
+            };
+            return emitted + direct + as_reflectance(attenuation, r) * incoming;
+        }
+        let incoming = match r.wavelengths {
+            // the other wavelengths would have gone elsewhere, so only the
+            // hero's path is followed, standing in for all three
+            Some(lambdas) if mat.dispersive() && !is_terminated(&lambdas) => {
+                scattered.wavelengths = Some(terminate_secondary(&lambdas));
+                let hero = get_color(&scattered, scene, sampler, depth + 1, pdf).x();
+                Vec3::new(3. * hero, 0., 0.)
+            }
+            _ => get_color(&scattered, scene, sampler, depth + 1, pdf)
+        };
+        return emitted + direct + as_reflectance(attenuation, r) * incoming;
+    }
+
+    // the background was also sampled directly from the last hit
+    let background = as_light(scene.background.color(r), r);
+    match mat_pdf {
+        Some(pdf) => background * power_heuristic(pdf, scene.background.pdf(r.dir())),
+        None => background
+    }
+}
+
+// One camera ray through pixel (i, j) for sample s.  Returns where the
+// ray went through the film, in the raster coordinates Film uses, along
+// with the color it brought back.
+#[allow(clippy::too_many_arguments)]
+fn trace_sample(scene: &Scene, nx: u32, ny: u32, i: u32, j: u32, s: u32,
+                sampler: &mut dyn Sampler, seed: u64) -> (f32, f32, Color) {
+    seed_rand(hash_values(&[seed, i as u64, j as u64, s as u64]));
+    sampler.start_sample(i, j, s);
+    let (du, dv) = sampler.get_2d();
+    let u = ((i as f32) + du) / nx as f32;
+    let v = ((j as f32) + dv) / ny as f32;
+    let lens = sampler.get_2d();
+    let lambdas = sample_wavelengths(sampler.get_1d());
+    let color = match scene.camera.get_ray(u, v, lens) {
+        Some(mut r) if scene.spectral => {
+            r.wavelengths = Some(lambdas);
+            scene.camera.exposure() * to_rgb(get_color(&r, scene, sampler, 0, None), &lambdas)
+        }
+        Some(r) => scene.camera.exposure() * get_color(&r, scene, sampler, 0, None),
+        None => Color::init()
+    };
+    // j counts up from the bottom, film rows count down from the top
+    (i as f32 + du, ny as f32 - (j as f32 + dv), color)
+}
+
+// Renders samples_per_pixel samples for every pixel into the film.
+// seed: makes the random numbers of every pixel sample reproducible
+pub fn render(scene: &Scene, sampler: &mut dyn Sampler, seed: u64, film: &mut Film) {
+    let (nx, ny) = (film.nx, film.ny);
+    let ns = sampler.samples_per_pixel();
+
+    for j in (0..ny).rev() {
+        for i in 0..nx {
+            for s in 0..ns {
+                let (x, y, color) = trace_sample(scene, nx, ny, i, j, s, sampler, seed);
+                film.add_sample(x, y, color);
+            }
+        }
+    }
+}
+
+// Renders the linear (not gamma corrected) radiance of each pixel,
+// img[row][col] with row 0 at the top, averaging the samples taken in
+// each pixel.  nx, ny: image dimensions.
+fn render_box(scene: &Scene, nx: u32, ny: u32, sampler: &mut dyn Sampler, seed: u64) -> FloatImage {
+    let mut film = Film::new(nx, ny, Box::new(BoxFilter::new(0.5)));
+    render(scene, sampler, seed, &mut film);
+    film.image()
+}
+
+// samplers whose samples don't depend on the sample count, which
+// progressive rendering needs to resume
+pub const RESUMABLE_SAMPLERS: [&str; 3] = ["independent", "halton", "sobol"];
+
+pub struct ProgressiveSettings {
+    pub pass_size: u32,       // samples added to every pixel per pass
+    pub target_samples: u32,  // per pixel
+    pub checkpoint: Option<String>,
+    pub checkpoint_every: u32 // passes between checkpoints
+}
+
+// Renders in passes that each add pass_size samples to every pixel of
+// acc, checkpointing along the way, until every pixel has
+// target_samples.  Resuming from a checkpoint gives the same image as
+// one uninterrupted run.  This needs one of the RESUMABLE_SAMPLERS,
+// and, for filters wider than a pixel, the same pass size (kept in the
+// checkpoint), since that decides the order samples from neighboring
+// pixels are summed in.
+pub fn render_progressive(scene: &Scene, sampler: &mut dyn Sampler, acc: &mut Accumulation,
+                          settings: &ProgressiveSettings) -> std::io::Result<()> {
+    let (nx, ny) = (acc.film.nx, acc.film.ny);
+    let pass_size = settings.pass_size.max(1);
+    let mut passes = 0;
+
+    while acc.min_count() < settings.target_samples {
+        for row in 0..ny {
+            let j = ny - 1 - row;
+            for i in 0..nx {
+                let idx = (row * nx + i) as usize;
+                let start = acc.counts[idx];
+                let end = (start + pass_size).min(settings.target_samples).max(start);
+                for s in start..end {
+                    let (x, y, color) = trace_sample(scene, nx, ny, i, j, s, sampler, acc.seed);
+                    acc.film.add_sample(x, y, color);
                 }
-                mat_idx += 1;
+                acc.counts[idx] = end;
+            }
+        }
+
+        passes += 1;
+        if let Some(path) = &settings.checkpoint {
+            if passes % settings.checkpoint_every.max(1) == 0 {
+                acc.save(path)?;
             }
         }
     }
 
-    let world = HittableList::new(list);
+    if let Some(path) = &settings.checkpoint {
+        acc.save(path)?;
+    }
+    Ok(())
+}
 
-    let mut rows = Vec::new();
+pub struct AdaptiveSettings {
+    pub batch_size: u32,   // samples added to a pixel at a time
+    pub max_samples: u32,  // per pixel
+    pub target_error: f32  // stop once stderr / mean luminance is below this
+}
 
-    let lookfrom   = Point::new(13.,2.,3.);
-    let lookat     = Point::new(0.,0.,0.);
-    let vup        = Vec3::new(0.,1.,0.);
-    let vfov       = 20.;
-    let aspect     = nx as f32 / ny as f32;
-    let aperature  = 0.1;
-    let focus_dist = 10.;
+// running mean and variance of a pixel's samples (Welford's method)
+#[derive(Copy, Clone, Default)]
+pub struct PixelStats {
+    pub count: u32,
+    pub mean: Color,
+    lum_mean: f32,
+    lum_m2: f32
+}
+
+impl PixelStats {
+    pub fn add(&mut self, c: Color) {
+        self.count += 1;
+        let n = self.count as f32;
+        self.mean += (c - self.mean) / n;
+        let lum = luminance(c);
+        let delta = lum - self.lum_mean;
+        self.lum_mean += delta / n;
+        self.lum_m2 += delta * (lum - self.lum_mean);
+    }
 
-    let cam = Camera::new(
-        lookfrom,
-        lookat,
-        vup,
-        vfov,
-        aspect,
-        aperature,
-        focus_dist);
+    // sample variance of the luminance
+    pub fn variance(&self) -> f32 {
+        if self.count < 2 { 0. } else { self.lum_m2 / (self.count - 1) as f32 }
+    }
+
+    // standard error of the mean relative to the mean itself
+    pub fn relative_error(&self) -> f32 {
+        if self.count < 2 {
+            return f32::INFINITY;
+        }
+        let std_error = (self.variance() / self.count as f32).sqrt();
+        // dark pixels are judged against a floor so noise in the
+        // shadows doesn't demand endless samples
+        std_error / self.lum_mean.max(0.01)
+    }
+}
+
+// Like render, but keeps adding batches of samples to each pixel until
+// its relative error drops below the target or it reaches max_samples.
+// Returns the number of samples each pixel took.
+pub fn render_adaptive(scene: &Scene, sampler: &mut dyn Sampler, seed: u64,
+                       settings: &AdaptiveSettings, film: &mut Film) -> Vec<Vec<u32>> {
+    let (nx, ny) = (film.nx, film.ny);
+    let batch_size = settings.batch_size.clamp(2, settings.max_samples.max(2));
+    let mut counts = Vec::new();
 
     for j in (0..ny).rev() {
-        let mut cols = Vec::new();
+        let mut count_cols = Vec::new();
         for i in 0..nx {
-            let mut color = Color::init();
-            for _ in 0..ns {
-                let u = ((i as f32) + rand_unit()) / nx as f32;
-                let v = ((j as f32) + rand_unit()) / ny as f32;
-                let r = cam.get_ray(u, v);
-                color += get_color(&r, &world, &materials, 0);
+            let mut stats = PixelStats::default();
+            while stats.count < settings.max_samples {
+                let end = (stats.count + batch_size).min(settings.max_samples);
+                for s in stats.count..end {
+                    let (x, y, color) = trace_sample(scene, nx, ny, i, j, s, sampler, seed);
+                    stats.add(color);
+                    film.add_sample(x, y, color);
+                }
+                if stats.relative_error() < settings.target_error {
+                    break;
+                }
             }
+            count_cols.push(stats.count);
+        }
+        counts.push(count_cols);
+    }
 
-            color /= ns as f32;
-            // gamma 2 correction
-            color = Color::new(color.r().sqrt(), color.g().sqrt(), color.b().sqrt());
+    counts
+}
 
-            color *= 255.99f32;
-            let ir = color.r() as u8;
-            let ig = color.g() as u8;
-            let ib = color.b() as u8;
-            cols.push((ir,ig,ib));
+// visualize per pixel sample counts, dark blue (few) through red (max)
+pub fn sample_heatmap(counts: &[Vec<u32>], max_samples: u32) -> Image {
+    counts.iter().map(|row| row.iter().map(|&n| {
+        let t = (n as f32 / max_samples.max(1) as f32).clamp(0., 1.);
+        // piecewise linear blue -> cyan -> yellow -> red
+        let c = if t < 1. / 3. {
+            let k = 3. * t;
+            Color::new(0., k, 0.5 + 0.5 * k)
+        }
+        else if t < 2. / 3. {
+            let k = 3. * t - 1.;
+            Color::new(k, 1., 1. - k)
+        }
+        else {
+            let k = 3. * t - 2.;
+            Color::new(1., 1. - k, 0.)
+        };
+        let c = c * 255.99f32;
+        (c.r() as u8, c.g() as u8, c.b() as u8)
+    }).collect()).collect()
+}
+
+// gamma correct and quantize linear radiance for display
+pub fn to_image(img: &FloatImage) -> Image {
+    img.iter().map(|row| row.iter().map(|&c| {
+        // gamma 2 correction
+        let mut color = Color::new(c.r().sqrt(), c.g().sqrt(), c.b().sqrt());
+
+        // emitters can push a pixel past white
+        color = Color::new(color.r().min(1.), color.g().min(1.), color.b().min(1.));
+
+        color *= 255.99f32;
+        (color.r() as u8, color.g() as u8, color.b() as u8)
+    }).collect()).collect()
+}
+
+// root mean square error over all pixels and channels
+pub fn rmse(img: &FloatImage, reference: &FloatImage) -> f32 {
+    let mut sum = 0.;
+    let mut count = 0;
+    for (row, ref_row) in img.iter().zip(reference.iter()) {
+        for (c, r) in row.iter().zip(ref_row.iter()) {
+            sum += (*c - *r).squared_length();
+            count += 3;
+        }
+    }
+    (sum / count as f32).sqrt()
+}
+
+// Renders the scene with every sampler at spp samples per pixel and
+// returns each one's RMSE against a reference rendered with
+// reference_spp samples per pixel.
+pub fn compare_samplers(scene: &Scene, nx: u32, ny: u32, spp: u32,
+                        reference_spp: u32, seed: u64) -> Vec<(&'static str, f32)> {
+    // use a different seed so the reference noise isn't shared
+    let ref_seed = mix_bits(seed ^ 0x5eed);
+    let mut ref_sampler = sampler_by_name("sobol", reference_spp, ref_seed).unwrap();
+    let reference = render_box(scene, nx, ny, ref_sampler.as_mut(), ref_seed);
+
+    SAMPLER_NAMES.iter().map(|&name| {
+        let mut sampler = sampler_by_name(name, spp, seed).unwrap();
+        let img = render_box(scene, nx, ny, sampler.as_mut(), seed);
+        (name, rmse(&img, &reference))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::module::View;
+
+    // Renders all the passes in one go, and again stopping partway,
+    // saving a checkpoint and carrying on from it, which must give the
+    // same film bit for bit
+    #[test]
+    fn resumed_render_matches_uninterrupted() {
+        let (nx, ny, seed) = (12, 8, 30);
+        let (pass_size, passes, before_save) = (2, 4, 1);
+        let scene = by_name("metal_glass", nx as f32 / ny as f32).unwrap();
+        let new_acc = || {
+            let filter = filter_by_name("gaussian", default_filter_radius("gaussian")).unwrap();
+            Accumulation::new("metal_glass", "halton", seed, "gaussian", pass_size, ImageOptions::default(),
+                              Film::new(nx, ny, filter))
+        };
+        let settings = |passes: u32, checkpoint: Option<String>| ProgressiveSettings {
+            pass_size,
+            target_samples: passes * pass_size,
+            checkpoint,
+            checkpoint_every: 1
+        };
+
+        let mut straight = new_acc();
+        let mut sampler = sampler_by_name("halton", passes * pass_size, seed).unwrap();
+        render_progressive(&scene, sampler.as_mut(), &mut straight, &settings(passes, None)).unwrap();
+
+        let path = std::env::temp_dir().join(format!("resume-test-{}.ckpt", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let mut first = new_acc();
+        let mut sampler = sampler_by_name("halton", passes * pass_size, seed).unwrap();
+        render_progressive(&scene, sampler.as_mut(), &mut first,
+                           &settings(before_save, Some(path.clone()))).unwrap();
+        let mut resumed = Accumulation::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut sampler = sampler_by_name("halton", passes * pass_size, seed).unwrap();
+        render_progressive(&scene, sampler.as_mut(), &mut resumed, &settings(passes, None)).unwrap();
+
+        assert!(straight.film.sums.iter().any(|c| c.x() > 0.));
+        assert_eq!(straight.counts, resumed.counts);
+        for idx in 0..(nx * ny) as usize {
+            assert_eq!(straight.film.weights[idx].to_bits(), resumed.film.weights[idx].to_bits());
+            for k in 0..3 {
+                assert_eq!(straight.film.sums[idx][k].to_bits(), resumed.film.sums[idx][k].to_bits());
+                assert_eq!(straight.film.box_sums[idx][k].to_bits(), resumed.film.box_sums[idx][k].to_bits());
+            }
+            assert_eq!(straight.film.box_counts[idx], resumed.film.box_counts[idx]);
+        }
+    }
+
+    #[test]
+    fn pixel_stats_match_the_sample_mean_and_variance() {
+        let samples = [0.2, 1.5, 0.7, 3.1, 0.0, 2.2];
+        let mut stats = PixelStats::default();
+        for &v in &samples {
+            stats.add(Color::new(v, v, v));
+        }
+        let n = samples.len() as f32;
+        let mean = samples.iter().sum::<f32>() / n;
+        let variance = samples.iter().map(|v| (v - mean) * (v - mean)).sum::<f32>() / (n - 1.);
+        assert!((stats.mean.r() - mean).abs() < 1e-5);
+        assert!((stats.variance() - variance).abs() < 1e-4);
+        assert!((stats.relative_error() - (variance / n).sqrt() / mean).abs() < 1e-4);
+    }
+
+    // the sky along the top row is flat so it stops after the first
+    // batch, while the noisier ground along the bottom takes more
+    #[test]
+    fn adaptive_render_stops_early_on_flat_pixels() {
+        let (nx, ny) = (8, 6);
+        let scene = by_name("diffuse", nx as f32 / ny as f32).unwrap();
+        let settings = AdaptiveSettings { batch_size: 8, max_samples: 64, target_error: 0.05 };
+        let mut film = Film::new(nx, ny, Box::new(BoxFilter::new(0.5)));
+        let mut sampler = IndependentSampler::new(64);
+        let counts = render_adaptive(&scene, &mut sampler, 29, &settings, &mut film);
+        assert_eq!(counts.len(), ny as usize);
+        assert!(counts[0].iter().all(|&n| n == 8));
+        assert!(counts[ny as usize - 1].iter().any(|&n| n > 8));
+        assert!(counts.iter().flatten().all(|&n| (8..=64).contains(&n)));
+    }
+
+    // the stratified and low discrepancy samplers are what they're for
+    // only if they beat independent samples at the same count
+    #[test]
+    fn samplers_beat_independent_sampling() {
+        let (nx, ny) = (16, 12);
+        let scene = by_name("diffuse", nx as f32 / ny as f32).unwrap();
+        let errors = compare_samplers(&scene, nx, ny, 16, 256, 28);
+        let error = |name: &str| errors.iter().find(|(n, _)| *n == name).unwrap().1;
+        for name in ["stratified", "halton", "sobol"] {
+            assert!(error(name) < error("independent"),
+                    "{} RMSE {} not below independent {}", name, error(name), error("independent"));
         }
-        rows.push(cols);
     }
 
-    rows
+    #[test]
+    fn power_heuristic_weights_sum_to_one() {
+        for (a, b) in [(1., 1.), (0.2, 5.), (3., 0.), (1e-3, 40.)] {
+            let sum = power_heuristic(a, b) + power_heuristic(b, a);
+            assert!((sum - 1.).abs() < 1e-6, "{} {}", a, b);
+        }
+        // the better strategy gets more of the weight
+        assert!(power_heuristic(4., 1.) > 0.9);
+        assert_eq!(power_heuristic(0., 0.), 0.);
+    }
+
+    // Walks into a unit sphere from its top leave through its surface,
+    // and a brighter albedo lets more of the light back out
+    #[test]
+    fn random_walks_leave_through_the_boundary() {
+        seed_rand(44);
+        let walk = |albedo: f32| {
+            let material = Subsurface::new(Color::new(albedo, albedo, albedo), Color::new(0.2, 0.2, 0.2), 0);
+            let view = View {
+                lookfrom: Point::new(0., 0., 5.),
+                lookat: Point::new(0., 0., 0.),
+                vup: Vec3::new(0., 1., 0.),
+                vfov: 30.,
+                aperature: 0.,
+                focus_dist: 5.
+            };
+            let scene = Scene::new(HittableList::new(vec![Box::new(Sphere::new(Point::new(0., 0., 0.), 1., 0))]),
+                                   vec![Box::new(material.clone())], view, 1., Background::Sky);
+            let entry = Ray::new(Point::new(0., 1., 0.), Vec3::new(0., -1., 0.));
+            let record = HitRecord { p: entry.origin(), normal: Vec3::new(0., 1., 0.), ..HitRecord::default() };
+            let medium = material.interior(&entry, &record).unwrap();
+            let mut sampler = IndependentSampler::new(1);
+            let n = 2000;
+            let mut total = 0.;
+            for _ in 0..n {
+                if let Some((exit, throughput)) = random_walk(&entry, &medium, &scene, &mut sampler, 0) {
+                    assert!(exit.origin().length() < 1. + 1e-4);
+                    let mut record = HitRecord::default();
+                    assert!(scene.world.hit(&exit, 0.001, f32::MAX, &mut record));
+                    assert!((record.p.length() - 1.).abs() < 1e-3);
+                    total += throughput.x();
+                }
+            }
+            total / n as f32
+        };
+        let (white, dark) = (walk(0.99), walk(0.2));
+        assert!(white > 0.95, "{}", white);
+        assert!(dark > 0.1 && dark < 0.6, "{}", dark);
+    }
 }
 
-}
\ No newline at end of file
+}
@@ -2,33 +2,87 @@ pub mod module {
 
 use crate::vec3::module::*;
 use crate::types::module::*;
-use rand::Rng;
+use rand::{Rng, SeedableRng, XorShiftRng};
 
-pub fn random_in_unit_disk() -> Point {
-    let mut p: Point;
+use std::cell::RefCell;
+use std::f32::consts;
 
-    loop {
-        p = 2.0 * Point::new(rand_unit(), rand_unit(), 0.) - Vec3::new(1.,1.,0.);
-        if  Vec3::dot(&p, &p) < 1.0 {
-            break;
-        }
-    }
-    p
+thread_local! {
+    // per thread generator behind rand_unit().  It starts from a random
+    // seed; renders reseed it so results are reproducible.
+    static RNG: RefCell<XorShiftRng> = RefCell::new(rand::thread_rng().gen());
+}
+
+// restart the generator behind rand_unit() at a known state
+pub fn seed_rand(seed: u64) {
+    let a = mix_bits(seed);
+    let b = mix_bits(a ^ 0x9e37_79b9_7f4a_7c15);
+    // xorshift must not be seeded with all zeros
+    let words = [a as u32 | 1, (a >> 32) as u32, b as u32, (b >> 32) as u32];
+    RNG.with(|rng| *rng.borrow_mut() = XorShiftRng::from_seed(words));
+}
+
+// 64 bit finalizer from splitmix64, good enough to decorrelate
+// neighboring integers (pixel coordinates, sample indices, ...)
+pub fn mix_bits(v: u64) -> u64 {
+    let mut v = v;
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^= v >> 33;
+    v
+}
+
+// combine several values into one well mixed hash
+pub fn hash_values(values: &[u64]) -> u64 {
+    values.iter().fold(0x51_7cc1_b727_220a_u64, |h, &v| {
+        mix_bits(h ^ v.wrapping_add(0x9e37_79b9_7f4a_7c15).wrapping_add(h << 6))
+    })
+}
+
+// The sample_* functions warp a point (u1, u2) in [0,1)^2 to a direction
+// without rejection, so a well distributed input stays well distributed.
+// Hemispheres are about +z; use an Onb to move them onto a normal.
+
+// pdf is cos(theta) / pi
+pub fn sample_cosine_hemisphere(u1: f32, u2: f32) -> Vec3 {
+    let r = u1.sqrt();
+    let phi = 2. * consts::PI * u2;
+    Vec3::new(r * phi.cos(), r * phi.sin(), (1. - u1).max(0.).sqrt())
+}
+
+// pdf is 1 / (4 pi)
+pub fn sample_uniform_sphere(u1: f32, u2: f32) -> Vec3 {
+    let z = 1. - 2. * u1;
+    let r = (1. - z * z).max(0.).sqrt();
+    let phi = 2. * consts::PI * u2;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
 }
 
-pub fn random_in_unit_sphere() -> Vec3 {
-    let mut p:Point;
-    loop {
-        p = 2.0 * Point::new(rand_unit(), rand_unit(), rand_unit()) - Vec3::new(1.,1.,1.);
-        if p.squared_length() < 1.0 {
-            break;
-        }
+// point in the unit disk (z = 0) using Shirley and Chiu's concentric
+// mapping, which keeps strata compact
+pub fn sample_unit_disk(u1: f32, u2: f32) -> Point {
+    let a = 2. * u1 - 1.;
+    let b = 2. * u2 - 1.;
+    if a == 0. && b == 0. {
+        return Point::init();
     }
-    p
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, consts::FRAC_PI_4 * (b / a))
+    }
+    else {
+        (b, consts::FRAC_PI_2 - consts::FRAC_PI_4 * (a / b))
+    };
+    Point::new(r * theta.cos(), r * theta.sin(), 0.)
+}
+
+pub fn cosine_hemisphere_pdf(cos_theta: f32) -> f32 {
+    cos_theta.max(0.) * consts::FRAC_1_PI
 }
 
 pub fn rand_unit() -> f32 {
-    rand::thread_rng().gen_range(0.0f32, 1.0f32)
+    RNG.with(|rng| rng.borrow_mut().gen_range(0.0f32, 1.0f32))
 }
 
-}
\ No newline at end of file
+}
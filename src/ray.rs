@@ -7,12 +7,13 @@ use crate::types::module::*;
 #[derive(Default)]
 pub struct Ray {
     orig: Point,
-    dir: Vec3
+    dir: Vec3,
+    pub wavelengths: Option<Vec3> // nm, hero first, in spectral mode
 }
 
 impl Ray {
     pub fn new(orig: Point, dir: Vec3) -> Ray {
-        Ray { orig: orig, dir: dir }
+        Ray { orig, dir, wavelengths: None }
     }
 
     pub fn origin(&self) -> Point {
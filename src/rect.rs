@@ -0,0 +1,152 @@
+pub mod module {
+
+use crate::types::module::*;
+use crate::vec3::module::*;
+use crate::ray::module::*;
+use crate::hittable::module::*;
+use crate::transform::module::*;
+
+// axis aligned rectangles, one per plane.  The normal points
+// along the positive remaining axis.
+
+pub struct XYRect {
+    x0: f32, x1: f32,
+    y0: f32, y1: f32,
+    k: f32,
+    material: u32
+}
+
+pub struct XZRect {
+    x0: f32, x1: f32,
+    z0: f32, z1: f32,
+    k: f32,
+    material: u32
+}
+
+pub struct YZRect {
+    y0: f32, y1: f32,
+    z0: f32, z1: f32,
+    k: f32,
+    material: u32
+}
+
+impl XYRect {
+    pub fn new(x0: f32, x1: f32, y0: f32, y1: f32, k: f32, material: u32) -> XYRect {
+        XYRect { x0, x1, y0, y1, k, material }
+    }
+}
+
+impl XZRect {
+    pub fn new(x0: f32, x1: f32, z0: f32, z1: f32, k: f32, material: u32) -> XZRect {
+        XZRect { x0, x1, z0, z1, k, material }
+    }
+}
+
+impl YZRect {
+    pub fn new(y0: f32, y1: f32, z0: f32, z1: f32, k: f32, material: u32) -> YZRect {
+        YZRect { y0, y1, z0, z1, k, material }
+    }
+}
+
+// shared intersection for a rectangle lying in the plane axis[c] = k,
+// spanning [a0, a1] along axis[a] and [b0, b1] along axis[b]
+#[allow(clippy::too_many_arguments)]
+fn hit_rect(r: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord,
+            (a, a0, a1): (usize, f32, f32),
+            (b, b0, b1): (usize, f32, f32),
+            (c, k): (usize, f32),
+            material: u32) -> bool {
+    let t = (k - r.origin()[c]) / r.dir()[c];
+    if !(t > t_min && t < t_max) {
+        return false;
+    }
+    let p = r.point_at_parameter(t);
+    if p[a] < a0 || p[a] > a1 || p[b] < b0 || p[b] > b1 {
+        return false;
+    }
+    let mut n = [0., 0., 0.];
+    n[c] = 1.;
+    let mut tangent = [0., 0., 0.];
+    tangent[a] = 1.;
+    record.t = t;
+    record.p = p;
+    record.normal = Vec3::new(n[0], n[1], n[2]);
+    record.u = (p[a] - a0) / (a1 - a0);
+    record.v = (p[b] - b0) / (b1 - b0);
+    record.tangent = Vec3::new(tangent[0], tangent[1], tangent[2]);
+    record.mat = Some(material);
+    true
+}
+
+// half the thickness of a rectangle's bounding box
+const RECT_PAD: f32 = 1e-4;
+
+impl Hittable for XYRect {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        hit_rect(r, t_min, t_max, record,
+                 (0, self.x0, self.x1), (1, self.y0, self.y1), (2, self.k), self.material)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // padded to give it some thickness
+        Some(Aabb::new(Point::new(self.x0, self.y0, self.k - RECT_PAD),
+                       Point::new(self.x1, self.y1, self.k + RECT_PAD)))
+    }
+}
+
+impl Hittable for XZRect {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        hit_rect(r, t_min, t_max, record,
+                 (0, self.x0, self.x1), (2, self.z0, self.z1), (1, self.k), self.material)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(Point::new(self.x0, self.k - RECT_PAD, self.z0),
+                       Point::new(self.x1, self.k + RECT_PAD, self.z1)))
+    }
+}
+
+impl Hittable for YZRect {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        hit_rect(r, t_min, t_max, record,
+                 (1, self.y0, self.y1), (2, self.z0, self.z1), (0, self.k), self.material)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(Point::new(self.k - RECT_PAD, self.y0, self.z0),
+                       Point::new(self.k + RECT_PAD, self.y1, self.z1)))
+    }
+}
+
+// an axis aligned box built out of six rectangles with outward normals
+pub struct Cuboid {
+    sides: HittableList
+}
+
+impl Cuboid {
+    pub fn new(p0: Point, p1: Point, material: u32) -> Cuboid {
+        let sides: Vec<Box<dyn Hittable>> = vec![
+            Box::new(XYRect::new(p0.x(), p1.x(), p0.y(), p1.y(), p1.z(), material)),
+            Box::new(FlipNormals::new(Box::new(
+                XYRect::new(p0.x(), p1.x(), p0.y(), p1.y(), p0.z(), material)))),
+            Box::new(XZRect::new(p0.x(), p1.x(), p0.z(), p1.z(), p1.y(), material)),
+            Box::new(FlipNormals::new(Box::new(
+                XZRect::new(p0.x(), p1.x(), p0.z(), p1.z(), p0.y(), material)))),
+            Box::new(YZRect::new(p0.y(), p1.y(), p0.z(), p1.z(), p1.x(), material)),
+            Box::new(FlipNormals::new(Box::new(
+                YZRect::new(p0.y(), p1.y(), p0.z(), p1.z(), p0.x(), material))))];
+        Cuboid { sides: HittableList::new(sides) }
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        self.sides.hit(r, t_min, t_max, record)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.sides.bounding_box()
+    }
+}
+
+}
@@ -0,0 +1,437 @@
+pub mod module {
+
+use crate::rand::module::*;
+
+// A sampler hands out the random numbers for one pixel sample as a
+// stream of dimensions.  Each call to get_1d/get_2d consumes the next
+// dimension(s).  Renders place each use at a fixed dimension (see
+// set_dimension) so the same dimension means the same thing, e.g., the
+// lens position, across all the samples of a pixel.
+pub trait Sampler {
+    fn samples_per_pixel(&self) -> u32;
+
+    // begin sample index (< samples_per_pixel) of pixel (x, y),
+    // restarting at dimension 0
+    fn start_sample(&mut self, x: u32, y: u32, index: u32);
+
+    fn set_dimension(&mut self, dim: u32);
+
+    fn get_1d(&mut self) -> f32;
+
+    fn get_2d(&mut self) -> (f32, f32);
+}
+
+pub const SAMPLER_NAMES: [&str; 4] = ["independent", "stratified", "halton", "sobol"];
+
+// spp: samples per pixel, seed: decorrelates whole renders
+pub fn sampler_by_name(name: &str, spp: u32, seed: u64) -> Option<Box<dyn Sampler>> {
+    let sampler: Box<dyn Sampler> = match name {
+        "independent" => Box::new(IndependentSampler::new(spp)),
+        "stratified"  => Box::new(StratifiedSampler::new(spp, seed)),
+        "halton"      => Box::new(HaltonSampler::new(spp, seed)),
+        "sobol"       => Box::new(SobolSampler::new(spp, seed)),
+        _ => return None
+    };
+    Some(sampler)
+}
+
+// largest f32 below 1
+const ONE_MINUS_EPSILON: f32 = 1. - f32::EPSILON / 2.;
+
+// u32 -> [0, 1) keeping the top 24 bits
+fn bits_to_unit(bits: u32) -> f32 {
+    (bits >> 8) as f32 * (1. / (1u32 << 24) as f32)
+}
+
+// per pixel, per dimension seed
+fn dimension_hash(seed: u64, x: u32, y: u32, dim: u32) -> u64 {
+    hash_values(&[seed, x as u64, y as u64, dim as u64])
+}
+
+// u in [lo, hi) stretched back out to [0, 1), the randomness left in
+// a sample after using it to pick that range
+pub fn remap(u: f32, lo: f32, hi: f32) -> f32 {
+    if hi <= lo {
+        return 0.;
+    }
+    ((u - lo) / (hi - lo)).clamp(0., ONE_MINUS_EPSILON)
+}
+
+// Hands out first as the next sample (the first half of a 2D one), then
+// carries on with the wrapped sampler's dimensions.  Materials that pick
+// between others pass on their choice sample, remapped, so nesting them
+// takes no more dimensions than the materials inside.
+pub struct ReusedSampler<'a> {
+    sampler: &'a mut dyn Sampler,
+    first: Option<f32>
+}
+
+impl<'a> ReusedSampler<'a> {
+    pub fn new(sampler: &'a mut dyn Sampler, first: f32) -> ReusedSampler<'a> {
+        ReusedSampler { sampler, first: Some(first) }
+    }
+}
+
+impl Sampler for ReusedSampler<'_> {
+    fn samples_per_pixel(&self) -> u32 {
+        self.sampler.samples_per_pixel()
+    }
+
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.first = None;
+        self.sampler.start_sample(x, y, index);
+    }
+
+    fn set_dimension(&mut self, dim: u32) {
+        self.first = None;
+        self.sampler.set_dimension(dim);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        match self.first.take() {
+            Some(u) => u,
+            None => self.sampler.get_1d()
+        }
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        match self.first.take() {
+            Some(u) => (u, self.sampler.get_1d()),
+            None => self.sampler.get_2d()
+        }
+    }
+}
+
+// uniform random numbers from rand_unit(), which the render reseeds for
+// every pixel sample
+pub struct IndependentSampler {
+    spp: u32
+}
+
+impl IndependentSampler {
+    pub fn new(spp: u32) -> IndependentSampler {
+        IndependentSampler { spp }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn samples_per_pixel(&self) -> u32 { self.spp }
+
+    fn start_sample(&mut self, _: u32, _: u32, _: u32) {}
+
+    fn set_dimension(&mut self, _: u32) {}
+
+    fn get_1d(&mut self) -> f32 {
+        rand_unit()
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (rand_unit(), rand_unit())
+    }
+}
+
+// Kensler's hash based permutation ("Correlated Multi-Jittered
+// Sampling", 2013): element i of a random permutation of 0..l chosen
+// by p, without storing the permutation
+fn permute(i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    let mut i = i;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    (i + p) % l
+}
+
+// Jittered grid.  1D dimensions use spp strata, 2D dimensions the
+// largest grid that fits.  The strata are visited in a different
+// shuffled order for each dimension so dimensions don't correlate.
+pub struct StratifiedSampler {
+    spp: u32,
+    seed: u64,
+    x: u32,
+    y: u32,
+    index: u32,
+    dim: u32
+}
+
+impl StratifiedSampler {
+    pub fn new(spp: u32, seed: u64) -> StratifiedSampler {
+        StratifiedSampler { spp, seed, x: 0, y: 0, index: 0, dim: 0 }
+    }
+
+    fn stratum(&self, count: u32, hash: u64) -> u32 {
+        permute(self.index % count, count, hash as u32)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn samples_per_pixel(&self) -> u32 { self.spp }
+
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.x = x;
+        self.y = y;
+        self.index = index;
+        self.dim = 0;
+    }
+
+    fn set_dimension(&mut self, dim: u32) {
+        self.dim = dim;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let hash = dimension_hash(self.seed, self.x, self.y, self.dim);
+        self.dim += 1;
+        let stratum = self.stratum(self.spp, hash);
+        let jitter = rand_unit();
+        ((stratum as f32 + jitter) / self.spp as f32).min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let hash = dimension_hash(self.seed, self.x, self.y, self.dim);
+        self.dim += 2;
+        let nx = (self.spp as f32).sqrt() as u32;
+        let ny = self.spp / nx;
+        let stratum = self.stratum(nx * ny, hash);
+        let (sx, sy) = (stratum % nx, stratum / nx);
+        let u = ((sx as f32 + rand_unit()) / nx as f32).min(ONE_MINUS_EPSILON);
+        let v = ((sy as f32 + rand_unit()) / ny as f32).min(ONE_MINUS_EPSILON);
+        (u, v)
+    }
+}
+
+// the first n primes, used as halton bases
+fn first_primes(n: usize) -> Vec<u32> {
+    let mut primes: Vec<u32> = Vec::with_capacity(n);
+    let mut candidate = 2;
+    while primes.len() < n {
+        if primes.iter().take_while(|&&p| p * p <= candidate).all(|&p| candidate % p != 0) {
+            primes.push(candidate);
+        }
+        candidate += 1;
+    }
+    primes
+}
+
+// mirror the base-b digits of a around the decimal point
+fn radical_inverse(base: u32, a: u32) -> f32 {
+    let inv_base = 1. / base as f64;
+    let mut a = a;
+    let mut reversed: u64 = 0;
+    let mut inv_base_n = 1.;
+    while a > 0 {
+        let next = a / base;
+        let digit = a - next * base;
+        reversed = reversed * base as u64 + digit as u64;
+        inv_base_n *= inv_base;
+        a = next;
+    }
+    ((reversed as f64 * inv_base_n) as f32).min(ONE_MINUS_EPSILON)
+}
+
+// Number of halton dimensions before falling back to random numbers;
+// large prime bases have poor distribution at low sample counts anyway.
+// That covers the camera and the first 31 bounces (see ramp.rs), but
+// deeper bounces and subsurface random walks get independent samples.
+const HALTON_DIMENSIONS: usize = 256;
+
+// Halton sequence with a different prime base per dimension.  Each
+// pixel gets its own Cranley-Patterson rotation so neighboring pixels
+// don't share the same pattern.
+pub struct HaltonSampler {
+    spp: u32,
+    seed: u64,
+    primes: Vec<u32>,
+    x: u32,
+    y: u32,
+    index: u32,
+    dim: u32
+}
+
+impl HaltonSampler {
+    pub fn new(spp: u32, seed: u64) -> HaltonSampler {
+        HaltonSampler {
+            spp, seed,
+            primes: first_primes(HALTON_DIMENSIONS),
+            x: 0, y: 0, index: 0, dim: 0
+        }
+    }
+
+    fn sample_dimension(&self, dim: u32) -> f32 {
+        if dim as usize >= self.primes.len() {
+            return rand_unit();
+        }
+        let hash = dimension_hash(self.seed, self.x, self.y, dim);
+        let offset = bits_to_unit(hash as u32);
+        // skip index 0 which is 0 in every base
+        let v = radical_inverse(self.primes[dim as usize], self.index + 1) + offset;
+        if v >= 1. { (v - 1.).min(ONE_MINUS_EPSILON) } else { v }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn samples_per_pixel(&self) -> u32 { self.spp }
+
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.x = x;
+        self.y = y;
+        self.index = index;
+        self.dim = 0;
+    }
+
+    fn set_dimension(&mut self, dim: u32) {
+        self.dim = dim;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let v = self.sample_dimension(self.dim);
+        self.dim += 1;
+        v
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let u = self.sample_dimension(self.dim);
+        let v = self.sample_dimension(self.dim + 1);
+        self.dim += 2;
+        (u, v)
+    }
+}
+
+// Laine and Karras style hash which only lets bits affect higher bits;
+// applied to reversed bits it becomes an Owen scramble
+fn laine_karras_permutation(x: u32, seed: u32) -> u32 {
+    let mut x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+// the first two sobol dimensions as 32 bit fractions
+fn sobol_2d(index: u32) -> (u32, u32) {
+    let x = index.reverse_bits();
+    let mut y = 0;
+    let mut v = 1u32 << 31;
+    let mut i = index;
+    while i != 0 {
+        if i & 1 != 0 {
+            y ^= v;
+        }
+        i >>= 1;
+        v ^= v >> 1;
+    }
+    (x, y)
+}
+
+// Owen scrambled Sobol, following Burley's "Practical Hash-based Owen
+// Scrambling" (2020).  Every 1D or 2D request uses the well distributed
+// first Sobol dimensions with its own scramble and its own shuffle of
+// the sample order ("padding"), so any number of dimensions are
+// available and they don't correlate with each other.
+pub struct SobolSampler {
+    spp: u32,
+    seed: u64,
+    x: u32,
+    y: u32,
+    index: u32,
+    dim: u32
+}
+
+impl SobolSampler {
+    pub fn new(spp: u32, seed: u64) -> SobolSampler {
+        SobolSampler { spp, seed, x: 0, y: 0, index: 0, dim: 0 }
+    }
+
+    fn shuffled_point(&self) -> (u32, u32, u64) {
+        let hash = dimension_hash(self.seed, self.x, self.y, self.dim);
+        let index = nested_uniform_scramble(self.index, hash as u32);
+        let (x, y) = sobol_2d(index);
+        (x, y, hash)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn samples_per_pixel(&self) -> u32 { self.spp }
+
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.x = x;
+        self.y = y;
+        self.index = index;
+        self.dim = 0;
+    }
+
+    fn set_dimension(&mut self, dim: u32) {
+        self.dim = dim;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let (x, _, hash) = self.shuffled_point();
+        self.dim += 1;
+        bits_to_unit(nested_uniform_scramble(x, (hash >> 32) as u32))
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let (x, y, hash) = self.shuffled_point();
+        self.dim += 2;
+        let h = mix_bits(hash);
+        (bits_to_unit(nested_uniform_scramble(x, h as u32)),
+         bits_to_unit(nested_uniform_scramble(y, (h >> 32) as u32)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // with a power of two count, every 1D dimension puts one sample in
+    // each of the count equal strata
+    #[test]
+    fn stratified_and_sobol_fill_every_stratum() {
+        const SPP: u32 = 16;
+        for name in ["stratified", "sobol"] {
+            let mut sampler = sampler_by_name(name, SPP, 28).unwrap();
+            for dim in [0, 7, 300] {
+                let mut seen = [false; SPP as usize];
+                for index in 0..SPP {
+                    sampler.start_sample(3, 5, index);
+                    sampler.set_dimension(dim);
+                    let u = sampler.get_1d();
+                    assert!((0. ..1.).contains(&u));
+                    seen[(u * SPP as f32) as usize] = true;
+                }
+                assert!(seen.iter().all(|&s| s), "{} leaves strata of dimension {} empty", name, dim);
+            }
+        }
+    }
+}
+
+}
@@ -0,0 +1,1065 @@
+pub mod module {
+
+use crate::types::module::*;
+use crate::vec3::module::*;
+use crate::ray::module::*;
+use crate::hittable::module::*;
+use crate::camera::module::*;
+use crate::material::module::*;
+use crate::texture::module::*;
+use crate::rect::module::*;
+use crate::transform::module::*;
+use crate::rand::module::*;
+use crate::environment::module::*;
+use crate::sky::module::*;
+use crate::light::module::*;
+use crate::sdf::module::*;
+use crate::csg::module::*;
+use crate::quadric::module::*;
+use crate::heightfield::module::*;
+use crate::perlin::module::*;
+
+use std::rc::Rc;
+
+// what a ray sees when it leaves the scene
+pub enum Background {
+    Sky, // white to light blue gradient
+    Solid(Color),
+    Environment(EnvironmentMap),
+    PhysicalSky(PhysicalSky)
+}
+
+impl Background {
+    pub fn color(&self, r: &Ray) -> Color {
+        match self {
+            Background::Sky => {
+                // make it so -1 < y < 1
+                let unit_direction = Vec3::unit_vector(r.dir());
+                // shift and scale so 0 < t < 1
+                // so y = 1  => t = 1
+                //    y = -1 => t = 0
+                let t = 0.5f32 * (unit_direction.y() + 1.);
+                let blue  = Color::new(0.5, 0.7, 1.0); // a light blue
+                let white = Color::new(1.,1.,1.);
+                (1.-t)*white + t*blue
+            }
+            Background::Solid(c) => *c,
+            Background::Environment(env) => env.radiance(r.dir()),
+            Background::PhysicalSky(sky) => sky.radiance(r.dir())
+        }
+    }
+
+    // Picks a direction to sample the background as a light from,
+    // returning it with its pdf per unit solid angle.  None for
+    // backgrounds that aren't worth sampling directly.
+    pub fn sample(&self, u1: f32, u2: f32) -> Option<(Vec3, f32)> {
+        match self {
+            Background::Environment(env) => Some(env.sample(u1, u2)),
+            Background::PhysicalSky(sky) => Some(sky.sample(u1, u2)),
+            _ => None
+        }
+    }
+
+    // pdf of sample() picking dir
+    pub fn pdf(&self, dir: Vec3) -> f32 {
+        match self {
+            Background::Environment(env) => env.pdf(dir),
+            Background::PhysicalSky(sky) => sky.pdf(dir),
+            _ => 0.
+        }
+    }
+}
+
+pub struct Scene {
+    pub world: HittableList,
+    pub materials: Vec<Box<dyn Material>>,
+    pub view: View,
+    pub camera: Box<dyn Camera>,
+    pub background: Background,
+    pub lights: Vec<Box<dyn Light>>, // point-like lights, on top of emissive materials
+    pub spectral: bool // trace wavelengths instead of RGB
+}
+
+impl Scene {
+    // looks through a perspective camera at view, see camera_by_name
+    // for other projections
+    pub fn new(mut world: HittableList, materials: Vec<Box<dyn Material>>,
+               view: View, aspect: f32, background: Background) -> Scene {
+        world.set_opacity(materials.iter().map(|m| m.opacity()).collect());
+        let camera = Box::new(ThinLensCamera::from_view(&view, aspect));
+        Scene { world, materials, view, camera, background, lights: Vec::new(), spectral: false }
+    }
+}
+
+// all the built-in scenes, in the order they appear in the books
+pub const SCENE_NAMES: [&str; 22] = [
+    "normals",
+    "diffuse",
+    "metal_glass",
+    "defocus",
+    "random_spheres",
+    "cornell_box",
+    "checkered_spheres",
+    "perlin_spheres",
+    "simple_light",
+    "punctual_lights",
+    "conductors",
+    "rough_glass",
+    "principled",
+    "layered",
+    "dispersion",
+    "subsurface",
+    "bumps",
+    "cutouts",
+    "sdf",
+    "csg",
+    "quadrics",
+    "terrain"
+];
+
+// aspect: width / height of the image the scene will be rendered to
+pub fn by_name(name: &str, aspect: f32) -> Option<Scene> {
+    let scene = match name {
+        "normals"           => normals(aspect),
+        "diffuse"           => diffuse(aspect),
+        "metal_glass"       => metal_glass(aspect),
+        "defocus"           => defocus(aspect),
+        "random_spheres"    => random_spheres(aspect),
+        "cornell_box"       => cornell_box(aspect),
+        "checkered_spheres" => checkered_spheres(aspect),
+        "perlin_spheres"    => perlin_spheres(aspect),
+        "simple_light"      => simple_light(aspect),
+        "punctual_lights"   => punctual_lights(aspect),
+        "conductors"        => conductors(aspect),
+        "rough_glass"       => rough_glass(aspect),
+        "principled"        => principled(aspect),
+        "layered"           => layered(aspect),
+        "dispersion"        => dispersion(aspect),
+        "subsurface"        => subsurface(aspect),
+        "bumps"             => bumps(aspect),
+        "cutouts"           => cutouts(aspect),
+        "sdf"               => sdf(aspect),
+        "csg"               => csg(aspect),
+        "quadrics"          => quadrics(aspect),
+        "terrain"           => terrain(aspect),
+        _ => return None
+    };
+    Some(scene)
+}
+
+// hands out material table indices as materials are added
+struct MaterialTable {
+    list: Vec<Box<dyn Material>>
+}
+
+impl MaterialTable {
+    fn new() -> MaterialTable {
+        MaterialTable { list: Vec::new() }
+    }
+
+    fn next_idx(&self) -> u32 {
+        self.list.len() as u32
+    }
+
+    fn add<M: Material + 'static>(&mut self, mat: M) -> u32 {
+        let idx = mat.get_idx();
+        assert_eq!(idx, self.next_idx());
+        self.list.push(Box::new(mat));
+        idx
+    }
+}
+
+fn simple_view() -> View {
+    View {
+        lookfrom: Point::new(0.,0.,0.),
+        lookat: Point::new(0.,0.,-1.),
+        vup: Vec3::new(0.,1.,0.),
+        vfov: 90.,
+        aperature: 0.,
+        focus_dist: 1.
+    }
+}
+
+// chap5: a sphere on a ground sphere, shaded by normal
+pub fn normals(aspect: f32) -> Scene {
+    let mut mats = MaterialTable::new();
+    let normal = mats.add(NormalShade::new(mats.next_idx()));
+    let list: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere::new(Point::new(0.,0.,-1.), 0.5, normal)),
+        Box::new(Sphere::new(Point::new(0.,-100.5,-1.), 100., normal))];
+    Scene::new(
+        HittableList::new(list),
+        mats.list,
+        simple_view(), aspect,
+        Background::Sky)
+}
+
+// chap7: matte spheres
+pub fn diffuse(aspect: f32) -> Scene {
+    let mut mats = MaterialTable::new();
+    let gray = mats.add(Lambertian::new(Color::new(0.5,0.5,0.5), mats.next_idx()));
+    let list: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere::new(Point::new(0.,0.,-1.), 0.5, gray)),
+        Box::new(Sphere::new(Point::new(0.,-100.5,-1.), 100., gray))];
+    Scene::new(
+        HittableList::new(list),
+        mats.list,
+        simple_view(), aspect,
+        Background::Sky)
+}
+
+// diffuse, hollow glass and metal spheres side by side
+fn metal_glass_world() -> (HittableList, Vec<Box<dyn Material>>) {
+    let mut mats = MaterialTable::new();
+    let ground = mats.add(Lambertian::new(Color::new(0.8,0.8,0.0), mats.next_idx()));
+    let center = mats.add(Lambertian::new(Color::new(0.1,0.2,0.5), mats.next_idx()));
+    let glass  = mats.add(Dielectric::new(1.5, mats.next_idx()));
+    let metal  = mats.add(Metal::new(Color::new(0.8,0.6,0.2), 0.0, mats.next_idx()));
+    let list: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere::new(Point::new(0.,-100.5,-1.), 100., ground)),
+        Box::new(Sphere::new(Point::new(0.,0.,-1.), 0.5, center)),
+        Box::new(Sphere::new(Point::new(-1.,0.,-1.), 0.5, glass)),
+        // negative radius flips the normal making a hollow bubble
+        Box::new(Sphere::new(Point::new(-1.,0.,-1.), -0.45, glass)),
+        Box::new(Sphere::new(Point::new(1.,0.,-1.), 0.5, metal))];
+    (HittableList::new(list), mats.list)
+}
+
+// chap9
+pub fn metal_glass(aspect: f32) -> Scene {
+    let (world, materials) = metal_glass_world();
+    Scene::new(
+        world,
+        materials,
+        simple_view(), aspect,
+        Background::Sky)
+}
+
+// chap11: the metal_glass scene through a wide aperture
+pub fn defocus(aspect: f32) -> Scene {
+    let (world, materials) = metal_glass_world();
+    let lookfrom = Point::new(3.,3.,2.);
+    let lookat   = Point::new(0.,0.,-1.);
+    let view = View {
+        lookfrom,
+        lookat,
+        vup: Vec3::new(0.,1.,0.),
+        vfov: 20.,
+        aperature: 2.0,
+        focus_dist: (lookfrom - lookat).length()
+    };
+    Scene::new(
+        world,
+        materials,
+        view, aspect,
+        Background::Sky)
+}
+
+// chap12: the cover image
+pub fn random_spheres(aspect: f32) -> Scene {
+    let mut mats = MaterialTable::new();
+
+    let ground_mat = mats.add(Lambertian::new(Color::new(0.5,0.5,0.5), mats.next_idx()));
+    let dielectric = mats.add(Dielectric::new(1.5, mats.next_idx()));
+    let lambertian = mats.add(Lambertian::new(Color::new(0.4,0.2,0.1), mats.next_idx()));
+    let metal = mats.add(Metal::new(Color::new(0.7,0.6,0.5), 0.0, mats.next_idx()));
+
+    let ground_sphere = Sphere::new(Point::new(0.,-1000.,0.), 1000., ground_mat);
+    let sphere1 = Sphere::new(Point::new(0.,1.,0.), 1., dielectric);
+    let sphere2 = Sphere::new(Point::new(-4.,1.,0.), 1., lambertian);
+    let sphere3 = Sphere::new(Point::new(4.,1.,0.), 1., metal);
+
+    let mut list:Vec<Box<dyn Hittable>> = vec![
+        Box::new(ground_sphere),
+        Box::new(sphere1),
+        Box::new(sphere2),
+        Box::new(sphere3)];
+
+    let num_spheres = 11;
+
+    for a in -num_spheres..num_spheres {
+        for b in -num_spheres..num_spheres {
+            let choose_mat = rand_unit();
+            let center = Point::new(a as f32+0.9*rand_unit(),0.2,b as f32+0.9*rand_unit());
+
+            if (center - Point::new(4.,0.2,0.)).length() > 0.9 {
+                let mat = if choose_mat < 0.8 { // diffuse
+                    mats.add(Lambertian::new(
+                        Color::new(
+                            rand_unit()*rand_unit(),
+                            rand_unit()*rand_unit(),
+                            rand_unit()*rand_unit()), mats.next_idx()))
+                }
+                else if choose_mat < 0.95 { // metal
+                    mats.add(Metal::new(
+                        Color::new(
+                            0.5*(1. + rand_unit()),
+                            0.5*(1. + rand_unit()),
+                            0.5*(1. + rand_unit())), 0., mats.next_idx()))
+                }
+                else { // glass
+                    mats.add(Dielectric::new(1.5, mats.next_idx()))
+                };
+                list.push(Box::new(Sphere::new(center, 0.2, mat)));
+            }
+        }
+    }
+
+    let lookfrom   = Point::new(13.,2.,3.);
+    let lookat     = Point::new(0.,0.,0.);
+    let vup        = Vec3::new(0.,1.,0.);
+    let vfov       = 20.;
+    let aperature  = 0.1;
+    let focus_dist = 10.;
+
+    let view = View {
+        lookfrom,
+        lookat,
+        vup,
+        vfov,
+        aperature,
+        focus_dist
+    };
+
+    Scene::new(
+        HittableList::new(list),
+        mats.list,
+        view, aspect,
+        Background::Sky)
+}
+
+// the next week, chap6: a closed room lit from a ceiling panel
+pub fn cornell_box(aspect: f32) -> Scene {
+    let mut mats = MaterialTable::new();
+    let red   = mats.add(Lambertian::new(Color::new(0.65,0.05,0.05), mats.next_idx()));
+    let white = mats.add(Lambertian::new(Color::new(0.73,0.73,0.73), mats.next_idx()));
+    let green = mats.add(Lambertian::new(Color::new(0.12,0.45,0.15), mats.next_idx()));
+    let light = mats.add(DiffuseLight::new(Color::new(15.,15.,15.), mats.next_idx()));
+
+    let tall_box = Translate::new(
+        Box::new(RotateY::new(
+            Box::new(Cuboid::new(Point::new(0.,0.,0.), Point::new(165.,330.,165.), white)),
+            15.)),
+        Vec3::new(265.,0.,295.));
+    let short_box = Translate::new(
+        Box::new(RotateY::new(
+            Box::new(Cuboid::new(Point::new(0.,0.,0.), Point::new(165.,165.,165.), white)),
+            -18.)),
+        Vec3::new(130.,0.,65.));
+
+    let list: Vec<Box<dyn Hittable>> = vec![
+        Box::new(FlipNormals::new(Box::new(YZRect::new(0., 555., 0., 555., 555., green)))),
+        Box::new(YZRect::new(0., 555., 0., 555., 0., red)),
+        Box::new(XZRect::new(213., 343., 227., 332., 554., light)),
+        Box::new(FlipNormals::new(Box::new(XZRect::new(0., 555., 0., 555., 555., white)))),
+        Box::new(XZRect::new(0., 555., 0., 555., 0., white)),
+        Box::new(FlipNormals::new(Box::new(XYRect::new(0., 555., 0., 555., 555., white)))),
+        Box::new(tall_box),
+        Box::new(short_box)];
+
+    let view = View {
+        lookfrom: Point::new(278.,278.,-800.),
+        lookat: Point::new(278.,278.,0.),
+        vup: Vec3::new(0.,1.,0.),
+        vfov: 40.,
+        aperature: 0.,
+        focus_dist: 10.
+    };
+
+    Scene::new(
+        HittableList::new(list),
+        mats.list,
+        view, aspect,
+        Background::Solid(Color::init()))
+}
+
+fn book2_view() -> View {
+    View {
+        lookfrom: Point::new(13.,2.,3.),
+        lookat: Point::new(0.,0.,0.),
+        vup: Vec3::new(0.,1.,0.),
+        vfov: 20.,
+        aperature: 0.,
+        focus_dist: 10.
+    }
+}
+
+// the next week, chap3
+pub fn checkered_spheres(aspect: f32) -> Scene {
+    let mut mats = MaterialTable::new();
+    let checker: Rc<dyn Texture> = Rc::new(CheckerTexture::new(
+        Box::new(ConstantTexture::new(Color::new(0.2,0.3,0.1))),
+        Box::new(ConstantTexture::new(Color::new(0.9,0.9,0.9))),
+        10.));
+    let checker = mats.add(Lambertian::textured(checker, mats.next_idx()));
+    let list: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere::new(Point::new(0.,-10.,0.), 10., checker)),
+        Box::new(Sphere::new(Point::new(0.,10.,0.), 10., checker))];
+    Scene::new(
+        HittableList::new(list),
+        mats.list,
+        book2_view(), aspect,
+        Background::Sky)
+}
+
+fn perlin_world(mats: &mut MaterialTable) -> Vec<Box<dyn Hittable>> {
+    let noise: Rc<dyn Texture> = Rc::new(NoiseTexture::new(4.));
+    let marble = mats.add(Lambertian::textured(noise, mats.next_idx()));
+    vec![
+        Box::new(Sphere::new(Point::new(0.,-1000.,0.), 1000., marble)),
+        Box::new(Sphere::new(Point::new(0.,2.,0.), 2., marble))]
+}
+
+// the next week, chap4
+pub fn perlin_spheres(aspect: f32) -> Scene {
+    let mut mats = MaterialTable::new();
+    let list = perlin_world(&mut mats);
+    Scene::new(
+        HittableList::new(list),
+        mats.list,
+        book2_view(), aspect,
+        Background::Sky)
+}
+
+// the next week, chap6: the perlin spheres lit only by emitters
+pub fn simple_light(aspect: f32) -> Scene {
+    let mut mats = MaterialTable::new();
+    let mut list = perlin_world(&mut mats);
+    let light = mats.add(DiffuseLight::new(Color::new(4.,4.,4.), mats.next_idx()));
+    list.push(Box::new(Sphere::new(Point::new(0.,7.,0.), 2., light)));
+    list.push(Box::new(XYRect::new(3., 5., 1., 3., -2., light)));
+
+    let view = View {
+        lookfrom: Point::new(26.,3.,6.),
+        lookat: Point::new(0.,2.,0.),
+        vup: Vec3::new(0.,1.,0.),
+        vfov: 20.,
+        aperature: 0.,
+        focus_dist: 10.
+    };
+
+    Scene::new(
+        HittableList::new(list),
+        mats.list,
+        view, aspect,
+        Background::Solid(Color::init()))
+}
+
+// the perlin spheres under a spot light, a point light and a dim
+// directional light, in meters and cd/m^2
+pub fn punctual_lights(aspect: f32) -> Scene {
+    let mut mats = MaterialTable::new();
+    let list = perlin_world(&mut mats);
+    let view = View {
+        lookfrom: Point::new(26.,3.,6.),
+        lookat: Point::new(0.,2.,0.),
+        vup: Vec3::new(0.,1.,0.),
+        vfov: 20.,
+        aperature: 0.,
+        focus_dist: 10.
+    };
+
+    let mut scene = Scene::new(
+        HittableList::new(list),
+        mats.list,
+        view, aspect,
+        Background::Solid(Color::init()));
+    scene.lights.push(Box::new(SpotLight::new(
+        Point::new(4.,8.,4.), Point::new(0.,2.,0.), Color::new(60.,55.,45.), 25., 18.)));
+    scene.lights.push(Box::new(PointLight::new(Point::new(3.,1.,-4.), Color::new(2.,4.,8.))));
+    scene.lights.push(Box::new(DirectionalLight::new(Vec3::new(-1.,-1.,0.5), Color::new(0.1,0.1,0.1))));
+    scene
+}
+
+// checkered ground under a row of five unit spheres, one per material
+fn material_row(mats: &mut MaterialTable, row: &[u32]) -> Vec<Box<dyn Hittable>> {
+    let ground = mats.add(Lambertian::textured(Rc::new(CheckerTexture::new(
+        Box::new(ConstantTexture::new(Color::new(0.2, 0.2, 0.2))),
+        Box::new(ConstantTexture::new(Color::new(0.8, 0.8, 0.8))),
+        10.)), mats.next_idx()));
+    let mut list: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere::new(Point::new(0.,-1000.,0.), 1000., ground))];
+    for (i, mat) in row.iter().enumerate() {
+        list.push(Box::new(Sphere::new(Point::new(2.2 * (i as f32 - 2.), 1., 0.), 1., *mat)));
+    }
+    list
+}
+
+fn material_row_view() -> View {
+    View {
+        lookfrom: Point::new(0.,3.,12.),
+        lookat: Point::new(0.,1.,0.),
+        vup: Vec3::new(0.,1.,0.),
+        vfov: 30.,
+        aperature: 0.,
+        focus_dist: 12.
+    }
+}
+
+// a row of microfacet metals getting rougher from left to right, the
+// silver one brushed along its lines of latitude
+pub fn conductors(aspect: f32) -> Scene {
+    let mut mats = MaterialTable::new();
+    let metals = [("gold", 0., 0.), ("copper", 0.2, 0.2), ("silver", 0.1, 0.5),
+                  ("aluminium", 0.4, 0.4), ("iron", 0.7, 0.7)];
+    let row: Vec<u32> = metals.iter().map(|(name, roughness_u, roughness_v)| {
+        mats.add(Conductor::preset(name, *roughness_u, *roughness_v, mats.next_idx()).unwrap())
+    }).collect();
+    let list = material_row(&mut mats, &row);
+
+    Scene::new(
+        HittableList::new(list),
+        mats.list,
+        material_row_view(), aspect,
+        Background::Sky)
+}
+
+// clear, frosted and tinted glass: the last two absorb like a green
+// liquid and amber glass
+pub fn rough_glass(aspect: f32) -> Scene {
+    let mut mats = MaterialTable::new();
+    let row = [
+        mats.add(RoughDielectric::new(1.5, 0., mats.next_idx())),
+        mats.add(RoughDielectric::new(1.5, 0.2, mats.next_idx())),
+        mats.add(RoughDielectric::new(1.5, 0.5, mats.next_idx())),
+        mats.add(RoughDielectric::new(1.33, 0., mats.next_idx())
+                 .with_absorption(Color::new(0.3, 0.8, 0.4), 1.)),
+        mats.add(RoughDielectric::new(1.5, 0.3, mats.next_idx())
+                 .with_absorption(Color::new(0.9, 0.5, 0.1), 2.))];
+    let list = material_row(&mut mats, &row);
+
+    Scene::new(
+        HittableList::new(list),
+        mats.list,
+        material_row_view(), aspect,
+        Background::Sky)
+}
+
+// principled materials: glossy plastic, metal with a checkered
+// roughness, clearcoated paint, cloth-like sheen over marble and
+// tinted transmission
+pub fn principled(aspect: f32) -> Scene {
+    let mut mats = MaterialTable::new();
+    let checker: Rc<dyn Texture> = Rc::new(CheckerTexture::new(
+        Box::new(ConstantTexture::new(Color::new(0.05, 0.05, 0.05))),
+        Box::new(ConstantTexture::new(Color::new(0.5, 0.5, 0.5))),
+        10.));
+    let marble: Rc<dyn Texture> = Rc::new(NoiseTexture::new(4.));
+    let row = [
+        mats.add(Principled::new(Color::new(0.8, 0.1, 0.1), mats.next_idx())
+                 .with_roughness(0.3)),
+        mats.add(Principled::new(Color::new(0.95, 0.75, 0.4), mats.next_idx())
+                 .with_metallic(1.)
+                 .with_roughness(checker)),
+        mats.add(Principled::new(Color::new(0.05, 0.15, 0.6), mats.next_idx())
+                 .with_metallic(0.5)
+                 .with_roughness(0.5)
+                 .with_clearcoat(1., 0.05)),
+        mats.add(Principled::new(marble, mats.next_idx())
+                 .with_roughness(1.)
+                 .with_sheen(1.)),
+        mats.add(Principled::new(Color::new(0.7, 0.9, 0.8), mats.next_idx())
+                 .with_roughness(0.1)
+                 .with_transmission(1., 1.5))];
+    let list = material_row(&mut mats, &row);
+
+    Scene::new(
+        HittableList::new(list),
+        mats.list,
+        material_row_view(), aspect,
+        Background::Sky)
+}
+
+// blended and layered materials: 30% gold over 70% varnished plastic,
+// copper and white chalk checkered together, glossy paint, lacquered
+// copper and satin varnished marble
+pub fn layered(aspect: f32) -> Scene {
+    let mut mats = MaterialTable::new();
+    let checker: Rc<dyn Texture> = Rc::new(CheckerTexture::new(
+        Box::new(ConstantTexture::new(Color::new(0., 0., 0.))),
+        Box::new(ConstantTexture::new(Color::new(1., 1., 1.))),
+        10.));
+    let marble: Rc<dyn Texture> = Rc::new(NoiseTexture::new(4.));
+    let gold = |idx| Conductor::preset("gold", 0.25, 0.25, idx).unwrap();
+    let copper = |roughness, idx| Conductor::preset("copper", roughness, roughness, idx).unwrap();
+    let row = [
+        mats.add(Mix::new(gold(0),
+                          Coated::new(Lambertian::new(Color::new(0.7, 0.1, 0.1), 0), 1.5, 0.1, 0),
+                          0.7, mats.next_idx())),
+        mats.add(Mix::new(copper(0.2, 0), Lambertian::new(Color::new(0.9, 0.9, 0.9), 0),
+                          checker, mats.next_idx())),
+        mats.add(Coated::new(Lambertian::new(Color::new(0.05, 0.15, 0.6), 0), 1.5, 0., mats.next_idx())),
+        mats.add(Coated::new(copper(0.5, 0), 1.5, 0.05, mats.next_idx())
+                 .with_tint(Color::new(0.9, 0.6, 0.3))),
+        mats.add(Coated::new(Lambertian::textured(marble, 0), 1.5, 0.3, mats.next_idx()))];
+    let list = material_row(&mut mats, &row);
+
+    Scene::new(
+        HittableList::new(list),
+        mats.list,
+        material_row_view(), aspect,
+        Background::Sky)
+}
+
+// a dense flint glass ball and a diamond on a checkerboard, rendered
+// spectrally so the edges seen through them split into colors
+pub fn dispersion(aspect: f32) -> Scene {
+    let mut mats = MaterialTable::new();
+    // hard edges in the ground show the fringes seen through the glass
+    let ground = mats.add(Lambertian::textured(Rc::new(CheckerTexture::new(
+        Box::new(ConstantTexture::new(Color::new(0.05, 0.05, 0.05))),
+        Box::new(ConstantTexture::new(Color::new(0.8, 0.8, 0.8))),
+        10.)), mats.next_idx()));
+    // Sellmeier coefficients of Schott SF11 and of diamond
+    let flint = mats.add(Dielectric::from_dispersion(Dispersion::Sellmeier {
+        b: [1.737_597, 0.313_747_3, 1.898_781],
+        c: [0.013_188_707, 0.062_306_81, 155.236_3]
+    }, mats.next_idx()));
+    let diamond = mats.add(Dielectric::from_dispersion(Dispersion::Sellmeier {
+        b: [0.3306, 4.3356, 0.],
+        c: [0.030_625, 0.011_236, 0.]
+    }, mats.next_idx()));
+    let list: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere::new(Point::new(0.,-1000.,0.), 1000., ground)),
+        Box::new(Sphere::new(Point::new(-1.2,1.,0.), 1., flint)),
+        Box::new(Sphere::new(Point::new(1.2,1.,0.), 1., diamond))];
+
+    let view = View {
+        lookfrom: Point::new(0.,4.,9.),
+        lookat: Point::new(0.,0.7,0.),
+        vup: Vec3::new(0.,1.,0.),
+        vfov: 30.,
+        aperature: 0.,
+        focus_dist: 9.
+    };
+
+    let mut scene = Scene::new(
+        HittableList::new(list),
+        mats.list,
+        view, aspect,
+        Background::Sky);
+    scene.spectral = true;
+    scene
+}
+
+// translucent skin, wax, marble and jade spheres and a block of milky
+// soap, lit from behind and to the left so light shows through them
+pub fn subsurface(aspect: f32) -> Scene {
+    let mut mats = MaterialTable::new();
+    let marble: Rc<dyn Texture> = Rc::new(NoiseTexture::new(4.));
+    let row = [
+        mats.add(Subsurface::new(Color::new(0.85, 0.55, 0.45), Color::new(0.4, 0.15, 0.08),
+                                 mats.next_idx())),
+        mats.add(Subsurface::new(Color::new(0.9, 0.75, 0.5), Color::new(0.5, 0.4, 0.25),
+                                 mats.next_idx())),
+        mats.add(Subsurface::new(marble, Color::new(0.2, 0.2, 0.2), mats.next_idx())),
+        mats.add(Subsurface::new(Color::new(0.3, 0.75, 0.45), Color::new(0.6, 1.2, 0.8),
+                                 mats.next_idx()))];
+    let soap = mats.add(Subsurface::new(Color::new(0.9, 0.9, 0.95), Color::new(0.3, 0.3, 0.4),
+                                        mats.next_idx()));
+    let mut list = material_row(&mut mats, &row);
+    // any closed object works, not just spheres
+    list.push(Box::new(Translate::new(
+        Box::new(RotateY::new(
+            Box::new(Cuboid::new(Point::new(-0.75,0.,-0.75), Point::new(0.75,1.5,0.75), soap)),
+            30.)),
+        Vec3::new(4.4,0.,0.))));
+
+    let mut scene = Scene::new(
+        HittableList::new(list),
+        mats.list,
+        material_row_view(), aspect,
+        Background::Sky);
+    scene.lights.push(Box::new(DirectionalLight::new(Vec3::new(1.,-0.6,1.), Color::new(2.,2.,2.))));
+    scene
+}
+
+// A tangent space normal map of round dents in a cols x rows grid,
+// like hammered metal
+fn dent_normals(width: usize, height: usize, cols: usize, rows: usize) -> FloatImage {
+    (0..height).map(|y| (0..width).map(|x| {
+        // position in the dent's cell, from -1 to 1
+        let cell = |i: usize, size: usize, count: usize| {
+            let t = (i as f32 + 0.5) / size as f32 * count as f32;
+            2. * (t - t.floor()) - 1.
+        };
+        let (dx, dy) = (cell(x, width, cols), -cell(y, height, rows));
+        let n = if dx * dx + dy * dy < 0.8 {
+            Vec3::unit_vector(Vec3::new(-0.6 * dx, -0.6 * dy, 1.))
+        } else {
+            Vec3::new(0., 0., 1.)
+        };
+        0.5 * (n + Vec3::new(1., 1., 1.))
+    }).collect()).collect()
+}
+
+// A height map of raised square tiles in a cols x rows grid with
+// sunken grout between them
+fn tile_heights(width: usize, height: usize, cols: usize, rows: usize) -> FloatImage {
+    (0..height).map(|y| (0..width).map(|x| {
+        // distance to the nearest edge of the tile, 0.5 at its center
+        let edge = |i: usize, size: usize, count: usize| {
+            let t = (i as f32 + 0.5) / size as f32 * count as f32;
+            (t - t.floor()).min(1. + t.floor() - t)
+        };
+        let d = edge(x, width, cols).min(edge(y, height, rows));
+        let t = ((d - 0.03) / 0.05).clamp(0., 1.);
+        let h = t * t * (3. - 2. * t);
+        Color::new(h, h, h)
+    }).collect()).collect()
+}
+
+// normal and bump mapped spheres: bumpy marble, hammered gold, tiled
+// plastic, rippled glass and dented lacquer
+pub fn bumps(aspect: f32) -> Scene {
+    let mut mats = MaterialTable::new();
+    let noise: Rc<dyn Texture> = Rc::new(NoiseTexture::new(4.));
+    let dents: Rc<dyn Texture> = Rc::new(ImageTexture::new(dent_normals(512, 256, 24, 12)));
+    let tiles: Rc<dyn Texture> = Rc::new(ImageTexture::new(tile_heights(512, 256, 16, 8)));
+    let row = [
+        mats.add(Bumped::height(Lambertian::textured(noise.clone(), 0), noise.clone(), 0.05,
+                                mats.next_idx())),
+        mats.add(Bumped::normal_map(Conductor::preset("gold", 0.2, 0.2, 0).unwrap(), dents.clone(),
+                                    mats.next_idx())),
+        mats.add(Bumped::height(Principled::new(Color::new(0.8, 0.3, 0.1), 0).with_roughness(0.3),
+                                tiles, 0.01, mats.next_idx())),
+        mats.add(Bumped::height(Dielectric::new(1.5, 0), noise, 0.02, mats.next_idx())),
+        mats.add(Bumped::normal_map(Coated::new(Lambertian::new(Color::new(0.05, 0.15, 0.6), 0),
+                                                1.5, 0., 0),
+                                    dents, mats.next_idx()))];
+    let list = material_row(&mut mats, &row);
+
+    Scene::new(
+        HittableList::new(list),
+        mats.list,
+        material_row_view(), aspect,
+        Background::Sky)
+}
+
+// An alpha mask of a diagonal lattice with a frame around it
+fn lattice_mask(size: usize, bars: f32) -> FloatImage {
+    (0..size).map(|y| (0..size).map(|x| {
+        let (u, v) = ((x as f32 + 0.5) / size as f32, (y as f32 + 0.5) / size as f32);
+        let on_bar = |t: f32| (t - t.floor() - 0.5).abs() > 0.38;
+        let frame = u.min(v).min(1. - u).min(1. - v) < 0.03;
+        let a = if frame || on_bar((u + v) * bars) || on_bar((u - v) * bars) { 1. } else { 0. };
+        Color::new(a, a, a)
+    }).collect()).collect()
+}
+
+// An alpha mask of a cols x rows grid of leaves, each turned a little
+// differently
+fn leaf_mask(size: usize, cols: usize, rows: usize) -> FloatImage {
+    (0..size).map(|y| (0..size).map(|x| {
+        let tx = (x as f32 + 0.5) / size as f32 * cols as f32;
+        let ty = (y as f32 + 0.5) / size as f32 * rows as f32;
+        let (dx, dy) = (2. * (tx - tx.floor()) - 1., 2. * (ty - ty.floor()) - 1.);
+        let angle = 0.7 * (tx.floor() * 1.3 + ty.floor() * 2.1).sin();
+        let (c, s) = (angle.cos(), angle.sin());
+        let (a, b) = (c * dx + s * dy, -s * dx + c * dy);
+        let alpha = if a * a / 0.81 + b * b / 0.16 < 1. { 1. } else { 0. };
+        Color::new(alpha, alpha, alpha)
+    }).collect()).collect()
+}
+
+// a lattice fence and a curtain of leaves cut out of flat rectangles by
+// alpha masks, and a half transparent sheet, casting shadows through
+// their holes
+pub fn cutouts(aspect: f32) -> Scene {
+    let mut mats = MaterialTable::new();
+    let ground = mats.add(Lambertian::textured(Rc::new(CheckerTexture::new(
+        Box::new(ConstantTexture::new(Color::new(0.2, 0.3, 0.1))),
+        Box::new(ConstantTexture::new(Color::new(0.8, 0.8, 0.8))),
+        10.)), mats.next_idx()));
+    let lattice: Rc<dyn Texture> = Rc::new(ImageTexture::new(lattice_mask(512, 6.)));
+    let leaves: Rc<dyn Texture> = Rc::new(ImageTexture::new(leaf_mask(512, 6, 6)));
+    let fence = mats.add(Cutout::new(Lambertian::new(Color::new(0.45, 0.3, 0.15), 0), lattice,
+                                     mats.next_idx()));
+    let foliage = mats.add(Cutout::new(Lambertian::new(Color::new(0.15, 0.45, 0.1), 0), leaves,
+                                       mats.next_idx()));
+    let sheet = mats.add(Cutout::new(Lambertian::new(Color::new(0.9, 0.2, 0.2), 0), 0.4,
+                                     mats.next_idx()));
+    let red = mats.add(Lambertian::new(Color::new(0.7, 0.2, 0.1), mats.next_idx()));
+    let blue = mats.add(Lambertian::new(Color::new(0.1, 0.2, 0.7), mats.next_idx()));
+
+    let list: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere::new(Point::new(0.,-1000.,0.), 1000., ground)),
+        Box::new(XYRect::new(-3.5, 0.5, 0., 2.5, 0., fence)),
+        Box::new(Translate::new(
+            Box::new(RotateY::new(Box::new(XYRect::new(-1., 1., 0., 2., 0., foliage)), -30.)),
+            Vec3::new(2.2, 0., 0.5))),
+        Box::new(XYRect::new(-0.5, 1.5, 0., 1.5, 2., sheet)),
+        Box::new(Sphere::new(Point::new(-1.5,0.8,-1.5), 0.8, red)),
+        Box::new(Sphere::new(Point::new(2.5,0.8,-1.5), 0.8, blue))];
+
+    let view = View {
+        lookfrom: Point::new(1.,2.5,9.),
+        lookat: Point::new(0.5,1.,0.),
+        vup: Vec3::new(0.,1.,0.),
+        vfov: 35.,
+        aperature: 0.,
+        focus_dist: 9.
+    };
+
+    let mut scene = Scene::new(
+        HittableList::new(list),
+        mats.list,
+        view, aspect,
+        Background::Sky);
+    scene.lights.push(Box::new(DirectionalLight::new(Vec3::new(-0.5,-1.,-0.8), Color::new(2.,2.,2.))));
+    scene
+}
+
+// ray marched distance fields: a gold Mandelbulb, a box and ball
+// blended together with a ring carved out, a glass rounded box
+// with a hole through it, and a row of balls from domain repetition
+pub fn sdf(aspect: f32) -> Scene {
+    let mut mats = MaterialTable::new();
+    let ground = mats.add(Lambertian::textured(Rc::new(CheckerTexture::new(
+        Box::new(ConstantTexture::new(Color::new(0.2, 0.2, 0.2))),
+        Box::new(ConstantTexture::new(Color::new(0.8, 0.8, 0.8))),
+        10.)), mats.next_idx()));
+    let gold = mats.add(Conductor::preset("gold", 0.3, 0.3, mats.next_idx()).unwrap());
+    let red = mats.add(Principled::new(Color::new(0.8, 0.15, 0.1), mats.next_idx()).with_roughness(0.3));
+    let glass = mats.add(Dielectric::new(1.5, mats.next_idx()));
+    let white = mats.add(Lambertian::new(Color::new(0.8, 0.8, 0.8), mats.next_idx()));
+
+    let bulb = Sdf::mandelbulb(8., 8).translate(Vec3::new(0., 1.1, 0.));
+    let blob = Sdf::sphere(0.75)
+        .smooth_union(Sdf::cuboid(Vec3::new(0.55, 0.55, 0.55)).translate(Vec3::new(0., -0.35, 0.)), 0.3)
+        .smooth_subtract(Sdf::torus(0.75, 0.15), 0.1)
+        .translate(Vec3::new(-2.6, 0.9, 0.));
+    let holed = Sdf::rounded_cuboid(Vec3::new(0.7, 0.7, 0.7), 0.2)
+        .subtract(Sdf::capsule(Point::new(0., 0., -1.), Point::new(0., 0., 1.), 0.35))
+        .translate(Vec3::new(2.6, 0.7, 0.));
+    let balls = Sdf::sphere(0.2)
+        .repeat(Vec3::new(0.6, 0., 0.), [5, 0, 0])
+        .translate(Vec3::new(0., 0.2, 2.));
+
+    let list: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere::new(Point::new(0.,-1000.,0.), 1000., ground)),
+        Box::new(SdfObject::new(bulb, gold)),
+        Box::new(SdfObject::new(blob, red)),
+        Box::new(SdfObject::new(holed, glass)),
+        Box::new(SdfObject::new(balls, white))];
+
+    let view = View {
+        lookfrom: Point::new(0.,3.5,9.),
+        lookat: Point::new(0.,0.9,0.),
+        vup: Vec3::new(0.,1.,0.),
+        vfov: 35.,
+        aperature: 0.,
+        focus_dist: 9.
+    };
+
+    Scene::new(
+        HittableList::new(list),
+        mats.list,
+        view, aspect,
+        Background::Sky)
+}
+
+// constructive solid geometry: a ball with a corner cut out, the
+// classic cube intersected with a ball and drilled through along each
+// axis, and a glass union of two balls without the walls inside it
+pub fn csg(aspect: f32) -> Scene {
+    let mut mats = MaterialTable::new();
+    let ground = mats.add(Lambertian::textured(Rc::new(CheckerTexture::new(
+        Box::new(ConstantTexture::new(Color::new(0.2, 0.2, 0.2))),
+        Box::new(ConstantTexture::new(Color::new(0.8, 0.8, 0.8))),
+        10.)), mats.next_idx()));
+    let red = mats.add(Principled::new(Color::new(0.8, 0.15, 0.1), mats.next_idx()).with_roughness(0.3));
+    let cream = mats.add(Lambertian::new(Color::new(0.9, 0.85, 0.7), mats.next_idx()));
+    let blue = mats.add(Principled::new(Color::new(0.1, 0.2, 0.7), mats.next_idx()).with_roughness(0.2));
+    let yellow = mats.add(Lambertian::new(Color::new(0.9, 0.7, 0.1), mats.next_idx()));
+    let glass = mats.add(Dielectric::new(1.5, mats.next_idx()));
+
+    let cut_ball = Csg::difference(
+        Closed::new(Sphere::new(Point::new(-3.,1.,0.), 1., red)),
+        Closed::new(Cuboid::new(Point::new(-3.,1.,0.), Point::new(-1.8,2.2,1.2), cream)));
+
+    let drill = |from: Point, to: Point| {
+        Closed::new(SdfObject::new(Sdf::capsule(from, to, 0.35), yellow))
+    };
+    let holes = Csg::union(
+        Csg::union(drill(Point::new(-2.,0.,0.), Point::new(2.,0.,0.)),
+                   drill(Point::new(0.,-2.,0.), Point::new(0.,2.,0.))),
+        drill(Point::new(0.,0.,-2.), Point::new(0.,0.,2.)));
+    let rounded_cube = Csg::intersection(
+        Closed::new(Cuboid::new(Point::new(-0.8,-0.8,-0.8), Point::new(0.8,0.8,0.8), blue)),
+        Closed::new(Sphere::new(Point::new(0.,0.,0.), 1.05, blue)));
+    let drilled = Translate::new(
+        Box::new(RotateY::new(Box::new(Csg::difference(rounded_cube, holes)), 30.)),
+        Vec3::new(0.,0.8,0.));
+
+    let lens = Csg::union(
+        Closed::new(Sphere::new(Point::new(2.6,0.9,0.), 0.9, glass)),
+        Closed::new(Sphere::new(Point::new(3.4,0.9,0.), 0.9, glass)));
+
+    let list: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere::new(Point::new(0.,-1000.,0.), 1000., ground)),
+        Box::new(cut_ball),
+        Box::new(drilled),
+        Box::new(lens)];
+
+    let view = View {
+        lookfrom: Point::new(1.,4.,9.),
+        lookat: Point::new(0.,0.8,0.),
+        vup: Vec3::new(0.,1.,0.),
+        vfov: 35.,
+        aperature: 0.,
+        focus_dist: 9.
+    };
+
+    Scene::new(
+        HittableList::new(list),
+        mats.list,
+        view, aspect,
+        Background::Sky)
+}
+
+// a cols x rows checkerboard in surface coordinates, for showing how
+// a shape's uv runs over it
+fn uv_checker(size: usize, cols: usize, rows: usize, odd: Color, even: Color) -> FloatImage {
+    (0..size).map(|y| (0..size).map(|x| {
+        let cell = x * cols / size + y * rows / size;
+        if cell.is_multiple_of(2) { even } else { odd }
+    }).collect()).collect()
+}
+
+// The quadrics with a uv checkerboard: a capped cylinder, a cone, an
+// open paraboloid bowl, a hyperboloid and a torus in front, and partial
+// sweeps of a cylinder, an annulus and a torus behind
+pub fn quadrics(aspect: f32) -> Scene {
+    let mut mats = MaterialTable::new();
+    let ground = mats.add(Lambertian::new(Color::new(0.5, 0.5, 0.5), mats.next_idx()));
+    let checker = |odd: Color| -> Rc<dyn Texture> {
+        Rc::new(ImageTexture::new(uv_checker(256, 16, 8, odd, Color::new(0.9, 0.9, 0.85))))
+    };
+    let red = mats.add(Lambertian::textured(checker(Color::new(0.7, 0.1, 0.1)), mats.next_idx()));
+    let blue = mats.add(Lambertian::textured(checker(Color::new(0.1, 0.2, 0.7)), mats.next_idx()));
+    let green = mats.add(Lambertian::textured(checker(Color::new(0.1, 0.5, 0.2)), mats.next_idx()));
+    let gold = mats.add(Conductor::preset("gold", 0.15, 0.15, mats.next_idx()).unwrap());
+
+    let at = |obj: Box<dyn Hittable>, x: f32, y: f32, z: f32| -> Box<dyn Hittable> {
+        Box::new(Translate::new(obj, Vec3::new(x, y, z)))
+    };
+    let list: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere::new(Point::new(0.,-1000.,0.), 1000., ground)),
+        at(Box::new(Quadric::cylinder(0.7, 0., 1.6, red).capped()), -4.4, 0., 1.5),
+        at(Box::new(Quadric::cone(0.8, 1.8, blue).capped()), -2.2, 0., 1.5),
+        at(Box::new(Quadric::paraboloid(0.9, 0., 1.4, green)), 0., 0.2, 1.5),
+        at(Box::new(Quadric::hyperboloid(0.4, 0.8, -0.8, 0.8, red).capped()), 2.2, 0.8, 1.5),
+        at(Box::new(Torus::new(0.7, 0.25, gold)), 4.4, 0.25, 1.5),
+        at(Box::new(RotateY::new(Box::new(
+            Quadric::cylinder(0.8, 0., 1.6, blue).with_phi_max(270.)), 45.)), -3.3, 0., -2.5),
+        at(Box::new(Disk::annulus(0., 0.4, 0.9, green).with_phi_max(300.)), -1.1, 0.01, -2.5),
+        at(Box::new(RotateY::new(Box::new(
+            Torus::new(0.7, 0.3, red).with_phi_max(270.)), 45.)), 3.3, 0.3, -3.2)];
+
+    let view = View {
+        lookfrom: Point::new(0.,7.,11.),
+        lookat: Point::new(0.,0.4,-0.5),
+        vup: Vec3::new(0.,1.,0.),
+        vfov: 40.,
+        aperature: 0.,
+        focus_dist: 11.
+    };
+
+    Scene::new(
+        HittableList::new(list),
+        mats.list,
+        view, aspect,
+        Background::Sky)
+}
+
+// Elevations in [0, 1] of ridged mountains, sharp crests made of
+// folded octaves of noise
+fn ridged_elevations(size: usize, octaves: u32) -> Vec<Vec<f32>> {
+    let noise = Perlin::new();
+    let raw: Vec<Vec<f32>> = (0..size).map(|j| (0..size).map(|i| {
+        let p = Point::new(i as f32, 0.5, j as f32) * (3. / size as f32);
+        let (mut sum, mut amplitude) = (0., 1.);
+        for octave in 0..octaves {
+            let ridge = 1. - noise.noise(&(p * (1 << octave) as f32)).abs();
+            sum += amplitude * ridge * ridge;
+            amplitude *= 0.45;
+        }
+        sum
+    }).collect()).collect();
+    let low = raw.iter().flatten().cloned().fold(f32::INFINITY, f32::min);
+    let high = raw.iter().flatten().cloned().fold(f32::NEG_INFINITY, f32::max);
+    // stretched to [0, 1] with the valleys flattened
+    raw.iter().map(|row| row.iter().map(|e| ((e - low) / (high - low)).powf(3.)).collect()).collect()
+}
+
+// Colors for terrain, by elevation and steepness: grass low down, rock
+// on slopes and high up, and snow on the flatter peaks.  relief: rise
+// over run of a difference of 1 between neighbouring elevations.
+fn terrain_colors(elevations: &[Vec<f32>], relief: f32) -> FloatImage {
+    let size = elevations.len();
+    let (grass, rock, snow) = (Color::new(0.15, 0.3, 0.08), Color::new(0.3, 0.27, 0.24), Color::new(0.9, 0.9, 0.92));
+    (0..size).map(|j| (0..size).map(|i| {
+        let h = elevations[j][i];
+        let dx = elevations[j][(i + 1).min(size - 1)] - elevations[j][i.saturating_sub(1)];
+        let dz = elevations[(j + 1).min(size - 1)][i] - elevations[j.saturating_sub(1)][i];
+        let steep = 0.5 * (dx * dx + dz * dz).sqrt() * relief;
+        let mix = |a: Color, b: Color, t: f32| (1. - t.clamp(0., 1.)) * a + t.clamp(0., 1.) * b;
+        let ground = mix(grass, rock, (h - 0.25) * 6. + (steep - 0.6) * 3.);
+        mix(ground, snow, (h - 0.55) * 8. - (steep - 0.5) * 3.)
+    }).collect()).collect()
+}
+
+// a mountain range as a 512 x 512 heightfield, colored by height and
+// slope, with a lake filling the valleys
+pub fn terrain(aspect: f32) -> Scene {
+    let mut mats = MaterialTable::new();
+    let size = Vec3::new(30., 5., 30.);
+    let elevations = ridged_elevations(512, 6);
+    let relief = size.y() * 511. / size.x();
+    let ground = mats.add(Lambertian::textured(
+        Rc::new(ImageTexture::new(terrain_colors(&elevations, relief))), mats.next_idx()));
+    let water = mats.add(Metal::new(Color::new(0.2, 0.35, 0.45), 0.02, mats.next_idx()));
+
+    let list: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Translate::new(
+            Box::new(Heightfield::new(&elevations, size, ground)),
+            Vec3::new(-15., 0., -15.))),
+        Box::new(XZRect::new(-15., 15., -15., 15., 0.15, water))];
+
+    let view = View {
+        lookfrom: Point::new(0.,9.,19.),
+        lookat: Point::new(0.,1.,-3.),
+        vup: Vec3::new(0.,1.,0.),
+        vfov: 50.,
+        aperature: 0.,
+        focus_dist: 19.
+    };
+
+    let mut scene = Scene::new(
+        HittableList::new(list),
+        mats.list,
+        view, aspect,
+        Background::Sky);
+    scene.lights.push(Box::new(DirectionalLight::new(Vec3::new(-1.,-0.8,-0.4), Color::new(2.5,2.3,2.))));
+    scene
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_catalogued_scene_builds() {
+        for name in SCENE_NAMES {
+            let scene = by_name(name, 1.5).unwrap_or_else(|| panic!("no scene '{}'", name));
+            for (i, mat) in scene.materials.iter().enumerate() {
+                assert_eq!(mat.get_idx() as usize, i, "{}: material {} out of place", name, i);
+            }
+            assert!(scene.camera.get_ray(0.5, 0.5, (0.5, 0.5)).is_some());
+        }
+        assert!(by_name("teapot", 1.5).is_none());
+    }
+}
+
+}
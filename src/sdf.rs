@@ -0,0 +1,331 @@
+pub mod module {
+
+use crate::types::module::*;
+use crate::vec3::module::*;
+use crate::ray::module::*;
+use crate::hittable::module::*;
+
+use std::rc::Rc;
+
+// steps sphere tracing takes before giving up on a ray
+const MAX_STEPS: u32 = 512;
+// how close to the surface counts as a hit
+const HIT_EPSILON: f32 = 1e-4;
+// offset normals are differentiated over
+const NORMAL_DELTA: f32 = 1e-4;
+
+// A shape given by its signed distance function, negative inside,
+// along with a box it fits in.  The distance may underestimate but
+// should never overestimate, or rays can step through the surface.
+// Shapes combine with the methods below into new shapes.
+#[derive(Clone)]
+pub struct Sdf {
+    distance: Rc<dyn Fn(Point) -> f32>,
+    bounds: Aabb
+}
+
+impl Sdf {
+    // distance: any signed distance function, bounds: where it's negative
+    pub fn new<F: Fn(Point) -> f32 + 'static>(distance: F, bounds: Aabb) -> Sdf {
+        Sdf { distance: Rc::new(distance), bounds }
+    }
+
+    pub fn distance(&self, p: Point) -> f32 {
+        (self.distance)(p)
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    // distance functions of the shapes are from Inigo Quilez's articles
+
+    pub fn sphere(radius: f32) -> Sdf {
+        let r = Vec3::new(radius, radius, radius);
+        Sdf::new(move |p| p.length() - radius, Aabb::new(-r, r))
+    }
+
+    // half_size: from the center to a corner
+    pub fn cuboid(half_size: Vec3) -> Sdf {
+        Sdf::rounded_cuboid(half_size, 0.)
+    }
+
+    // a cuboid with its edges rounded off by radius, keeping its size
+    pub fn rounded_cuboid(half_size: Vec3, radius: f32) -> Sdf {
+        let inner = half_size - Vec3::new(radius, radius, radius);
+        Sdf::new(move |p| {
+            let q = Vec3::new(p.x().abs() - inner.x(), p.y().abs() - inner.y(), p.z().abs() - inner.z());
+            let outside = Vec3::new(q.x().max(0.), q.y().max(0.), q.z().max(0.)).length();
+            outside + q.x().max(q.y()).max(q.z()).min(0.) - radius
+        }, Aabb::new(-half_size, half_size))
+    }
+
+    // a ring around the y axis.  major: radius of the ring, minor: of
+    // its tube
+    pub fn torus(major: f32, minor: f32) -> Sdf {
+        let extent = Vec3::new(major + minor, minor, major + minor);
+        Sdf::new(move |p| {
+            let ring = (p.x() * p.x() + p.z() * p.z()).sqrt() - major;
+            (ring * ring + p.y() * p.y()).sqrt() - minor
+        }, Aabb::new(-extent, extent))
+    }
+
+    // a line segment from a to b thickened by radius
+    pub fn capsule(a: Point, b: Point, radius: f32) -> Sdf {
+        let bounds = Aabb::new(a, b).pad(radius);
+        Sdf::new(move |p| {
+            let (pa, ba) = (p - a, b - a);
+            let h = (Vec3::dot(&pa, &ba) / Vec3::dot(&ba, &ba)).clamp(0., 1.);
+            (pa - h * ba).length() - radius
+        }, bounds)
+    }
+
+    // a cylinder along the y axis, height: from end to end
+    pub fn cylinder(radius: f32, height: f32) -> Sdf {
+        let extent = Vec3::new(radius, 0.5 * height, radius);
+        Sdf::new(move |p| {
+            let dx = (p.x() * p.x() + p.z() * p.z()).sqrt() - radius;
+            let dy = p.y().abs() - 0.5 * height;
+            dx.max(dy).min(0.) + (dx.max(0.) * dx.max(0.) + dy.max(0.) * dy.max(0.)).sqrt()
+        }, Aabb::new(-extent, extent))
+    }
+
+    // The Mandelbulb fractal (White and Nylander), at about unit size
+    // with its axis along y.  power: 8 gives the classic shape,
+    // iterations: more gives finer detail.  The distance is the usual
+    // estimate from the derivative of the iteration.
+    pub fn mandelbulb(power: f32, iterations: u32) -> Sdf {
+        let extent = Vec3::new(1.2, 1.2, 1.2);
+        Sdf::new(move |p| {
+            let p = Vec3::new(p.x(), p.z(), p.y());
+            let mut z = p;
+            let mut dr = 1.;
+            let mut r = z.length();
+            for _ in 0..iterations {
+                if r > 2. {
+                    break;
+                }
+                // z^power + p in spherical coordinates
+                let theta = (z.z() / r).clamp(-1., 1.).acos() * power;
+                let phi = z.y().atan2(z.x()) * power;
+                dr = r.powf(power - 1.) * power * dr + 1.;
+                z = r.powf(power) * Vec3::new(theta.sin() * phi.cos(),
+                                              theta.sin() * phi.sin(),
+                                              theta.cos()) + p;
+                r = z.length();
+            }
+            if r == 0. {
+                return 0.;
+            }
+            0.5 * r.ln() * r / dr
+        }, Aabb::new(-extent, extent))
+    }
+
+    pub fn translate(self, offset: Vec3) -> Sdf {
+        let bounds = Aabb { min: self.bounds.min + offset, max: self.bounds.max + offset };
+        Sdf::new(move |p| self.distance(p - offset), bounds)
+    }
+
+    // factor: must be positive, as a mirrored or flattened distance
+    // no longer bounds the distance to the surface
+    pub fn scale(self, factor: f32) -> Sdf {
+        assert!(factor > 0., "Sdf::scale by {}, which isn't positive", factor);
+        let bounds = Aabb::new(self.bounds.min * factor, self.bounds.max * factor);
+        Sdf::new(move |p| self.distance(p / factor) * factor, bounds)
+    }
+
+    pub fn union(self, other: Sdf) -> Sdf {
+        let bounds = self.bounds.union(&other.bounds);
+        Sdf::new(move |p| self.distance(p).min(other.distance(p)), bounds)
+    }
+
+    pub fn intersect(self, other: Sdf) -> Sdf {
+        let bounds = self.bounds.intersection(&other.bounds);
+        Sdf::new(move |p| self.distance(p).max(other.distance(p)), bounds)
+    }
+
+    // self with other carved out of it
+    pub fn subtract(self, other: Sdf) -> Sdf {
+        let bounds = self.bounds;
+        Sdf::new(move |p| self.distance(p).max(-other.distance(p)), bounds)
+    }
+
+    // Unions blending into each other over about k, which can swell the
+    // shape by up to k / 4 where they meet
+    pub fn smooth_union(self, other: Sdf, k: f32) -> Sdf {
+        let bounds = self.bounds.union(&other.bounds).pad(0.25 * k);
+        Sdf::new(move |p| smooth_min(self.distance(p), other.distance(p), k), bounds)
+    }
+
+    pub fn smooth_intersect(self, other: Sdf, k: f32) -> Sdf {
+        let bounds = self.bounds.intersection(&other.bounds);
+        Sdf::new(move |p| -smooth_min(-self.distance(p), -other.distance(p), k), bounds)
+    }
+
+    pub fn smooth_subtract(self, other: Sdf, k: f32) -> Sdf {
+        let bounds = self.bounds;
+        Sdf::new(move |p| -smooth_min(-self.distance(p), other.distance(p), k), bounds)
+    }
+
+    // Copies of the shape every spacing along each axis, count copies
+    // out to either side of the original (so 2 * count + 1 along the
+    // axis).  The shape should fit within its cell.
+    pub fn repeat(self, spacing: Vec3, count: [u32; 3]) -> Sdf {
+        let reach = Vec3::new(spacing.x() * count[0] as f32,
+                              spacing.y() * count[1] as f32,
+                              spacing.z() * count[2] as f32);
+        let bounds = Aabb { min: self.bounds.min - reach, max: self.bounds.max + reach };
+        Sdf::new(move |p| {
+            // move p into the nearest copy's cell
+            let cell = |v: f32, s: f32, n: u32| {
+                if s == 0. { 0. } else { s * (v / s).round().clamp(-(n as f32), n as f32) }
+            };
+            let offset = Vec3::new(cell(p.x(), spacing.x(), count[0]),
+                                   cell(p.y(), spacing.y(), count[1]),
+                                   cell(p.z(), spacing.z(), count[2]));
+            self.distance(p - offset)
+        }, bounds)
+    }
+}
+
+// polynomial smooth minimum, k: how far apart a and b blend over
+fn smooth_min(a: f32, b: f32, k: f32) -> f32 {
+    if k <= 0. {
+        return a.min(b);
+    }
+    let h = (k - (a - b).abs()).max(0.) / k;
+    a.min(b) - h * h * k * 0.25
+}
+
+// An Sdf traced as an object by sphere tracing (Hart 1996): rays step
+// forward by the distance to the surface, which can't overshoot it,
+// until they're within HIT_EPSILON.  Rays starting inside, like those
+// refracted into glass, trace to where they leave.
+pub struct SdfObject {
+    sdf: Sdf,
+    material: u32
+}
+
+impl SdfObject {
+    pub fn new(sdf: Sdf, material: u32) -> SdfObject {
+        SdfObject { sdf, material }
+    }
+
+    // the gradient of the distance by central differences
+    fn normal(&self, p: Point) -> Vec3 {
+        let d = |offset: Vec3| self.sdf.distance(p + offset) - self.sdf.distance(p - offset);
+        let h = NORMAL_DELTA;
+        let gradient = Vec3::new(d(Vec3::new(h, 0., 0.)), d(Vec3::new(0., h, 0.)), d(Vec3::new(0., 0., h)));
+        let len = gradient.length();
+        if len > 0. { gradient / len } else { Vec3::new(0., 1., 0.) }
+    }
+}
+
+impl Hittable for SdfObject {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        let (start, end) = match self.sdf.bounds.hit(r, t_min, t_max) {
+            Some(span) => span,
+            None => return false
+        };
+        let speed = r.dir().length();
+        if speed == 0. {
+            return false;
+        }
+        // march on the distance to the surface from whichever side the
+        // ray starts on, going by the direction for rays starting on it
+        let p = r.point_at_parameter(start);
+        let d = self.sdf.distance(p);
+        let inside = if start > t_min {
+            // came in through the bounds, which the shape is within
+            false
+        } else if d.abs() < HIT_EPSILON {
+            Vec3::dot(&self.normal(p), &r.dir()) < 0.
+        } else {
+            d < 0.
+        };
+        let mut t = start;
+        let mut last = f32::INFINITY;
+        let mut hit = false;
+        for step in 0..MAX_STEPS {
+            let d = self.sdf.distance(r.point_at_parameter(t));
+            let d = if inside { -d } else { d };
+            // only count closing in on the surface, not leaving the one
+            // the ray starts on
+            if d < HIT_EPSILON && d <= last && step > 0 {
+                hit = true;
+                break;
+            }
+            last = d;
+            t += d.max(HIT_EPSILON) / speed;
+            if t > end {
+                break;
+            }
+        }
+        if !hit || t <= t_min || t >= t_max {
+            return false;
+        }
+
+        record.t = t;
+        record.p = r.point_at_parameter(t);
+        record.normal = self.normal(record.p);
+        // project the normal onto a sphere for surface coordinates
+        let (u, v) = sphere_uv(&record.normal);
+        record.u = u;
+        record.v = v;
+        record.tangent = Onb::from_w(record.normal).u();
+        record.mat = Some(self.material);
+        true
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.sdf.bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace(sdf: Sdf, origin: Point, dir: Vec3) -> Option<HitRecord> {
+        let mut record = HitRecord::default();
+        SdfObject::new(sdf, 0).hit(&Ray::new(origin, dir), 0.001, f32::MAX, &mut record).then_some(record)
+    }
+
+    #[test]
+    fn sphere_tracing_stops_at_the_surface() {
+        let hit = trace(Sdf::sphere(1.), Point::new(0., 0., -5.), Vec3::new(0., 0., 1.)).unwrap();
+        assert!((hit.t - 4.).abs() < 1e-3);
+        assert!((hit.normal - Vec3::new(0., 0., -1.)).length() < 1e-3);
+        // t is along dir, whatever its length
+        let hit = trace(Sdf::sphere(1.), Point::new(0., 0., -5.), Vec3::new(0., 0., 2.)).unwrap();
+        assert!((hit.t - 2.).abs() < 1e-3);
+        // from inside, to where the ray leaves
+        let hit = trace(Sdf::sphere(1.), Point::new(0., 0., 0.), Vec3::new(0., 1., 0.)).unwrap();
+        assert!((hit.t - 1.).abs() < 1e-3);
+        assert!((hit.normal - Vec3::new(0., 1., 0.)).length() < 1e-3);
+        assert!(trace(Sdf::sphere(1.), Point::new(0., 1.5, -5.), Vec3::new(0., 0., 1.)).is_none());
+    }
+
+    #[test]
+    fn combined_shapes_trace_to_their_surfaces() {
+        let moved = Sdf::sphere(1.).scale(2.).translate(Vec3::new(0., 0., 1.));
+        let hit = trace(moved, Point::new(0., 0., -5.), Vec3::new(0., 0., 1.)).unwrap();
+        assert!((hit.t - 4.).abs() < 1e-3);
+        // a ball with a hole down through it, traced down the hole, beside
+        // it and from the side
+        let drilled = || Sdf::sphere(1.).subtract(Sdf::cylinder(0.5, 4.));
+        assert!(trace(drilled(), Point::new(0.2, 5., 0.), Vec3::new(0., -1., 0.)).is_none());
+        let hit = trace(drilled(), Point::new(0.75, 5., 0.), Vec3::new(0., -1., 0.)).unwrap();
+        assert!((hit.t - (5. - 0.4375f32.sqrt())).abs() < 1e-3);
+        let hit = trace(drilled(), Point::new(-5., 0., 0.), Vec3::new(1., 0., 0.)).unwrap();
+        assert!((hit.t - 4.).abs() < 1e-3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn scale_rejects_mirroring() {
+        let _ = Sdf::sphere(1.).scale(-1.);
+    }
+}
+
+}
@@ -0,0 +1,237 @@
+pub mod module {
+
+use crate::types::module::*;
+use crate::vec3::module::*;
+use crate::environment::module::*;
+use crate::spectrum::module::*;
+
+use std::f32::consts;
+
+// angular radius of the sun seen from the earth
+const SUN_RADIUS: f32 = 0.00465;
+// luminance of the sun's disk above the atmosphere, cd/m^2
+const SUN_LUMINANCE: f32 = 1.6e9;
+// wavelengths (micrometers) the sun's attenuation is evaluated at for
+// the red, green and blue channels
+const SUN_WAVELENGTHS: [f32; 3] = [0.65, 0.57, 0.475];
+
+// size of the image the sky is baked into for importance sampling
+const SAMPLE_WIDTH: usize = 128;
+const SAMPLE_HEIGHT: usize = 64;
+
+// Perez et al.'s sky luminance distribution, relative to the zenith.
+// theta: angle from the zenith, gamma: angle from the sun
+fn perez(coeffs: &[f32; 5], cos_theta: f32, gamma: f32) -> f32 {
+    let [a, b, c, d, e] = *coeffs;
+    let cos_gamma = gamma.cos();
+    (1. + a * (b / cos_theta).exp()) * (1. + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+}
+
+fn xyy_to_rgb(x: f32, y: f32, lum: f32) -> Color {
+    if y <= 0. {
+        return Color::init();
+    }
+    xyz_to_rgb(Vec3::new(x * lum / y, lum, (1. - x - y) * lum / y))
+}
+
+// the sky without the sun or the intensity applied
+struct PerezSky {
+    sun_dir: Vec3,
+    zenith: [f32; 3], // Y (cd/m^2), x, y
+    coeffs: [[f32; 5]; 3]
+}
+
+impl PerezSky {
+    fn radiance(&self, dir: Vec3) -> Color {
+        let d = Vec3::unit_vector(dir);
+        if d.y() <= 0. {
+            return Color::init();
+        }
+        let gamma = Vec3::dot(&d, &self.sun_dir).clamp(-1., 1.).acos();
+        let theta_s = self.sun_dir.y().clamp(-1., 1.).acos();
+        // keep exp(B / cos theta) finite at the horizon
+        let cos_theta = d.y().max(0.01);
+        let value = |i: usize| self.zenith[i] * perez(&self.coeffs[i], cos_theta, gamma)
+            / perez(&self.coeffs[i], 1., theta_s);
+        let rgb = xyy_to_rgb(value(1), value(2), value(0));
+        Color::new(rgb.r().max(0.), rgb.g().max(0.), rgb.b().max(0.))
+    }
+}
+
+// Daylight sky from Preetham, Shirley and Smits' analytic model, "A
+// Practical Analytic Model for Daylight" (1999), with the sun as a small
+// disk whose color comes from the atmosphere's Rayleigh and aerosol
+// attenuation along the path to it.  Radiance is in cd/m^2 times the
+// intensity.  The ground below the horizon is black.
+pub struct PhysicalSky {
+    sun_dir: Vec3,
+    sun_radiance: Color,
+    perez: PerezSky,
+    intensity: f32,
+    sun_weight: f32, // chance sample() picks the sun over the sky
+    sky: EnvironmentMap // baked without the sun, only used for sampling
+}
+
+impl PhysicalSky {
+    // elevation: of the sun above the horizon in degrees
+    // azimuth: of the sun in degrees, 0 looks down -z and 90 down +x
+    // turbidity: haziness of the atmosphere, 2 is very clear, 10 hazy
+    // intensity: scales the radiance of the sky and sun
+    // None for a sun below the horizon or past the zenith, which the
+    // model isn't fitted for, or a turbidity the sky blows up at
+    pub fn new(elevation: f32, azimuth: f32, turbidity: f32, intensity: f32) -> Option<PhysicalSky> {
+        if !(0. ..=90.).contains(&elevation) {
+            return None;
+        }
+        // just above the horizon, where the sun is still in front of it
+        let elevation = elevation.to_radians().max(0.001);
+        let azimuth = azimuth.to_radians();
+        let sun_dir = Vec3::new(elevation.cos() * azimuth.sin(),
+                                elevation.sin(),
+                                -elevation.cos() * azimuth.cos());
+        let t = turbidity;
+        let theta_s = consts::FRAC_PI_2 - elevation;
+
+        let chi = (4. / 9. - t / 120.) * (consts::PI - 2. * theta_s);
+        let zenith_y = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192) * 1000.;
+        let cubic = |c: [f32; 4]| ((c[0] * theta_s + c[1]) * theta_s + c[2]) * theta_s + c[3];
+        let zenith_x = t * t * cubic([0.00166, -0.00375, 0.00209, 0.])
+            + t * cubic([-0.02903, 0.06377, -0.03202, 0.00394])
+            + cubic([0.11693, -0.21196, 0.06052, 0.25886]);
+        let zenith_yc = t * t * cubic([0.00275, -0.00610, 0.00317, 0.])
+            + t * cubic([-0.04214, 0.08970, -0.04153, 0.00516])
+            + cubic([0.15346, -0.26756, 0.06670, 0.26688]);
+
+        let coeffs = [
+            [0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251,
+             0.1206 * t - 2.5771, -0.0670 * t + 0.3703],
+            [-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125,
+             -0.0641 * t - 0.8989, -0.0033 * t + 0.0452],
+            [-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102,
+             -0.0441 * t - 1.6537, -0.0109 * t + 0.0529]
+        ];
+
+        // relative optical mass of the air towards the sun (Kasten)
+        let mass = 1. / (theta_s.cos() + 0.15 * (93.885 - theta_s.to_degrees()).powf(-1.253));
+        let beta = 0.04608 * t - 0.04586; // Angstrom turbidity
+        let transmittance = |lambda: f32| {
+            let rayleigh = (-0.008735 * lambda.powf(-4.08) * mass).exp();
+            let aerosol = (-beta * lambda.powf(-1.3) * mass).exp();
+            rayleigh * aerosol
+        };
+        let sun_radiance = SUN_LUMINANCE * Color::new(transmittance(SUN_WAVELENGTHS[0]),
+                                                      transmittance(SUN_WAVELENGTHS[1]),
+                                                      transmittance(SUN_WAVELENGTHS[2]));
+
+        let perez = PerezSky { sun_dir, zenith: [zenith_y, zenith_x, zenith_yc], coeffs };
+
+        // bake the sky so its bright regions (around the sun, the
+        // horizon) get sampled more, and split samples between it and
+        // the sun by the power each sends
+        let img: FloatImage = (0..SAMPLE_HEIGHT).map(|row| (0..SAMPLE_WIDTH).map(|col| {
+            let phi = ((col as f32 + 0.5) / SAMPLE_WIDTH as f32 - 0.5) * 2. * consts::PI;
+            let theta = (row as f32 + 0.5) / SAMPLE_HEIGHT as f32 * consts::PI;
+            perez.radiance(Vec3::new(theta.sin() * phi.sin(), theta.cos(), -theta.sin() * phi.cos()))
+        }).collect()).collect();
+        let sky_power: f32 = img.iter().enumerate().map(|(row, cols)| {
+            let theta = (row as f32 + 0.5) / SAMPLE_HEIGHT as f32 * consts::PI;
+            let pixel_angle = theta.sin() * 2. * consts::PI * consts::PI
+                / (SAMPLE_WIDTH * SAMPLE_HEIGHT) as f32;
+            cols.iter().map(|c| luminance(*c) * pixel_angle).sum::<f32>()
+        }).sum();
+        let sun_power = luminance(sun_radiance) * PhysicalSky::sun_solid_angle();
+        let sun_weight = sun_power / (sun_power + sky_power);
+        if !sun_weight.is_finite() {
+            return None;
+        }
+        Some(PhysicalSky {
+            sun_dir,
+            sun_radiance,
+            perez,
+            intensity,
+            sun_weight,
+            sky: EnvironmentMap::new(img, 0., 1.)?
+        })
+    }
+
+    pub fn sun_direction(&self) -> Vec3 {
+        self.sun_dir
+    }
+
+    fn sun_solid_angle() -> f32 {
+        // 2 pi (1 - cos r), written to keep precision for tiny r
+        let half = (0.5 * SUN_RADIUS).sin();
+        4. * consts::PI * half * half
+    }
+
+    // dir within the sun's disk, tested on the sine of the angle between
+    // them as the cosine is too close to 1 for f32
+    fn in_sun(&self, dir: Vec3) -> bool {
+        let d = Vec3::unit_vector(dir);
+        let sin_radius = SUN_RADIUS.sin();
+        Vec3::dot(&d, &self.sun_dir) > 0. &&
+            Vec3::cross(&d, &self.sun_dir).squared_length() <= sin_radius * sin_radius
+    }
+
+    pub fn radiance(&self, dir: Vec3) -> Color {
+        let sun = if self.in_sun(dir) { self.sun_radiance } else { Color::init() };
+        self.intensity * (self.perez.radiance(dir) + sun)
+    }
+
+    // Picks a direction from either the sun's disk or the baked sky.
+    // Returns the world direction and its pdf per unit solid angle.
+    pub fn sample(&self, u1: f32, u2: f32) -> (Vec3, f32) {
+        let dir = if u1 < self.sun_weight {
+            // uniform over the cone the sun covers
+            let u1 = u1 / self.sun_weight;
+            let half = (0.5 * SUN_RADIUS).sin();
+            let one_minus_cos = u1 * 2. * half * half;
+            let sin_theta = (one_minus_cos * (2. - one_minus_cos)).sqrt();
+            let phi = 2. * consts::PI * u2;
+            let frame = Onb::from_w(self.sun_dir);
+            frame.local(Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), 1. - one_minus_cos))
+        }
+        else {
+            let u1 = ((u1 - self.sun_weight) / (1. - self.sun_weight)).min(1. - f32::EPSILON);
+            self.sky.sample(u1, u2).0
+        };
+        (dir, self.pdf(dir))
+    }
+
+    // pdf per unit solid angle of sample() returning dir
+    pub fn pdf(&self, dir: Vec3) -> f32 {
+        let sun = if self.in_sun(dir) { 1. / PhysicalSky::sun_solid_angle() } else { 0. };
+        self.sun_weight * sun + (1. - self.sun_weight) * self.sky.pdf(dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_suns_below_the_horizon() {
+        assert!(PhysicalSky::new(-5., 0., 3., 1.).is_none());
+        assert!(PhysicalSky::new(95., 0., 3., 1.).is_none());
+        let low = PhysicalSky::new(0., 90., 3., 1.).unwrap();
+        let d = low.sun_direction();
+        assert!(d.y() > 0. && d.y() < 0.01 && d.x() > 0.99);
+        let high = PhysicalSky::new(90., 0., 3., 1.).unwrap();
+        assert!((high.sun_direction().y() - 1.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn sun_outshines_the_sky_and_samples_match_the_pdf() {
+        let sky = PhysicalSky::new(40., 30., 3., 1.).unwrap();
+        let sun = sky.radiance(sky.sun_direction());
+        let zenith = sky.radiance(Vec3::new(0., 1., 0.));
+        assert!(luminance(sun) > 1000. * luminance(zenith));
+        for i in 0..32 {
+            let (dir, pdf) = sky.sample((i as f32 + 0.5) / 32., 0.37);
+            assert!(pdf > 0.);
+            assert_eq!(pdf, sky.pdf(dir));
+        }
+    }
+}
+
+}
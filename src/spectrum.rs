@@ -0,0 +1,196 @@
+pub mod module {
+
+use crate::types::module::*;
+use crate::vec3::module::*;
+
+use std::sync::OnceLock;
+
+// range of wavelengths (nm) rendered in spectral mode
+pub const LAMBDA_MIN: f32 = 380.;
+pub const LAMBDA_MAX: f32 = 780.;
+
+// where dispersive materials take their index of refraction from in RGB
+// mode, the helium d line
+pub const LAMBDA_D: f32 = 587.6;
+
+// Picks the hero wavelength uniformly from u and two more spread evenly
+// around the range from it (Wilkie et al. 2014, "Hero Wavelength
+// Spectral Sampling").  Each has pdf 1 / (LAMBDA_MAX - LAMBDA_MIN).
+pub fn sample_wavelengths(u: f32) -> Vec3 {
+    let range = LAMBDA_MAX - LAMBDA_MIN;
+    let lambda = |offset: f32| {
+        let l = u * range + offset * range / 3.;
+        LAMBDA_MIN + if l >= range { l - range } else { l }
+    };
+    Vec3::new(lambda(0.), lambda(1.), lambda(2.))
+}
+
+// The wavelengths a path keeps after a bounce that depends on the
+// wavelength, like dispersion: only the hero, repeated
+pub fn terminate_secondary(lambdas: &Vec3) -> Vec3 {
+    Vec3::new(lambdas.x(), lambdas.x(), lambdas.x())
+}
+
+pub fn is_terminated(lambdas: &Vec3) -> bool {
+    lambdas.x() == lambdas.y() && lambdas.x() == lambdas.z()
+}
+
+// piecewise gaussian used by the color matching function fits
+fn lobe(lambda: f32, mu: f32, sigma_low: f32, sigma_high: f32) -> f32 {
+    let t = (lambda - mu) / if lambda < mu { sigma_low } else { sigma_high };
+    (-0.5 * t * t).exp()
+}
+
+// CIE 1931 color matching functions, from the multi-lobe fit in Wyman,
+// Sloan and Shirley 2013, "Simple Analytic Approximations to the CIE XYZ
+// Color Matching Functions"
+pub fn cie_xyz(lambda: f32) -> Vec3 {
+    let x = 1.056 * lobe(lambda, 599.8, 37.9, 31.0) + 0.362 * lobe(lambda, 442.0, 16.0, 26.7)
+        - 0.065 * lobe(lambda, 501.1, 20.4, 26.2);
+    let y = 0.821 * lobe(lambda, 568.8, 46.9, 40.5) + 0.286 * lobe(lambda, 530.9, 16.3, 31.1);
+    let z = 1.217 * lobe(lambda, 437.0, 11.8, 36.0) + 0.681 * lobe(lambda, 459.0, 26.0, 13.8);
+    Vec3::new(x, y, z)
+}
+
+// relative spectral power of CIE illuminant D65, the white point of
+// sRGB, from 380 to 780 nm every 10 nm
+const D65: [f32; 41] = [
+    49.98, 54.65, 82.75, 91.49, 93.43, 86.68, 104.86, 117.01, 117.81, 114.86,
+    115.92, 108.81, 109.35, 107.80, 104.79, 107.69, 104.41, 104.05, 100.00, 96.33,
+    95.79, 88.69, 90.01, 89.60, 87.70, 83.29, 83.70, 80.03, 80.21, 82.28,
+    78.28, 69.72, 71.61, 74.35, 61.60, 69.89, 75.09, 63.59, 46.42, 66.81,
+    63.38
+];
+
+fn d65(lambda: f32) -> f32 {
+    let x = ((lambda - LAMBDA_MIN) / 10.).clamp(0., (D65.len() - 1) as f32);
+    let i = (x as usize).min(D65.len() - 2);
+    let t = x - i as f32;
+    (1. - t) * D65[i] + t * D65[i + 1]
+}
+
+// D65 scaled so that it has a luminance (Y) of 1
+fn normalized_d65(lambda: f32) -> f32 {
+    static SCALE: OnceLock<f32> = OnceLock::new();
+    let scale = SCALE.get_or_init(|| {
+        let steps = (LAMBDA_MAX - LAMBDA_MIN) as usize;
+        let y: f32 = (0..steps).map(|i| {
+            let lambda = LAMBDA_MIN + i as f32 + 0.5;
+            d65(lambda) * cie_xyz(lambda).y()
+        }).sum();
+        1. / y
+    });
+    d65(lambda) * scale
+}
+
+// Basis spectra for upsampling RGB from Smits 1999, "An RGB to Spectrum
+// Conversion for Reflectances", in 10 bins from 380 to 720 nm
+const SMITS_WHITE:   [f32; 10] = [1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000];
+const SMITS_CYAN:    [f32; 10] = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000];
+const SMITS_MAGENTA: [f32; 10] = [1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959];
+const SMITS_YELLOW:  [f32; 10] = [0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840];
+const SMITS_RED:     [f32; 10] = [0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149];
+const SMITS_GREEN:   [f32; 10] = [0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025];
+const SMITS_BLUE:    [f32; 10] = [1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496];
+
+// value of a smooth spectrum with rgb's color at lambda
+fn smits(rgb: Color, lambda: f32) -> f32 {
+    let bin = (((lambda - LAMBDA_MIN) / 34.) as usize).min(9);
+    let (r, g, b) = (rgb.r(), rgb.g(), rgb.b());
+    let w = SMITS_WHITE[bin];
+    if r <= g && r <= b {
+        r * w + if g <= b {
+            (g - r) * SMITS_CYAN[bin] + (b - g) * SMITS_BLUE[bin]
+        } else {
+            (b - r) * SMITS_CYAN[bin] + (g - b) * SMITS_GREEN[bin]
+        }
+    }
+    else if g <= r && g <= b {
+        g * w + if r <= b {
+            (r - g) * SMITS_MAGENTA[bin] + (b - r) * SMITS_BLUE[bin]
+        } else {
+            (b - g) * SMITS_MAGENTA[bin] + (r - b) * SMITS_RED[bin]
+        }
+    }
+    else {
+        b * w + if r <= g {
+            (r - b) * SMITS_YELLOW[bin] + (g - r) * SMITS_GREEN[bin]
+        } else {
+            (g - b) * SMITS_YELLOW[bin] + (r - g) * SMITS_RED[bin]
+        }
+    }
+}
+
+// A reflectance (or any other ratio, like an attenuation) given in RGB,
+// at each of the wavelengths
+pub fn reflectance(rgb: Color, lambdas: &Vec3) -> Vec3 {
+    Vec3::new(smits(rgb, lambdas.x()), smits(rgb, lambdas.y()), smits(rgb, lambdas.z()))
+}
+
+// Light given in RGB at each of the wavelengths.  White is D65, so it
+// comes back out as white.
+pub fn illuminant(rgb: Color, lambdas: &Vec3) -> Vec3 {
+    let value = |lambda: f32| smits(rgb, lambda) * normalized_d65(lambda);
+    Vec3::new(value(lambdas.x()), value(lambdas.y()), value(lambdas.z()))
+}
+
+// XYZ -> linear sRGB
+pub fn xyz_to_rgb(xyz: Vec3) -> Color {
+    let (x, y, z) = (xyz.x(), xyz.y(), xyz.z());
+    Color::new( 3.2406 * x - 1.5372 * y - 0.4986 * z,
+               -0.9689 * x + 1.8758 * y + 0.0415 * z,
+                0.0557 * x - 0.2040 * y + 1.0570 * z)
+}
+
+// Estimates the color of radiance sampled at sample_wavelengths'
+// wavelengths, in linear sRGB
+pub fn to_rgb(values: Vec3, lambdas: &Vec3) -> Color {
+    let inv_pdf = LAMBDA_MAX - LAMBDA_MIN;
+    let xyz = values.x() * cie_xyz(lambdas.x()) +
+              values.y() * cie_xyz(lambdas.y()) +
+              values.z() * cie_xyz(lambdas.z());
+    xyz_to_rgb(xyz * (inv_pdf / 3.))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wavelengths_cover_the_range_a_third_apart() {
+        for u in [0., 0.2, 0.5, 0.9, 1. - f32::EPSILON] {
+            let l = sample_wavelengths(u);
+            for lambda in [l.x(), l.y(), l.z()] {
+                assert!((LAMBDA_MIN..LAMBDA_MAX).contains(&lambda), "{}", lambda);
+            }
+            let gap = |a: f32, b: f32| (b - a).rem_euclid(LAMBDA_MAX - LAMBDA_MIN);
+            assert!((gap(l.x(), l.y()) - 400. / 3.).abs() < 1e-3);
+            assert!((gap(l.y(), l.z()) - 400. / 3.).abs() < 1e-3);
+        }
+        let hero = terminate_secondary(&sample_wavelengths(0.3));
+        assert!(is_terminated(&hero) && !is_terminated(&sample_wavelengths(0.3)));
+    }
+
+    // RGB light turned into a spectrum and seen at stratified
+    // wavelengths comes back as the same color
+    #[test]
+    fn illuminants_round_trip_through_spectra() {
+        let n = 4000;
+        for rgb in [Color::new(1., 1., 1.), Color::new(0.25, 0.25, 0.25), Color::new(0.8, 0.1, 0.1),
+                    Color::new(0.1, 0.7, 0.2), Color::new(0.1, 0.2, 0.9), Color::new(0.9, 0.6, 0.3)] {
+            let mut sum = Color::init();
+            for i in 0..n {
+                let lambdas = sample_wavelengths((i as f32 + 0.5) / n as f32);
+                sum += to_rgb(illuminant(rgb, &lambdas), &lambdas);
+            }
+            let back = sum / n as f32;
+            // Smits' spectra are only exact for grays
+            let tolerance = if rgb.r() == rgb.b() { 0.002 } else { 0.05 };
+            for k in 0..3 {
+                assert!((back[k] - rgb[k]).abs() < tolerance, "{:?} came back as {:?}", rgb, back);
+            }
+        }
+    }
+}
+
+}
@@ -0,0 +1,119 @@
+pub mod module {
+
+use crate::types::module::*;
+use crate::perlin::module::*;
+use crate::ppm::module::*;
+
+pub trait Texture {
+    // u, v: surface coordinates in [0, 1], p: hit location
+    fn value(&self, u: f32, v: f32, p: &Point) -> Color;
+}
+
+pub struct ConstantTexture {
+    color: Color
+}
+
+impl ConstantTexture {
+    pub fn new(color: Color) -> ConstantTexture {
+        ConstantTexture { color }
+    }
+}
+
+impl Texture for ConstantTexture {
+    fn value(&self, _: f32, _: f32, _: &Point) -> Color {
+        self.color
+    }
+}
+
+// 3D checker pattern alternating between two textures
+pub struct CheckerTexture {
+    odd: Box<dyn Texture>,
+    even: Box<dyn Texture>,
+    scale: f32
+}
+
+impl CheckerTexture {
+    // scale: number of checks per world unit (times pi)
+    pub fn new(odd: Box<dyn Texture>, even: Box<dyn Texture>, scale: f32) -> CheckerTexture {
+        CheckerTexture { odd, even, scale }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f32, v: f32, p: &Point) -> Color {
+        let sines = (self.scale * p.x()).sin() *
+                    (self.scale * p.y()).sin() *
+                    (self.scale * p.z()).sin();
+        if sines < 0. {
+            self.odd.value(u, v, p)
+        }
+        else {
+            self.even.value(u, v, p)
+        }
+    }
+}
+
+// marble-like pattern driven by perlin turbulence
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f32
+}
+
+impl NoiseTexture {
+    pub fn new(scale: f32) -> NoiseTexture {
+        NoiseTexture { noise: Perlin::new(), scale }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _: f32, _: f32, p: &Point) -> Color {
+        let phase = self.scale * p.z() + 10. * self.noise.turb(p, 7);
+        Color::new(1.,1.,1.) * 0.5 * (1. + phase.sin())
+    }
+}
+
+// An image over the surface's u, v coordinates, bilinearly filtered and
+// repeating outside [0, 1].  Values are used as read, so images meant
+// as colors should be stored linear.
+pub struct ImageTexture {
+    img: FloatImage, // img[row][col], row 0 at v = 1
+    width: usize,
+    height: usize
+}
+
+impl ImageTexture {
+    pub fn new(img: FloatImage) -> ImageTexture {
+        let height = img.len();
+        let width = img.first().map_or(0, |row| row.len());
+        ImageTexture { img, width, height }
+    }
+
+    // a PPM or PGM image
+    pub fn load(path: &str) -> std::io::Result<ImageTexture> {
+        Ok(ImageTexture::new(read_ppm(path)?))
+    }
+
+    fn texel(&self, col: i64, row: i64) -> Color {
+        let col = col.rem_euclid(self.width as i64) as usize;
+        let row = row.rem_euclid(self.height as i64) as usize;
+        self.img[row][col]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f32, v: f32, _: &Point) -> Color {
+        if self.width == 0 || self.height == 0 {
+            return Color::init();
+        }
+        // texel centers sit at half integers
+        let x = u * self.width as f32 - 0.5;
+        let y = (1. - v) * self.height as f32 - 0.5;
+        let (col, row) = (x.floor(), y.floor());
+        let (tx, ty) = (x - col, y - row);
+        let (col, row) = (col as i64, row as i64);
+        (1. - ty) * ((1. - tx) * self.texel(col, row) + tx * self.texel(col + 1, row)) +
+            ty * ((1. - tx) * self.texel(col, row + 1) + tx * self.texel(col + 1, row + 1))
+    }
+}
+
+}
@@ -0,0 +1,137 @@
+pub mod module {
+
+use crate::vec3::module::*;
+use crate::ray::module::*;
+use crate::hittable::module::*;
+use crate::csg::module::*;
+
+// reverses the surface normal of the wrapped object, e.g., so the walls
+// of a room face inward
+pub struct FlipNormals {
+    obj: Box<dyn Hittable>
+}
+
+impl FlipNormals {
+    pub fn new(obj: Box<dyn Hittable>) -> FlipNormals {
+        FlipNormals { obj }
+    }
+}
+
+impl Hittable for FlipNormals {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        if self.obj.hit(r, t_min, t_max, record) {
+            record.normal = -record.normal;
+            return true;
+        }
+        false
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.obj.bounding_box()
+    }
+}
+
+// moves the wrapped object by offset, and is a Solid when it is
+pub struct Translate<H: ?Sized = dyn Hittable> {
+    obj: Box<H>,
+    offset: Vec3
+}
+
+impl<H: Hittable + ?Sized> Translate<H> {
+    pub fn new(obj: Box<H>, offset: Vec3) -> Translate<H> {
+        Translate { obj, offset }
+    }
+}
+
+impl<H: Hittable + ?Sized> Hittable for Translate<H> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        // move the ray into object space rather than moving the object
+        let moved = Ray::new(r.origin() - self.offset, r.dir());
+        if self.obj.hit(&moved, t_min, t_max, record) {
+            record.p += self.offset;
+            return true;
+        }
+        false
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let b = self.obj.bounding_box()?;
+        Some(Aabb { min: b.min + self.offset, max: b.max + self.offset })
+    }
+}
+
+impl<S: Solid + ?Sized> Solid for Translate<S> {
+    fn spans(&self, r: &Ray) -> Vec<Span> {
+        let moved = Ray::new(r.origin() - self.offset, r.dir());
+        let mut spans = self.obj.spans(&moved);
+        for span in spans.iter_mut() {
+            span.enter.p += self.offset;
+            span.exit.p += self.offset;
+        }
+        spans
+    }
+}
+
+// rotates the wrapped object about the y axis, and is a Solid when it is
+pub struct RotateY<H: ?Sized = dyn Hittable> {
+    obj: Box<H>,
+    sin_theta: f32,
+    cos_theta: f32
+}
+
+impl<H: Hittable + ?Sized> RotateY<H> {
+    // angle: in degrees
+    pub fn new(obj: Box<H>, angle: f32) -> RotateY<H> {
+        let radians = angle.to_radians();
+        RotateY { obj, sin_theta: radians.sin(), cos_theta: radians.cos() }
+    }
+
+    fn to_object(&self, v: Vec3) -> Vec3 {
+        Vec3::new(self.cos_theta * v.x() - self.sin_theta * v.z(),
+                  v.y(),
+                  self.sin_theta * v.x() + self.cos_theta * v.z())
+    }
+
+    fn to_world(&self, v: Vec3) -> Vec3 {
+        Vec3::new(self.cos_theta * v.x() + self.sin_theta * v.z(),
+                  v.y(),
+                  -self.sin_theta * v.x() + self.cos_theta * v.z())
+    }
+
+    fn record_to_world(&self, record: &mut HitRecord) {
+        record.p = self.to_world(record.p);
+        record.normal = self.to_world(record.normal);
+        record.tangent = self.to_world(record.tangent);
+    }
+}
+
+impl<H: Hittable + ?Sized> Hittable for RotateY<H> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, record: &mut HitRecord) -> bool {
+        let rotated = Ray::new(self.to_object(r.origin()), self.to_object(r.dir()));
+        if self.obj.hit(&rotated, t_min, t_max, record) {
+            self.record_to_world(record);
+            return true;
+        }
+        false
+    }
+
+    // the box around the rotated corners of the object's box
+    fn bounding_box(&self) -> Option<Aabb> {
+        let corners = self.obj.bounding_box()?.corners().map(|c| self.to_world(c));
+        Some(corners.iter().fold(Aabb::new(corners[0], corners[0]), |b, c| b.union(&Aabb::new(*c, *c))))
+    }
+}
+
+impl<S: Solid + ?Sized> Solid for RotateY<S> {
+    fn spans(&self, r: &Ray) -> Vec<Span> {
+        let rotated = Ray::new(self.to_object(r.origin()), self.to_object(r.dir()));
+        let mut spans = self.obj.spans(&rotated);
+        for span in spans.iter_mut() {
+            self.record_to_world(&mut span.enter);
+            self.record_to_world(&mut span.exit);
+        }
+        spans
+    }
+}
+
+}
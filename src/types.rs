@@ -6,6 +6,12 @@ use crate::vec3::module::*;
 pub type PPMColor = (u8, u8, u8);
 pub type Color = Vec3;
 pub type Image = Vec<Vec<PPMColor>>;
+pub type FloatImage = Vec<Vec<Color>>; // linear radiance
 pub type Point = Vec3;
 
+// relative luminance of a linear sRGB color
+pub fn luminance(c: Color) -> f32 {
+    0.2126 * c.r() + 0.7152 * c.g() + 0.0722 * c.b()
+}
+
 }
\ No newline at end of file
@@ -55,6 +55,58 @@ impl Vec3 {
     }
 }
 
+// orthonormal basis used to move directions in and out of a local
+// frame where w is the z axis (usually the surface normal)
+#[derive(Copy, Clone, Debug)]
+pub struct Onb {
+    axis: [Vec3; 3]
+}
+
+impl Onb {
+    // n: need not be normalized
+    pub fn from_w(n: Vec3) -> Onb {
+        // branchless construction from Duff et al. 2017,
+        // "Building an Orthonormal Basis, Revisited"
+        let w = Vec3::unit_vector(n);
+        let sign = 1f32.copysign(w.z());
+        let a = -1. / (sign + w.z());
+        let b = w.x() * w.y() * a;
+        let u = Vec3::new(1. + sign * w.x() * w.x() * a, sign * b, -sign * w.x());
+        let v = Vec3::new(b, sign + w.y() * w.y() * a, -w.y());
+        Onb { axis: [u, v, w] }
+    }
+
+    // n: the w axis, t: a direction to line the u axis up with as
+    // closely as possible.  Neither need be normalized, and t may be zero
+    // or parallel to n, in which case u is arbitrary.
+    pub fn from_wu(n: Vec3, t: Vec3) -> Onb {
+        let w = Vec3::unit_vector(n);
+        let u = t - Vec3::dot(&t, &w) * w;
+        let len = u.length();
+        if len <= 1e-6 * t.length() {
+            return Onb::from_w(n);
+        }
+        let u = u / len;
+        Onb { axis: [u, Vec3::cross(&w, &u), w] }
+    }
+
+    pub fn u(&self) -> Vec3 { self.axis[0] }
+    pub fn v(&self) -> Vec3 { self.axis[1] }
+    pub fn w(&self) -> Vec3 { self.axis[2] }
+
+    // local frame -> world
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        a.x() * self.axis[0] + a.y() * self.axis[1] + a.z() * self.axis[2]
+    }
+
+    // world -> local frame
+    pub fn to_local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(Vec3::dot(&a, &self.axis[0]),
+                  Vec3::dot(&a, &self.axis[1]),
+                  Vec3::dot(&a, &self.axis[2]))
+    }
+}
+
 impl ops::AddAssign for Vec3 {
     fn add_assign(&mut self, rhs: Vec3) {
         self.e[0] += rhs.e[0];